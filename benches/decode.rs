@@ -0,0 +1,187 @@
+use amf::options::{DecodeOptions, EncodeOptions};
+use amf::{amf0, amf3, Pair, Value, ValueRef, Version};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+fn encode(value: Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    value.write_to(&mut buf).unwrap();
+    buf
+}
+
+fn decode_amf0_ecma_array(c: &mut Criterion) {
+    let input = include_bytes!("../src/testdata/amf0-ecma-ordinal-array.bin");
+    c.bench_function("decode_amf0_ecma_array", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(input)[..], Version::Amf0).unwrap())
+    });
+}
+
+fn decode_large_primitive_arrays(c: &mut Criterion) {
+    let amf0_input = encode(Value::Amf0(amf0::array(
+        (0..10_000).map(amf0::number).collect(),
+    )));
+    c.bench_function("decode_amf0_large_strict_array", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&amf0_input)[..], Version::Amf0).unwrap())
+    });
+
+    let amf3_input = encode(Value::Amf3(amf3::Value::Array {
+        assoc_entries: Vec::new(),
+        dense_entries: (0..10_000).map(amf3::Value::Integer).collect(),
+    }));
+    c.bench_function("decode_amf3_large_dense_array", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&amf3_input)[..], Version::Amf3).unwrap())
+    });
+}
+
+fn decode_repeated_keys(c: &mut Criterion) {
+    // Like a stream of `onStatus` info objects.
+    let input = encode(Value::Amf0(amf0::array(
+        (0..1_000)
+            .map(|_| {
+                amf0::object(
+                    [
+                        ("code", amf0::string("NetStream.Play.Start")),
+                        ("level", amf0::string("status")),
+                        ("description", amf0::string("Started playing.")),
+                    ]
+                    .into_iter(),
+                )
+            })
+            .collect(),
+    )));
+    c.bench_function("decode_amf0_repeated_keys", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&input)[..], Version::Amf0).unwrap())
+    });
+    // Copies the repeated keys and values from the interned strings.
+    let mut options = DecodeOptions::default();
+    options.intern_strings = true;
+    c.bench_function("decode_amf0_repeated_keys_interned", |b| {
+        b.iter(|| {
+            amf0::Decoder::with_options(black_box(&input[..]), options.clone())
+                .decode()
+                .unwrap()
+        })
+    });
+    // Reuses the strings and the entries of the previously decoded value.
+    let mut value = amf0::Value::Null;
+    c.bench_function("decode_amf0_repeated_keys_into", |b| {
+        b.iter(|| {
+            amf0::Decoder::new(black_box(&input[..]))
+                .decode_into(&mut value)
+                .unwrap()
+        })
+    });
+    // Borrows the keys from the input instead of allocating a `String` for each of them.
+    c.bench_function("decode_amf0_repeated_keys_ref", |b| {
+        b.iter(|| ValueRef::read_from_slice(black_box(&input), Version::Amf0).unwrap())
+    });
+
+    // Repeated strings which take longer to validate than to look up.
+    let input = encode(Value::Amf0(amf0::array(
+        (0..1_000)
+            .map(|_| amf0::string("あいうえお".repeat(16)))
+            .collect(),
+    )));
+    c.bench_function("decode_amf0_repeated_strings", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&input)[..], Version::Amf0).unwrap())
+    });
+    c.bench_function("decode_amf0_repeated_strings_interned", |b| {
+        b.iter(|| {
+            amf0::Decoder::with_options(black_box(&input[..]), options.clone())
+                .decode()
+                .unwrap()
+        })
+    });
+}
+
+fn decode_deeply_nested_objects(c: &mut Criterion) {
+    let amf0_input = encode(Value::Amf0((0..200).fold(amf0::Value::Null, |v, _| {
+        amf0::object([("child", v)].into_iter())
+    })));
+    c.bench_function("decode_amf0_deeply_nested_object", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&amf0_input)[..], Version::Amf0).unwrap())
+    });
+
+    let amf3_input = encode(Value::Amf3((0..200).fold(amf3::Value::Null, |v, _| {
+        amf3::Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: vec![Pair {
+                key: "child".to_string(),
+                value: v,
+            }],
+        }
+    })));
+    c.bench_function("decode_amf3_deeply_nested_object", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&amf3_input)[..], Version::Amf3).unwrap())
+    });
+}
+
+fn decode_typed_object_vector(c: &mut Criterion) {
+    let point = |i: i32| amf3::Value::Object {
+        class_name: Some("com.example.Point".to_string()),
+        sealed_count: 2,
+        entries: vec![
+            Pair {
+                key: "x".to_string(),
+                value: amf3::Value::Integer(i),
+            },
+            Pair {
+                key: "y".to_string(),
+                value: amf3::Value::Double(f64::from(i) / 2.0),
+            },
+        ],
+    };
+    let vector = amf3::Value::object_vector(
+        Some("com.example.Point".to_string()),
+        (0..1_000).map(point),
+        false,
+    );
+    // The traits (and the member names) are sent only once and referred to after that.
+    let mut options = EncodeOptions::default();
+    options.use_string_references = true;
+    options.use_trait_references = true;
+    let mut input = Vec::new();
+    vector.write_to_with_options(&mut input, &options).unwrap();
+    c.bench_function("decode_amf3_typed_object_vector", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&input)[..], Version::Amf3).unwrap())
+    });
+}
+
+fn decode_string_heavy_messages(c: &mut Criterion) {
+    // Like an RTMP `connect` command with long URLs.
+    let strings = (0..100)
+        .map(|i| {
+            format!(
+                "rtmp://example.com/live/stream-{i}?token={}",
+                "x".repeat(64)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let amf0_input = encode(Value::Amf0(amf0::array(
+        strings.iter().cloned().map(amf0::string).collect(),
+    )));
+    c.bench_function("decode_amf0_string_heavy_message", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&amf0_input)[..], Version::Amf0).unwrap())
+    });
+
+    let amf3_input = encode(Value::Amf3(amf3::Value::Array {
+        assoc_entries: Vec::new(),
+        dense_entries: strings.into_iter().map(amf3::Value::String).collect(),
+    }));
+    c.bench_function("decode_amf3_string_heavy_message", |b| {
+        b.iter(|| Value::read_from(&mut &black_box(&amf3_input)[..], Version::Amf3).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    decode_amf0_ecma_array,
+    decode_large_primitive_arrays,
+    decode_repeated_keys,
+    decode_deeply_nested_objects,
+    decode_typed_object_vector,
+    decode_string_heavy_messages
+);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+use amf::{amf3, Value, Version};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+const LEN: usize = 50_000;
+
+fn encode(value: &amf3::Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    value.write_to(&mut buf).unwrap();
+    buf
+}
+
+fn vectors(is_fixed: bool) -> [(&'static str, amf3::Value); 2] {
+    [
+        (
+            "int_vector",
+            amf3::Value::int_vector((0..LEN as i32).map(|i| i - 25_000), is_fixed),
+        ),
+        (
+            "double_vector",
+            amf3::Value::double_vector((0..LEN).map(|i| i as f64 / 3.0), is_fixed),
+        ),
+    ]
+}
+
+fn decode_numeric_vectors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_amf3_vector");
+    group.throughput(Throughput::Elements(LEN as u64));
+    for is_fixed in [true, false] {
+        for (name, value) in vectors(is_fixed) {
+            let input = encode(&value);
+            let id = BenchmarkId::new(name, if is_fixed { "fixed" } else { "variable" });
+            group.bench_with_input(id, &input, |b, input| {
+                b.iter(|| Value::read_from(&mut &black_box(input)[..], Version::Amf3).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn encode_numeric_vectors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_amf3_vector");
+    group.throughput(Throughput::Elements(LEN as u64));
+    for is_fixed in [true, false] {
+        for (name, value) in vectors(is_fixed) {
+            let mut buf = Vec::with_capacity(encode(&value).len());
+            let id = BenchmarkId::new(name, if is_fixed { "fixed" } else { "variable" });
+            group.bench_with_input(id, &value, |b, value| {
+                b.iter(|| {
+                    buf.clear();
+                    black_box(value).write_to(&mut buf).unwrap();
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode_numeric_vectors, encode_numeric_vectors);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+#![no_main]
+use amf::{Value, ValueRef, Version};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for version in [Version::Amf0, Version::Amf3] {
+        let _ = ValueRef::read_from_slice(data, version);
+
+        // Decoded values are encodable unless they violate an invariant which only the encoder checks
+        // (e.g., a non-object element of an AMF3 `ObjectVector`).
+        if let Ok(value) = Value::read_from(&mut &data[..], version) {
+            if value.validate().is_ok() {
+                value.write_to(&mut Vec::new()).unwrap();
+            }
+        }
+    }
+});
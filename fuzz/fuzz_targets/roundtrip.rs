@@ -0,0 +1,8 @@
+#![no_main]
+use amf::Value;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|value: Value| {
+    // Generated values are well-formed, so they survive a round trip unchanged.
+    amf::testing::assert_roundtrip(&value, value.version());
+});
@@ -1,19 +1,76 @@
 use super::marker;
 use super::Value;
 use crate::amf3;
-use crate::error::DecodeError;
-use crate::{DecodeResult, Pair};
-use byteorder::{BigEndian, ReadBytesExt};
-use std::io;
-use std::time;
+use crate::error::{DecodeError, StringContext, Warning};
+use crate::intern::{self, Interner};
+use crate::io;
+use crate::options::{DecodeLimits, DecodeOptions};
+use crate::registry::ClassRegistry;
+use crate::{
+    DecodeResult, Pair, MAX_DECODE_DEPTH, MAX_PREALLOCATED_BYTES, MAX_PREALLOCATED_ENTRIES,
+};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::any::Any;
+use core::cmp;
+use core::mem;
+use core::str;
+use core::time;
 
 /// AMF0 decoder.
+///
+/// `S` is the type of the sink which large binary payloads are streamed into (see `with_byte_array_sink`).
 #[derive(Debug)]
-pub struct Decoder<R> {
+pub struct Decoder<R, S = io::Sink> {
     inner: R,
+    position: usize,
+    depth: usize,
     complexes: Vec<Value>,
+    scratch: Vec<u8>,
+    interner: Interner,
+    options: DecodeOptions,
+    amf3_tables: amf3::ReferenceTables,
+    byte_array_sink: Option<S>,
+    streamed: usize,
+    warnings: Vec<Warning>,
+    allocated: usize,
 }
 impl<R> Decoder<R> {
+    /// Makes this decoder stream the bytes of long strings and AMF3 `ByteArray` values into `sink`
+    /// as they are read.
+    ///
+    /// This avoids buffering large payloads in memory.
+    /// A streamed long string is decoded as a `StreamedLongString` placeholder
+    /// which locates its bytes in the output of `sink`
+    /// (the bytes are validated as UTF-8 while being streamed),
+    /// and a byte array in an `AvmPlus` value is decoded as described in `amf3::Decoder::with_byte_array_sink`.
+    /// The bytes of consecutive payloads are written back-to-back to `sink`,
+    /// and the offsets of the placeholders are counted across `decode` calls.
+    pub fn with_byte_array_sink<S>(self, sink: S) -> Decoder<R, S> {
+        Decoder {
+            inner: self.inner,
+            position: self.position,
+            depth: self.depth,
+            complexes: self.complexes,
+            scratch: self.scratch,
+            interner: self.interner,
+            options: self.options,
+            amf3_tables: self.amf3_tables,
+            byte_array_sink: Some(sink),
+            streamed: 0,
+            warnings: self.warnings,
+            allocated: self.allocated,
+        }
+    }
+}
+impl<R, S> Decoder<R, S> {
+    /// Unwraps this `Decoder`, returning the sink of the payloads (if any).
+    pub fn into_byte_array_sink(self) -> Option<S> {
+        self.byte_array_sink
+    }
+
     /// Unwraps this `Decoder`, returning the underlying reader.
     pub fn into_inner(self) -> R {
         self.inner
@@ -28,6 +85,33 @@ impl<R> Decoder<R> {
     pub fn inner_mut(&mut self) -> &mut R {
         &mut self.inner
     }
+
+    /// Returns a mutable reference to the sink of the payloads (if any).
+    pub fn byte_array_sink_mut(&mut self) -> Option<&mut S> {
+        self.byte_array_sink.as_mut()
+    }
+
+    /// Returns the number of bytes consumed by this decoder so far.
+    ///
+    /// Note that bytes read directly from the underlying reader (e.g., via `inner_mut`)
+    /// are not counted.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Takes the warnings collected so far (see `DecodeOptions::collect_warnings`).
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Replaces the limits of this decoder with `limits`.
+    ///
+    /// The new limits apply to the subsequent `decode` calls.
+    /// This is useful to decode a trusted message leniently and then untrusted ones strictly
+    /// without rebuilding the decoder (and its reference tables).
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        self.options.set_limits(limits);
+    }
 }
 impl<R> Decoder<R>
 where
@@ -35,35 +119,193 @@ where
 {
     /// Makes a new instance.
     pub fn new(inner: R) -> Self {
+        Self::with_options(inner, DecodeOptions::default())
+    }
+
+    /// Makes a new instance with the given options.
+    pub fn with_options(inner: R, options: DecodeOptions) -> Self {
         Decoder {
             inner,
+            position: 0,
+            depth: 0,
             complexes: Vec::new(),
+            scratch: Vec::new(),
+            interner: Interner::default(),
+            options,
+            amf3_tables: amf3::ReferenceTables::default(),
+            byte_array_sink: None,
+            streamed: 0,
+            warnings: Vec::new(),
+            allocated: 0,
         }
     }
-
+}
+impl<R, S> Decoder<R, S>
+where
+    R: io::Read,
+    S: io::Write,
+{
     /// Decodes a AMF0 value.
     pub fn decode(&mut self) -> DecodeResult<Value> {
+        self.allocated = 0;
         self.decode_value()
     }
 
+    /// Decodes a AMF0 value and converts it into a `T` using `registry`.
+    ///
+    /// If the value is not a typed object of the class registered for `T`,
+    /// the decoded value is returned as `Ok(Err(value))` (see `ClassRegistry::from_value`).
+    pub fn decode_with_registry<T: Any>(
+        &mut self,
+        registry: &ClassRegistry<Value>,
+    ) -> DecodeResult<Result<T, Value>> {
+        let value = self.decode()?;
+        Ok(registry.from_value(value))
+    }
+
+    /// Decodes a AMF0 value into `out`, reusing its allocations.
+    ///
+    /// This is useful for decoding a stream of similarly shaped values in a loop.
+    /// The strings, the entries of the objects and the arrays in `out` are reused
+    /// where the shapes of `out` and the decoded value match,
+    /// and the other parts of `out` are simply replaced.
+    /// The resulting `out` is equal to the value which `decode` would return.
+    ///
+    /// If an error is returned, the content of `out` is unspecified.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf0::{Decoder, Value};
+    ///
+    /// let mut buf = Vec::new();
+    /// Value::Array { entries: vec![Value::Number(1.0)] }.write_to(&mut buf).unwrap();
+    /// Value::Array { entries: vec![Value::Number(2.0)] }.write_to(&mut buf).unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&buf[..]);
+    /// let mut value = Value::Null;
+    /// decoder.decode_into(&mut value).unwrap();
+    /// assert_eq!(value, Value::Array { entries: vec![Value::Number(1.0)] });
+    /// decoder.decode_into(&mut value).unwrap();
+    /// assert_eq!(value, Value::Array { entries: vec![Value::Number(2.0)] });
+    /// ```
+    pub fn decode_into(&mut self, out: &mut Value) -> DecodeResult<()> {
+        self.allocated = 0;
+        self.decode_value_into(out)
+    }
+
+    /// Decode an AMF0 `UTF-8` string (i.e., a string with a `u16` length prefix and without a marker).
+    ///
+    /// Use this if you need to decode an AMF0 string outside of value context.
+    /// An example of this is reading names in Local Shared Object file.
+    pub fn decode_str(&mut self) -> DecodeResult<String> {
+        let len = self.read_u16()? as usize;
+        self.read_utf8(len)
+    }
+
+    /// Decode an AMF0 `UTF-8-long` string (i.e., a string with a `u32` length prefix and without a marker).
+    pub fn decode_long_str(&mut self) -> DecodeResult<String> {
+        let len = self.read_u32()? as usize;
+        self.read_utf8(len)
+    }
+
     /// Clear the reference table of this decoder.
     ///
     /// > Note that object reference indices are local to each message body.
     /// > Serializers and deserializers must reset reference indices to 0 each time a new message is processed.
     /// >
     /// > [AMF 0 Specification: 4.1.3 AMF Message](http://download.macromedia.com/pub/labs/amf/amf0_spec_121207.pdf)
+    ///
+    /// The AMF3 reference tables shared by `AVMPLUS_OBJECT` values (if enabled) are also cleared.
     pub fn clear_reference_table(&mut self) {
         self.complexes.clear();
+        self.amf3_tables = amf3::ReferenceTables::default();
     }
 
     fn decode_value(&mut self) -> DecodeResult<Value> {
-        let marker = self.inner.read_u8()?;
+        let marker = self.read_u8()?;
+        self.decode_marked_value(marker)
+    }
+    fn decode_value_into(&mut self, out: &mut Value) -> DecodeResult<()> {
+        let marker = self.read_u8()?;
+        self.decode_marked_value_into(marker, out)
+    }
+    fn decode_marked_value_into(&mut self, marker: u8, out: &mut Value) -> DecodeResult<()> {
+        match (marker, out) {
+            (marker::STRING, Value::String(s)) => {
+                let len = self.read_u16()? as usize;
+                *s = self.read_utf8_into(len, mem::take(s).into_bytes())?;
+            }
+            (marker::OBJECT, out @ Value::Object { .. }) => {
+                let mut entries = take_entries(out);
+                *out = self.decode_complex_type(|this| {
+                    this.decode_pairs_into(&mut entries)?;
+                    Ok(Value::Object {
+                        class_name: None,
+                        entries,
+                    })
+                })?;
+            }
+            (marker::TYPED_OBJECT, out @ Value::Object { .. }) => {
+                let mut class_name = match *out {
+                    Value::Object {
+                        ref mut class_name, ..
+                    } => class_name.take().unwrap_or_default(),
+                    _ => String::new(),
+                };
+                let mut entries = take_entries(out);
+                *out = self.decode_complex_type(|this| {
+                    let len = this.read_u16()? as usize;
+                    this.read_key_into(len, &mut class_name, StringContext::ClassName)?;
+                    this.decode_pairs_into(&mut entries)?;
+                    Ok(Value::Object {
+                        class_name: Some(class_name),
+                        entries,
+                    })
+                })?;
+            }
+            (marker::ECMA_ARRAY, out @ Value::EcmaArray { .. }) => {
+                let mut entries = take_entries(out);
+                *out = self.decode_complex_type(|this| {
+                    let offset = this.position;
+                    let count = this.read_u32()?;
+                    this.decode_pairs_into(&mut entries)?;
+                    let declared_count = this.check_ecma_array_count(count, entries.len(), offset);
+                    Ok(Value::EcmaArray {
+                        entries,
+                        declared_count,
+                    })
+                })?;
+            }
+            (marker::STRICT_ARRAY, out @ Value::Array { .. }) => {
+                let mut entries = match *out {
+                    Value::Array { ref mut entries } => mem::take(entries),
+                    _ => Vec::new(),
+                };
+                *out = self.decode_complex_type(|this| {
+                    let count = this.read_u32()? as usize;
+                    this.allocate_entries::<Value>(count)?;
+                    entries.truncate(count);
+                    for i in 0..count {
+                        match entries.get_mut(i) {
+                            Some(v) => this.decode_value_into(v)?,
+                            None => entries.push(this.decode_value()?),
+                        }
+                    }
+                    Ok(Value::Array { entries })
+                })?;
+            }
+            (marker, out) => *out = self.decode_marked_value(marker)?,
+        }
+        Ok(())
+    }
+    fn decode_marked_value(&mut self, marker: u8) -> DecodeResult<Value> {
+        let offset = self.position - 1;
         match marker {
             marker::NUMBER => self.decode_number(),
             marker::BOOLEAN => self.decode_boolean(),
             marker::STRING => self.decode_string(),
             marker::OBJECT => self.decode_object(),
-            marker::MOVIECLIP => Err(DecodeError::Unsupported { marker }),
+            marker::MOVIECLIP => self.decode_unsupported(marker, offset),
             marker::NULL => Ok(Value::Null),
             marker::UNDEFINED => Ok(Value::Undefined),
             marker::REFERENCE => self.decode_reference(),
@@ -72,24 +314,39 @@ where
             marker::STRICT_ARRAY => self.decode_strict_array(),
             marker::DATE => self.decode_date(),
             marker::LONG_STRING => self.decode_long_string(),
-            marker::UNSUPPORTED => Err(DecodeError::Unsupported { marker }),
-            marker::RECORDSET => Err(DecodeError::Unsupported { marker }),
+            marker::UNSUPPORTED => self.decode_unsupported(marker, offset),
+            marker::RECORDSET if self.options.decode_recordsets => self.decode_recordset(),
+            marker::RECORDSET => self.decode_unsupported(marker, offset),
             marker::XML_DOCUMENT => self.decode_xml_document(),
             marker::TYPED_OBJECT => self.decode_typed_object(),
             marker::AVMPLUS_OBJECT => self.decode_avmplus(),
-            _ => Err(DecodeError::Unknown { marker }),
+            _ => Err(DecodeError::Unknown { marker, offset }),
+        }
+    }
+    fn decode_unsupported(&mut self, marker: u8, offset: usize) -> DecodeResult<Value> {
+        if self.options.tolerate_unsupported {
+            Ok(Value::Unsupported { marker })
+        } else {
+            Err(DecodeError::Unsupported { marker, offset })
         }
     }
     fn decode_number(&mut self) -> DecodeResult<Value> {
-        let n = self.inner.read_f64::<BigEndian>()?;
+        let offset = self.position;
+        let n = self.read_f64()?;
+        self.check_finite_number(n, offset)?;
         Ok(Value::Number(n))
     }
     fn decode_boolean(&mut self) -> DecodeResult<Value> {
-        let b = self.inner.read_u8()? != 0;
+        let b = self.read_u8()? != 0;
         Ok(Value::Boolean(b))
     }
     fn decode_string(&mut self) -> DecodeResult<Value> {
-        let len = self.inner.read_u16::<BigEndian>()? as usize;
+        let len = self.read_u16()? as usize;
+        if self.options.intern_strings && len <= intern::MAX_LEN {
+            return self
+                .read_interned(len, StringContext::Value)
+                .map(Value::String);
+        }
         self.read_utf8(len).map(Value::String)
     }
     fn decode_object(&mut self) -> DecodeResult<Value> {
@@ -101,38 +358,49 @@ where
             })
         })
     }
+    // The index of a reference is a `u16`, so only the first 65536 complex values can be referred to
+    // (the later ones are always serialized in full by a conforming encoder).
     fn decode_reference(&mut self) -> DecodeResult<Value> {
-        let index = self.inner.read_u16::<BigEndian>()? as usize;
-        self.complexes
-            .get(index)
-            .ok_or(DecodeError::OutOfRangeReference { index })
-            .and_then(|v| {
-                if *v == Value::Null {
-                    Err(DecodeError::CircularReference { index })
-                } else {
-                    Ok(v.clone())
-                }
-            })
+        let index = self.read_u16()? as usize;
+        let size = match self.complexes.get(index) {
+            None => return Err(DecodeError::OutOfRangeReference { index }),
+            Some(Value::Null) => return Err(DecodeError::CircularReference { index }),
+            Some(v) => v.approx_size(),
+        };
+        self.allocate(size)?;
+        Ok(self.complexes[index].clone())
     }
     fn decode_ecma_array(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this| {
-            let _count = this.inner.read_u32::<BigEndian>()? as usize;
+            let offset = this.position;
+            let count = this.read_u32()?;
             let entries = this.decode_pairs()?;
-            Ok(Value::EcmaArray { entries })
+            let declared_count = this.check_ecma_array_count(count, entries.len(), offset);
+            Ok(Value::EcmaArray {
+                entries,
+                declared_count,
+            })
         })
     }
     fn decode_strict_array(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this| {
-            let count = this.inner.read_u32::<BigEndian>()? as usize;
-            let entries = (0..count)
-                .map(|_| this.decode_value())
-                .collect::<DecodeResult<_>>()?;
+            let count = this.read_u32()? as usize;
+            this.allocate_entries::<Value>(count)?;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.decode_value()?);
+            }
             Ok(Value::Array { entries })
         })
     }
     fn decode_date(&mut self) -> DecodeResult<Value> {
-        let millis = self.inner.read_f64::<BigEndian>()?;
-        let time_zone = self.inner.read_i16::<BigEndian>()?;
+        let millis = self.read_f64()?;
+        let offset = self.position;
+        let time_zone = self.read_i16()?;
+        if time_zone != 0 && self.options.collect_warnings {
+            self.warnings
+                .push(Warning::NonZeroTimeZone { time_zone, offset });
+        }
         if !(millis.is_finite() && millis.is_sign_positive()) {
             Err(DecodeError::InvalidDate { millis })
         } else {
@@ -143,17 +411,22 @@ where
         }
     }
     fn decode_long_string(&mut self) -> DecodeResult<Value> {
-        let len = self.inner.read_u32::<BigEndian>()? as usize;
+        let len = self.read_u32()? as usize;
+        if self.byte_array_sink.is_some() {
+            let offset = self.streamed;
+            self.stream_utf8(len)?;
+            return Ok(Value::StreamedLongString { offset, len });
+        }
         self.read_utf8(len).map(Value::String)
     }
     fn decode_xml_document(&mut self) -> DecodeResult<Value> {
-        let len = self.inner.read_u32::<BigEndian>()? as usize;
+        let len = self.read_u32()? as usize;
         self.read_utf8(len).map(Value::XmlDocument)
     }
     fn decode_typed_object(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this| {
-            let len = this.inner.read_u16::<BigEndian>()? as usize;
-            let class_name = this.read_utf8(len)?;
+            let len = this.read_u16()? as usize;
+            let class_name = this.read_key(len, StringContext::ClassName)?;
             let entries = this.decode_pairs()?;
             Ok(Value::Object {
                 class_name: Some(class_name),
@@ -161,54 +434,343 @@ where
             })
         })
     }
+    fn decode_recordset(&mut self) -> DecodeResult<Value> {
+        self.decode_complex_type(|this| {
+            let entries = this.decode_pairs()?;
+            Ok(Value::Object {
+                class_name: Some("RecordSet".to_owned()),
+                entries,
+            })
+        })
+    }
     fn decode_avmplus(&mut self) -> DecodeResult<Value> {
-        let value = amf3::Decoder::new(&mut self.inner).decode()?;
-        Ok(Value::AvmPlus(value))
+        let mut decoder = amf3::Decoder::new_at(
+            &mut self.inner,
+            self.position,
+            self.depth,
+            self.options.clone(),
+            self.byte_array_sink.as_mut(),
+        );
+        let share = self.options.share_avmplus_reference_tables;
+        if share {
+            decoder.set_reference_tables(mem::take(&mut self.amf3_tables));
+        }
+        let value =
+            decoder.decode_nested(&mut self.allocated, &mut self.streamed, &mut self.interner);
+        self.position = decoder.position();
+        self.warnings.append(&mut decoder.take_warnings());
+        if share {
+            self.amf3_tables = decoder.take_reference_tables();
+        }
+        Ok(Value::AvmPlus(value?))
     }
 
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+    fn read_u16(&mut self) -> io::Result<u16> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(BigEndian::read_u16(&buf))
+    }
+    fn read_i16(&mut self) -> io::Result<i16> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(BigEndian::read_i16(&buf))
+    }
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(BigEndian::read_u32(&buf))
+    }
+    fn read_f64(&mut self) -> io::Result<f64> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(BigEndian::read_f64(&buf))
+    }
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(buf)?;
+        self.position += buf.len();
+        Ok(())
+    }
+    /// Reads a property key or a class name through the scratch buffer
+    /// to avoid allocating a zero-filled buffer for each short-lived read.
+    fn read_key(&mut self, len: usize, context: StringContext) -> DecodeResult<String> {
+        if self.options.intern_strings {
+            return self.read_interned(len, context);
+        }
+        self.read_scratch_str(len, context).map(ToOwned::to_owned)
+    }
+    /// Reads a string through the scratch buffer, copying the interned one if any
+    /// (see `DecodeOptions::intern_strings`).
+    fn read_interned(&mut self, len: usize, context: StringContext) -> DecodeResult<String> {
+        let offset = self.read_scratch(len)?;
+        if let Some(s) = self.interner.get(&self.scratch) {
+            return Ok(s);
+        }
+        let s = scratch_str(&self.scratch, offset, context)?;
+        self.interner.insert(s);
+        Ok(s.to_owned())
+    }
+    fn read_key_into(
+        &mut self,
+        len: usize,
+        out: &mut String,
+        context: StringContext,
+    ) -> DecodeResult<()> {
+        let s = self.read_scratch_str(len, context)?;
+        out.clear();
+        out.push_str(s);
+        Ok(())
+    }
+    fn read_scratch_str(&mut self, len: usize, context: StringContext) -> DecodeResult<&str> {
+        let offset = self.read_scratch(len)?;
+        scratch_str(&self.scratch, offset, context)
+    }
+    /// Reads `len` bytes into the scratch buffer, returning the offset of them.
+    fn read_scratch(&mut self, len: usize) -> DecodeResult<usize> {
+        self.allocate(len)?;
+        let offset = self.position;
+        self.scratch.clear();
+        self.scratch.resize(len, 0);
+        self.inner.read_exact(&mut self.scratch)?;
+        self.position += len;
+        Ok(offset)
+    }
+    /// Copies the `len` bytes of a string value from the reader to the byte array sink
+    /// through the scratch buffer, validating them as UTF-8.
+    ///
+    /// A character split across two chunks is kept in the scratch buffer until it is complete.
+    fn stream_utf8(&mut self, mut len: usize) -> DecodeResult<()> {
+        let offset = self.position;
+        self.scratch.clear();
+        while len > 0 {
+            let n = cmp::min(len, MAX_PREALLOCATED_BYTES);
+            let start = self.scratch.len();
+            self.scratch.resize(start + n, 0);
+            self.inner.read_exact(&mut self.scratch[start..])?;
+            self.position += n;
+            len -= n;
+            let valid = match str::from_utf8(&self.scratch) {
+                Ok(_) => self.scratch.len(),
+                Err(e) if e.error_len().is_none() && len > 0 => e.valid_up_to(),
+                Err(_) => {
+                    return Err(DecodeError::Utf8 {
+                        error: String::from_utf8(mem::take(&mut self.scratch)).unwrap_err(),
+                        offset,
+                        context: StringContext::Value,
+                    })
+                }
+            };
+            if let Some(sink) = self.byte_array_sink.as_mut() {
+                sink.write_all(&self.scratch[..valid])?;
+            }
+            self.streamed += valid;
+            self.scratch.drain(..valid);
+        }
+        Ok(())
+    }
     fn read_utf8(&mut self, len: usize) -> DecodeResult<String> {
-        let mut buf = vec![0; len];
-        self.inner.read_exact(&mut buf)?;
-        let utf8 = String::from_utf8(buf)?;
-        Ok(utf8)
+        self.read_utf8_into(len, Vec::new())
     }
-    fn decode_pairs(&mut self) -> DecodeResult<Vec<Pair<String, Value>>> {
-        let mut entries = Vec::new();
+    /// Reads a string value into `buf`, reusing its allocation.
+    fn read_utf8_into(&mut self, len: usize, mut buf: Vec<u8>) -> DecodeResult<String> {
+        self.allocate(len)?;
+        let offset = self.position;
+        buf.clear();
+        buf.reserve(cmp::min(len, MAX_PREALLOCATED_BYTES));
+        while buf.len() < len {
+            let start = buf.len();
+            buf.resize(cmp::min(len, start + MAX_PREALLOCATED_BYTES), 0);
+            self.read_exact(&mut buf[start..])?;
+        }
+        String::from_utf8(buf).map_err(|error| DecodeError::Utf8 {
+            error,
+            offset,
+            context: StringContext::Value,
+        })
+    }
+    fn decode_pairs_into(&mut self, entries: &mut Vec<Pair<String, Value>>) -> DecodeResult<()> {
+        let mut n = 0;
         loop {
-            let len = self.inner.read_u16::<BigEndian>()? as usize;
-            let key = self.read_utf8(len)?;
-            match self.decode_value() {
-                Ok(value) => {
+            let offset = self.position;
+            let len = self.read_u16()? as usize;
+            let (decoded, rest) = entries.split_at_mut(n);
+            match rest.first_mut() {
+                Some(p) => {
+                    self.read_key_into(len, &mut p.key, StringContext::Key)?;
+                    let marker = self.read_u8()?;
+                    if len == 0 && marker == marker::OBJECT_END_MARKER {
+                        break;
+                    }
+                    self.check_duplicate_key(decoded, &p.key, offset)?;
+                    self.decode_marked_value_into(marker, &mut p.value)?;
+                }
+                None => {
+                    let key = self.read_key(len, StringContext::Key)?;
+                    let marker = self.read_u8()?;
+                    if len == 0 && marker == marker::OBJECT_END_MARKER {
+                        break;
+                    }
+                    self.check_duplicate_key(decoded, &key, offset)?;
+                    self.allocate_entries::<Pair<String, Value>>(1)?;
+                    let value = self.decode_marked_value(marker)?;
                     entries.push(Pair { key, value });
                 }
-                Err(DecodeError::UnexpectedObjectEnd) if key.is_empty() => break,
-                Err(e) => return Err(e),
             }
+            n += 1;
+            self.check_object_entries(n)?;
+        }
+        entries.truncate(n);
+        Ok(())
+    }
+    fn decode_pairs(&mut self) -> DecodeResult<Vec<Pair<String, Value>>> {
+        let mut entries = Vec::new();
+        loop {
+            let offset = self.position;
+            let len = self.read_u16()? as usize;
+            let key = self.read_key(len, StringContext::Key)?;
+            let marker = self.read_u8()?;
+            if key.is_empty() && marker == marker::OBJECT_END_MARKER {
+                break;
+            }
+            self.check_duplicate_key(&entries, &key, offset)?;
+            self.allocate_entries::<Pair<String, Value>>(1)?;
+            let value = self.decode_marked_value(marker)?;
+            self.check_object_entries(entries.len() + 1)?;
+            entries.push(Pair { key, value });
         }
         Ok(entries)
     }
+    fn check_duplicate_key(
+        &mut self,
+        entries: &[Pair<String, Value>],
+        key: &str,
+        offset: usize,
+    ) -> DecodeResult<()> {
+        let check = self.options.reject_duplicate_keys || self.options.collect_warnings;
+        if !(check && entries.iter().any(|p| p.key == key)) {
+            return Ok(());
+        }
+        if self.options.reject_duplicate_keys {
+            return Err(DecodeError::DuplicateKey {
+                key: key.to_owned(),
+            });
+        }
+        self.warnings.push(Warning::DuplicateKey {
+            key: key.to_owned(),
+            offset,
+        });
+        Ok(())
+    }
+    fn check_ecma_array_count(&mut self, count: u32, actual: usize, offset: usize) -> Option<u32> {
+        if count as usize == actual {
+            return None;
+        }
+        if self.options.collect_warnings {
+            self.warnings.push(Warning::EcmaArrayCountMismatch {
+                declared: count,
+                actual,
+                offset,
+            });
+        }
+        if self.options.preserve_ecma_array_counts {
+            Some(count)
+        } else {
+            None
+        }
+    }
+    fn check_finite_number(&self, n: f64, offset: usize) -> DecodeResult<()> {
+        if self.options.reject_non_finite_numbers && !n.is_finite() {
+            Err(DecodeError::NonFiniteNumber { offset })
+        } else {
+            Ok(())
+        }
+    }
+    /// Charges `bytes` to the allocation budget (see `DecodeOptions::max_total_bytes`).
+    fn allocate(&mut self, bytes: usize) -> DecodeResult<()> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        match self.options.max_total_bytes {
+            Some(max) if self.allocated > max => Err(DecodeError::AllocationBudgetExceeded {
+                max,
+                offset: self.position,
+            }),
+            _ => Ok(()),
+        }
+    }
+    fn allocate_entries<T>(&mut self, count: usize) -> DecodeResult<()> {
+        self.allocate(count.saturating_mul(mem::size_of::<T>()))
+    }
+    fn check_object_entries(&self, count: usize) -> DecodeResult<()> {
+        match self.options.max_object_entries {
+            Some(max) if count > max => Err(DecodeError::CollectionTooLarge {
+                max,
+                offset: self.position,
+            }),
+            _ => Ok(()),
+        }
+    }
     fn decode_complex_type<F>(&mut self, f: F) -> DecodeResult<Value>
     where
         F: FnOnce(&mut Self) -> DecodeResult<Value>,
     {
+        if self.depth == MAX_DECODE_DEPTH {
+            return Err(DecodeError::DepthLimitExceeded {
+                offset: self.position,
+            });
+        }
         let index = self.complexes.len();
         self.complexes.push(Value::Null);
-        let value = f(self)?;
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        let value = value?;
         self.complexes[index] = value.clone();
         Ok(value)
     }
 }
 
+fn take_entries(value: &mut Value) -> Vec<Pair<String, Value>> {
+    match *value {
+        Value::Object {
+            ref mut entries, ..
+        }
+        | Value::EcmaArray {
+            ref mut entries, ..
+        } => mem::take(entries),
+        _ => Vec::new(),
+    }
+}
+
+/// Validates the bytes of a string read into the scratch buffer.
+fn scratch_str(bytes: &[u8], offset: usize, context: StringContext) -> DecodeResult<&str> {
+    str::from_utf8(bytes).map_err(|_| DecodeError::Utf8 {
+        error: String::from_utf8(bytes.to_vec()).unwrap_err(),
+        offset,
+        context,
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    #![allow(clippy::approx_constant)]
+    #![allow(
+        clippy::approx_constant,
+        clippy::manual_repeat_n,
+        clippy::manual_str_repeat,
+        clippy::redundant_field_names
+    )]
     use super::super::marker;
     use super::super::Value;
+    use super::Decoder;
     use crate::amf3;
-    use crate::error::DecodeError;
+    use crate::error::{DecodeError, StringContext};
+    use crate::io;
+    use crate::options::DecodeOptions;
     use crate::Pair;
     use std::f64;
-    use std::io;
+    use std::io::Read;
     use std::iter;
     use std::time;
 
@@ -342,19 +904,22 @@ mod tests {
         assert_eq!(
             decode!("amf0-movieclip.bin"),
             Err(DecodeError::Unsupported {
-                marker: marker::MOVIECLIP
+                marker: marker::MOVIECLIP,
+                offset: 0
             })
         );
         assert_eq!(
             decode!("amf0-recordset.bin"),
             Err(DecodeError::Unsupported {
-                marker: marker::RECORDSET
+                marker: marker::RECORDSET,
+                offset: 0
             })
         );
         assert_eq!(
             decode!("amf0-unsupported.bin"),
             Err(DecodeError::Unsupported {
-                marker: marker::UNSUPPORTED
+                marker: marker::UNSUPPORTED,
+                offset: 0
             })
         );
     }
@@ -363,12 +928,92 @@ mod tests {
         let entries = es(&[("0", s("a")), ("1", s("b")), ("2", s("c")), ("3", s("d"))][..]);
         decode_eq!(
             "amf0-ecma-ordinal-array.bin",
-            Value::EcmaArray { entries: entries }
+            Value::EcmaArray {
+                entries: entries,
+                declared_count: None
+            }
         );
         decode_unexpected_eof!("amf0-ecma-array-partial.bin");
 
         let entries = es(&[("c", s("d")), ("a", s("b"))][..]);
-        decode_eq!("amf0-hash.bin", Value::EcmaArray { entries: entries });
+        decode_eq!(
+            "amf0-hash.bin",
+            Value::EcmaArray {
+                entries: entries,
+                declared_count: None
+            }
+        );
+    }
+    #[test]
+    fn interns_strings_if_enabled() {
+        let object = |s: &str| Value::Object {
+            class_name: Some("foo".to_string()),
+            entries: es(&[("code", Value::String(s.to_string()))][..]),
+        };
+        let value = Value::Array {
+            entries: vec![
+                object("bar"),
+                object("bar"),
+                Value::AvmPlus(amf3::Value::String("bar".to_string())),
+                object(&"x".repeat(0x1000)),
+            ],
+        };
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        value.write_to(&mut buf).unwrap();
+
+        let options = DecodeOptions {
+            intern_strings: true,
+            ..Default::default()
+        };
+        let mut decoder = Decoder::with_options(&buf[..], options.clone());
+        assert_eq!(decoder.decode(), Ok(value.clone()));
+        assert_eq!(decoder.decode(), Ok(value));
+
+        // A string is validated before it is interned.
+        let input = [0x02, 0x00, 0x01, 0xFF];
+        let mut decoder = Decoder::with_options(&input[..], options);
+        assert!(matches!(
+            decoder.decode(),
+            Err(DecodeError::Utf8 { offset: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn preserves_ecma_array_counts_if_enabled() {
+        let input = include_bytes!("../testdata/amf0-ecma-array-mismatched-count.bin");
+        let entries = es(&[("a", s("b"))][..]);
+        assert_eq!(
+            Value::read_from(&mut &input[..]),
+            Ok(Value::EcmaArray {
+                entries: entries.clone(),
+                declared_count: None,
+            })
+        );
+
+        let options = DecodeOptions {
+            preserve_ecma_array_counts: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Decoder::with_options(&input[..], options.clone()).decode(),
+            Ok(Value::EcmaArray {
+                entries,
+                declared_count: Some(5),
+            })
+        );
+        let input = include_bytes!("../testdata/amf0-hash.bin");
+        let mut value = Value::Null;
+        Decoder::with_options(&input[..], options)
+            .decode_into(&mut value)
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::EcmaArray {
+                entries: es(&[("c", s("d")), ("a", s("b"))][..]),
+                declared_count: Some(0),
+            }
+        );
     }
     #[test]
     fn decodes_strict_array() {
@@ -437,7 +1082,24 @@ mod tests {
         decode_unexpected_eof!("amf0-empty.bin");
         assert_eq!(
             decode!("amf0-unknown-marker.bin"),
-            Err(DecodeError::Unknown { marker: 97 })
+            Err(DecodeError::Unknown {
+                marker: 97,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            decode!("amf0-strict-array-unknown-marker.bin"),
+            Err(DecodeError::Unknown {
+                marker: 97,
+                offset: 14
+            })
+        );
+        assert_eq!(
+            decode!("amf0-avmplus-unknown-marker.bin"),
+            Err(DecodeError::Unknown {
+                marker: 123,
+                offset: 6
+            })
         );
     }
 
@@ -462,4 +1124,550 @@ mod tests {
             })
             .collect()
     }
+
+    #[test]
+    fn shares_avmplus_reference_tables() {
+        // A strict array of two AVM+ strings; the second one refers to the first one.
+        let input = [
+            0x0A, 0, 0, 0, 2, 0x11, 0x06, 0x07, b'f', b'o', b'o', 0x11, 0x06, 0x00,
+        ];
+        assert_eq!(
+            Value::read_from(&mut &input[..]),
+            Err(DecodeError::OutOfRangeReference { index: 0 })
+        );
+
+        let options = DecodeOptions {
+            share_avmplus_reference_tables: true,
+            ..Default::default()
+        };
+        let foo = Value::AvmPlus(amf3::Value::String("foo".to_string()));
+        let mut decoder = Decoder::with_options(&input[..], options.clone());
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Value::Array {
+                entries: vec![foo.clone(), foo.clone()]
+            }
+        );
+
+        // The tables also persist across top-level values until they are cleared.
+        let input = [
+            0x11, 0x06, 0x07, b'f', b'o', b'o', 0x11, 0x06, 0x00, 0x11, 0x06, 0x00,
+        ];
+        let mut decoder = Decoder::with_options(&input[..], options);
+        assert_eq!(decoder.decode().unwrap(), foo);
+        assert_eq!(decoder.decode().unwrap(), foo);
+        decoder.clear_reference_table();
+        assert_eq!(
+            decoder.decode(),
+            Err(DecodeError::OutOfRangeReference { index: 0 })
+        );
+    }
+
+    #[test]
+    fn decodes_references_in_large_reference_table() {
+        use crate::options::EncodeOptions;
+
+        // The outer array takes index 0, so the objects are at indices 1..=65537.
+        let objects = (0..0x10001)
+            .map(|i| Value::Object {
+                class_name: None,
+                entries: es(&[("i", Value::Number(f64::from(i)))]),
+            })
+            .collect::<Vec<_>>();
+        let mut entries = objects.clone();
+        entries.push(objects[0].clone()); // index 1
+        entries.push(objects[0xFFFE].clone()); // index 65535
+        entries.push(objects[0xFFFF].clone()); // index 65536 (not referable)
+        let value = Value::Array { entries };
+
+        let options = EncodeOptions {
+            use_object_references: true,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+        let tail = &buf[buf.len() - 6 - 16..];
+        assert_eq!(&tail[..6], &[0x07, 0x00, 0x01, 0x07, 0xFF, 0xFF][..]);
+        assert_eq!(tail[6], 0x03); // written in full
+        assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), value);
+    }
+
+    #[test]
+    fn decodes_recordsets_if_enabled() {
+        let input = include_bytes!("../testdata/amf0-recordset-object.bin");
+        assert_eq!(
+            Value::read_from(&mut &input[..]),
+            Err(DecodeError::Unsupported {
+                marker: marker::RECORDSET,
+                offset: 0
+            })
+        );
+
+        let options = DecodeOptions {
+            decode_recordsets: true,
+            ..Default::default()
+        };
+        let s = |s: &str| Value::String(s.to_string());
+        let array = |entries| Value::Array { entries };
+        assert_eq!(
+            Decoder::with_options(&input[..], options.clone())
+                .decode()
+                .unwrap(),
+            Value::Object {
+                class_name: Some("RecordSet".to_string()),
+                entries: es(&[
+                    ("cursor", Value::Number(1.0)),
+                    ("columnNames", array(vec![s("id"), s("name")])),
+                    (
+                        "initialData",
+                        array(vec![array(vec![Value::Number(1.0), s("foo")])])
+                    ),
+                ]),
+            }
+        );
+
+        // A marker without a body is still an error.
+        let input = include_bytes!("../testdata/amf0-recordset.bin");
+        assert!(matches!(
+            Decoder::with_options(&input[..], options).decode(),
+            Err(DecodeError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn tolerates_unsupported_markers_if_enabled() {
+        let options = DecodeOptions {
+            tolerate_unsupported: true,
+            ..Default::default()
+        };
+        let decode = |input: &[u8]| Decoder::with_options(input, options.clone()).decode();
+        for m in [marker::MOVIECLIP, marker::UNSUPPORTED, marker::RECORDSET] {
+            assert_eq!(decode(&[m]), Ok(Value::Unsupported { marker: m }));
+        }
+
+        // The decoding continues after the placeholder.
+        let input = [
+            0x0A, 0, 0, 0, 3, 0x04, 0x0D, 0x00, 0x3F, 0xF0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(
+            decode(&input),
+            Ok(Value::Array {
+                entries: vec![
+                    Value::Unsupported {
+                        marker: marker::MOVIECLIP
+                    },
+                    Value::Unsupported {
+                        marker: marker::UNSUPPORTED
+                    },
+                    Value::Number(1.0),
+                ]
+            })
+        );
+        assert_eq!(
+            Value::read_from(&mut &input[..]),
+            Err(DecodeError::Unsupported {
+                marker: marker::MOVIECLIP,
+                offset: 5
+            })
+        );
+
+        // Unknown markers are still fatal.
+        let input = include_bytes!("../testdata/amf0-strict-array-unknown-marker.bin");
+        assert!(matches!(
+            decode(&input[..]),
+            Err(DecodeError::Unknown { .. })
+        ));
+
+        // A placeholder is encoded with the `UNSUPPORTED` marker.
+        let mut buf = Vec::new();
+        Value::Unsupported {
+            marker: marker::RECORDSET,
+        }
+        .write_to(&mut buf)
+        .unwrap();
+        assert_eq!(buf, [marker::UNSUPPORTED]);
+    }
+
+    #[test]
+    fn decodes_into_reused_value() {
+        let inputs: [&[u8]; 9] = [
+            include_bytes!("../testdata/amf0-object.bin"),
+            include_bytes!("../testdata/amf0-typed-object.bin"),
+            include_bytes!("../testdata/amf0-ecma-ordinal-array.bin"),
+            include_bytes!("../testdata/amf0-strict-array.bin"),
+            include_bytes!("../testdata/amf0-string.bin"),
+            include_bytes!("../testdata/amf0-hash.bin"),
+            include_bytes!("../testdata/amf0-ref-test.bin"),
+            include_bytes!("../testdata/amf0-number.bin"),
+            include_bytes!("../testdata/amf0-complex-encoded-string.bin"),
+        ];
+        let mut value = Value::Null;
+        for input in inputs.iter().chain(inputs.iter().rev()).chain(&inputs) {
+            let expected = Value::read_from(&mut &input[..]).unwrap();
+            Decoder::new(&input[..]).decode_into(&mut value).unwrap();
+            assert_eq!(value, expected);
+        }
+
+        // Fewer entries than the reused value.
+        let mut value = Value::Array {
+            entries: vec![Value::String("foo".to_string()); 10],
+        };
+        let input = include_bytes!("../testdata/amf0-strict-array.bin");
+        Decoder::new(&input[..]).decode_into(&mut value).unwrap();
+        assert_eq!(value, Value::read_from(&mut &input[..]).unwrap());
+
+        let input = include_bytes!("../testdata/amf0-object-partial.bin");
+        assert!(matches!(
+            Decoder::new(&input[..]).decode_into(&mut value),
+            Err(DecodeError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn reads_trailing_bytes_from_borrowed_reader() {
+        let input = [0x05, 0xAB, 0xCD];
+        let mut decoder = Decoder::new(&input[..]);
+        assert_eq!(decoder.decode().unwrap(), Value::Null);
+        assert_eq!(decoder.inner(), &[0xAB, 0xCD]);
+
+        let mut byte = [0];
+        decoder.inner_mut().read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [0xAB]);
+        assert_eq!(decoder.inner(), &[0xCD]);
+    }
+
+    #[test]
+    fn decodes_bare_strings() {
+        use crate::amf0::Encoder;
+
+        let long = "a".repeat(0x10000);
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.encode_str("foo").unwrap();
+        encoder.encode_long_str(&long).unwrap();
+        encoder.encode_str("").unwrap();
+        let buf = encoder.into_inner();
+        assert_eq!(&buf[..5], b"\x00\x03foo");
+
+        let mut decoder = Decoder::new(&buf[..]);
+        assert_eq!(decoder.decode_str().unwrap(), "foo");
+        assert_eq!(decoder.decode_long_str().unwrap(), long);
+        assert_eq!(decoder.decode_str().unwrap(), "");
+        assert_eq!(decoder.position(), buf.len());
+
+        assert!(Encoder::new(Vec::new()).encode_str(&long).is_err());
+    }
+
+    #[test]
+    fn limits_object_entries_if_enabled() {
+        let options = DecodeOptions {
+            max_object_entries: Some(2),
+            ..Default::default()
+        };
+        let input = [
+            0x03, 0, 1, b'a', 0x05, 0, 1, b'b', 0x05, 0, 1, b'c', 0x05, 0, 0, 0x09,
+        ];
+        assert_eq!(
+            Decoder::with_options(&input[..], options.clone()).decode(),
+            Err(DecodeError::CollectionTooLarge { max: 2, offset: 13 })
+        );
+        let mut value = Value::Null;
+        assert_eq!(
+            Decoder::with_options(&input[..], options.clone()).decode_into(&mut value),
+            Err(DecodeError::CollectionTooLarge { max: 2, offset: 13 })
+        );
+        assert_eq!(
+            Decoder::with_options(&input[..2 + 13], options).decode(),
+            Err(DecodeError::CollectionTooLarge { max: 2, offset: 13 })
+        );
+
+        let options = DecodeOptions {
+            max_object_entries: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode(),
+            Value::read_from(&input[..])
+        );
+    }
+
+    #[test]
+    fn limits_total_allocation_if_enabled() {
+        let array = Value::Array {
+            entries: vec![Value::String("x".repeat(1000)); 64],
+        };
+        let mut input = Vec::new();
+        array.write_to(&mut input).unwrap();
+        array.write_to(&mut input).unwrap();
+
+        let options = DecodeOptions {
+            max_total_bytes: Some(32 * 1024),
+            ..Default::default()
+        };
+        let result = Decoder::with_options(&input[..], options).decode();
+        assert!(
+            matches!(
+                result,
+                Err(DecodeError::AllocationBudgetExceeded { max: 32768, .. })
+            ),
+            "{:?}",
+            result
+        );
+
+        // The budget applies to each value separately.
+        let options = DecodeOptions {
+            max_total_bytes: Some(128 * 1024),
+            ..Default::default()
+        };
+        let mut decoder = Decoder::with_options(&input[..], options);
+        assert_eq!(decoder.decode(), Ok(array.clone()));
+        assert_eq!(decoder.decode(), Ok(array));
+    }
+
+    #[test]
+    fn retains_empty_key_members() {
+        // {"": 1.0, "a": 2.0}
+        let input = [
+            0x03, 0, 0, 0x00, 0x3F, 0xF0, 0, 0, 0, 0, 0, 0, 0, 1, b'a', 0x00, 0x40, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0x09,
+        ];
+        let expected = Value::Object {
+            class_name: None,
+            entries: es(&[("", Value::Number(1.0)), ("a", Value::Number(2.0))]),
+        };
+        assert_eq!(Value::read_from(&input[..]), Ok(expected.clone()));
+        let mut value = Value::empty_object();
+        Decoder::new(&input[..]).decode_into(&mut value).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(
+            super::super::ValueRef::read_from_slice(&input[..]).map(|v| v.to_owned()),
+            Ok(expected)
+        );
+
+        // An object-end marker nested in an empty-key member does not terminate the object.
+        let input = [0x03, 0, 0, 0x0A, 0, 0, 0, 1, 0x09, 0, 0, 0x09];
+        assert_eq!(
+            Value::read_from(&input[..]),
+            Err(DecodeError::UnexpectedObjectEnd)
+        );
+        let mut value = Value::empty_object();
+        assert_eq!(
+            Decoder::new(&input[..]).decode_into(&mut value),
+            Err(DecodeError::UnexpectedObjectEnd)
+        );
+        assert_eq!(
+            super::super::ValueRef::read_from_slice(&input[..]),
+            Err(DecodeError::UnexpectedObjectEnd)
+        );
+    }
+
+    #[test]
+    fn streams_long_strings_and_byte_arrays_into_sink() {
+        let value = Value::Array {
+            entries: vec![
+                Value::String("a".repeat(0x10000)),
+                Value::String("short".to_string()),
+                Value::AvmPlus(amf3::Value::ByteArray(vec![1, 2, 3])),
+            ],
+        };
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+
+        let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Value::Array {
+                entries: vec![
+                    Value::StreamedLongString {
+                        offset: 0,
+                        len: 0x10000,
+                    },
+                    Value::String("short".to_string()),
+                    Value::AvmPlus(amf3::Value::StreamedByteArray {
+                        offset: 0x10000,
+                        len: 3,
+                    }),
+                ],
+            }
+        );
+        let mut expected = "a".repeat(0x10000).into_bytes();
+        expected.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(decoder.into_byte_array_sink(), Some(expected));
+    }
+    #[test]
+    fn validates_streamed_long_strings() {
+        // A two-byte character straddles the boundary of the chunks.
+        let s = format!("{}\u{e9}", "a".repeat(crate::MAX_PREALLOCATED_BYTES - 1));
+        let mut buf = Vec::new();
+        Value::String(s.clone()).write_to(&mut buf).unwrap();
+        let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Value::StreamedLongString {
+                offset: 0,
+                len: s.len()
+            }
+        );
+        assert_eq!(decoder.into_byte_array_sink(), Some(s.into_bytes()));
+
+        let mut buf = [marker::LONG_STRING, 0, 0, 0, 2, b'a', 0xFF];
+        let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+        assert!(matches!(
+            decoder.decode(),
+            Err(DecodeError::Utf8 {
+                offset: 5,
+                context: StringContext::Value,
+                ..
+            })
+        ));
+
+        // A truncated character at the end of the string is also invalid.
+        buf[6] = 0xC3;
+        let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+        assert!(matches!(decoder.decode(), Err(DecodeError::Utf8 { .. })));
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers_if_enabled() {
+        let options = DecodeOptions {
+            reject_non_finite_numbers: true,
+            ..Default::default()
+        };
+        for file in [
+            &include_bytes!("../testdata/amf0-number-positive-infinity.bin")[..],
+            &include_bytes!("../testdata/amf0-number-negative-infinity.bin")[..],
+            &include_bytes!("../testdata/amf0-number-quiet-nan.bin")[..],
+        ] {
+            let value = Value::read_from(file).unwrap();
+            assert!(!value.is_finite_number());
+            assert_eq!(
+                Decoder::with_options(file, options.clone()).decode(),
+                Err(DecodeError::NonFiniteNumber { offset: 1 })
+            );
+        }
+
+        let file = include_bytes!("../testdata/amf0-number.bin");
+        let value = Decoder::with_options(&file[..], options).decode().unwrap();
+        assert!(value.is_finite_number());
+        assert!(!Value::Null.is_finite_number());
+    }
+    #[test]
+    fn collects_warnings_if_enabled() {
+        use crate::error::Warning;
+
+        let options = DecodeOptions {
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let warnings_of = |input: &[u8]| {
+            let mut decoder = Decoder::with_options(input, options.clone());
+            decoder.decode().unwrap();
+            decoder.take_warnings()
+        };
+
+        let input = include_bytes!("../testdata/amf0-ecma-array-mismatched-count.bin");
+        assert_eq!(
+            warnings_of(input),
+            [Warning::EcmaArrayCountMismatch {
+                declared: 5,
+                actual: 1,
+                offset: 1
+            }]
+        );
+        let mut decoder = Decoder::new(&input[..]);
+        decoder.decode().unwrap();
+        assert_eq!(decoder.take_warnings(), []);
+
+        let input = [0x0B, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x3C];
+        assert_eq!(
+            warnings_of(&input),
+            [Warning::NonZeroTimeZone {
+                time_zone: 60,
+                offset: 9
+            }]
+        );
+
+        let input = [
+            0x03, 0, 1, b'a', 0x05, 0, 1, b'a', 0x05, 0, 1, b'b', 0x05, 0, 0, 0x09,
+        ];
+        let duplicate = Warning::DuplicateKey {
+            key: "a".to_string(),
+            offset: 5,
+        };
+        assert_eq!(warnings_of(&input), std::slice::from_ref(&duplicate));
+        let mut value = Value::Object {
+            class_name: None,
+            entries: es(&[("x", Value::Null), ("y", Value::Null)]),
+        };
+        let mut decoder = Decoder::with_options(&input[..], options.clone());
+        decoder.decode_into(&mut value).unwrap();
+        assert_eq!(decoder.take_warnings(), [duplicate]);
+
+        // The warnings of an AMF3 value are reported with the offsets in the AMF0 input.
+        let input = [
+            0x11, 0x0A, 0x0B, 0x01, 0x03, b'a', 0x01, 0x03, b'a', 0x01, 0x01,
+        ];
+        assert_eq!(
+            warnings_of(&input),
+            [Warning::DuplicateKey {
+                key: "a".to_string(),
+                offset: 7
+            }]
+        );
+    }
+    #[test]
+    fn rejects_duplicate_keys_if_enabled() {
+        decode_eq!(
+            "amf0-duplicate-keys.bin",
+            Value::Object {
+                class_name: None,
+                entries: es(&[("a", Value::Null), ("a", s("foo"))]),
+            }
+        );
+
+        let options = DecodeOptions {
+            reject_duplicate_keys: true,
+            ..Default::default()
+        };
+        let input = include_bytes!("../testdata/amf0-duplicate-keys.bin");
+        let error = DecodeError::DuplicateKey {
+            key: "a".to_string(),
+        };
+        assert_eq!(
+            Decoder::with_options(&input[..], options.clone()).decode(),
+            Err(error.clone())
+        );
+        let mut value = Value::Null;
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode_into(&mut value),
+            Err(error)
+        );
+    }
+    #[test]
+    fn reports_invalid_utf8_with_context() {
+        let input = include_bytes!("../testdata/amf0-bad-utf8-key.bin");
+        let error = Decoder::new(&input[..]).decode().unwrap_err();
+        match error {
+            DecodeError::Utf8 {
+                ref error,
+                offset: 3,
+                context: StringContext::Key,
+            } => assert_eq!(error.as_bytes(), [0xFF]),
+            ref other => panic!("{:?}", other),
+        }
+        assert!(error
+            .to_string()
+            .starts_with("Invalid UTF-8 in object key at offset 3:"));
+        let mut value = Value::Null;
+        assert_eq!(Decoder::new(&input[..]).decode_into(&mut value), Err(error));
+
+        let input = [0x02, 0x00, 0x02, b'a', 0xFF];
+        assert!(matches!(
+            Decoder::new(&input[..]).decode(),
+            Err(DecodeError::Utf8 {
+                offset: 3,
+                context: StringContext::Value,
+                ..
+            })
+        ));
+    }
 }
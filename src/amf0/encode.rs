@@ -1,18 +1,50 @@
 use super::marker;
 use super::Value;
 use crate::amf3;
-use crate::Pair;
-use byteorder::{BigEndian, WriteBytesExt};
-use std::io;
-use std::time;
+use crate::error::{check_len, EncodeError, ValidationError};
+use crate::io;
+use crate::options::EncodeOptions;
+use crate::registry::ClassRegistry;
+use crate::{EncodeResult, Pair};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use byteorder::{BigEndian, ByteOrder};
+use core::any::{type_name, Any};
+use core::cmp::Ordering;
+use core::time;
+
+/// A key of the object reference table which is compared by `Value::cmp_canonical`.
+#[derive(Debug)]
+struct ComplexKey(Value);
+impl PartialEq for ComplexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for ComplexKey {}
+impl PartialOrd for ComplexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for ComplexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_canonical(&other.0)
+    }
+}
 
 /// AMF0 encoder.
 #[derive(Debug)]
 pub struct Encoder<W> {
     inner: W,
+    options: EncodeOptions,
+    complexes: BTreeMap<ComplexKey, usize>,
+    complex_count: usize,
 }
 impl<W> Encoder<W> {
     /// Unwraps this `Encoder`, returning the underlying writer.
+    ///
+    /// Note that the writer is not flushed (use `finish` to flush it).
     pub fn into_inner(self) -> W {
         self.inner
     }
@@ -23,10 +55,41 @@ where
 {
     /// Makes a new instance.
     pub fn new(inner: W) -> Self {
-        Encoder { inner }
+        Self::with_options(inner, EncodeOptions::default())
+    }
+    /// Makes a new instance with the given options.
+    pub fn with_options(inner: W, options: EncodeOptions) -> Self {
+        Encoder {
+            inner,
+            options,
+            complexes: BTreeMap::new(),
+            complex_count: 0,
+        }
+    }
+    /// Flushes the underlying writer and unwraps this `Encoder`, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+    /// Clear the reference table of this encoder.
+    ///
+    /// The table is only used if references are enabled by `EncodeOptions`.
+    pub fn clear_reference_table(&mut self) {
+        self.complexes.clear();
+        self.complex_count = 0;
     }
     /// Encodes a AMF0 value.
-    pub fn encode(&mut self, value: &Value) -> io::Result<()> {
+    pub fn encode(&mut self, value: &Value) -> EncodeResult<()> {
+        if self.options.use_object_references && value.is_reference_type() {
+            let key = ComplexKey(value.clone());
+            if let Some(&index) = self.complexes.get(&key) {
+                return self.encode_reference(index as u16);
+            }
+            if self.complex_count <= 0xFFFF {
+                self.complexes.insert(key, self.complex_count);
+            }
+            self.complex_count += 1;
+        }
         match *value {
             Value::Number(x) => self.encode_number(x),
             Value::Boolean(x) => self.encode_boolean(x),
@@ -37,7 +100,10 @@ where
             } => self.encode_object(class_name, entries),
             Value::Null => self.encode_null(),
             Value::Undefined => self.encode_undefined(),
-            Value::EcmaArray { ref entries } => self.encode_ecma_array(entries),
+            Value::EcmaArray {
+                ref entries,
+                declared_count,
+            } => self.encode_ecma_array(entries, declared_count),
             Value::Array { ref entries } => self.encode_strict_array(entries),
             Value::Date {
                 unix_time,
@@ -45,25 +111,77 @@ where
             } => self.encode_date(unix_time, time_zone),
             Value::XmlDocument(ref x) => self.encode_xml_document(x),
             Value::AvmPlus(ref x) => self.encode_avmplus(x),
+            Value::Unsupported { .. } => self.encode_unsupported(),
+            Value::StreamedLongString { .. } => {
+                Err(EncodeError::Invalid(ValidationError::StreamedPlaceholder {
+                    type_name: value.type_name(),
+                }))
+            }
         }
     }
 
-    fn encode_number(&mut self, n: f64) -> io::Result<()> {
-        self.inner.write_u8(marker::NUMBER)?;
-        self.inner.write_f64::<BigEndian>(n)?;
+    /// Converts `x` into a typed object using `registry` and encodes it.
+    ///
+    /// `EncodeError::UnregisteredType` is returned if `T` is not registered.
+    pub fn encode_with_registry<T: Any>(
+        &mut self,
+        x: &T,
+        registry: &ClassRegistry<Value>,
+    ) -> EncodeResult<()> {
+        let value = registry
+            .to_value(x)
+            .ok_or_else(|| EncodeError::UnregisteredType {
+                type_name: type_name::<T>(),
+            })?;
+        self.encode(&value)
+    }
+
+    /// Encode an AMF0 `UTF-8` string (i.e., a string with a `u16` length prefix and without a marker).
+    ///
+    /// Use this if you need to encode an AMF0 string outside of value context.
+    /// An example of this is writing names in Local Shared Object file.
+    /// `ValidationError::TooLarge` is returned if `s` is longer than 65535 bytes.
+    pub fn encode_str(&mut self, s: &str) -> EncodeResult<()> {
+        self.write_str_u16(s)
+    }
+    /// Encode an AMF0 `UTF-8-long` string (i.e., a string with a `u32` length prefix and without a marker).
+    ///
+    /// `ValidationError::TooLarge` is returned if `s` is longer than 4294967295 bytes.
+    pub fn encode_long_str(&mut self, s: &str) -> EncodeResult<()> {
+        self.write_str_u32(s)
+    }
+
+    /// Writes a key-value pair of an object body (i.e., a `UTF-8` key followed by an encoded value).
+    ///
+    /// This and `write_object_end` are the building blocks of the objects and the ECMA arrays,
+    /// and can be used to compose an object body in a non-standard framing.
+    pub fn write_pair(&mut self, key: &str, value: &Value) -> EncodeResult<()> {
+        self.write_str_u16(key)?;
+        self.encode(value)
+    }
+    /// Writes the terminator of an object body (i.e., an empty key followed by the `OBJECT_END` marker).
+    pub fn write_object_end(&mut self) -> EncodeResult<()> {
+        self.write_u16(0)?;
+        self.write_u8(marker::OBJECT_END_MARKER)?;
         Ok(())
     }
-    fn encode_boolean(&mut self, b: bool) -> io::Result<()> {
-        self.inner.write_u8(marker::BOOLEAN)?;
-        self.inner.write_u8(b as u8)?;
+
+    fn encode_number(&mut self, n: f64) -> EncodeResult<()> {
+        self.write_u8(marker::NUMBER)?;
+        self.write_f64(n)?;
         Ok(())
     }
-    fn encode_string(&mut self, s: &str) -> io::Result<()> {
-        if s.len() <= 0xFFFF {
-            self.inner.write_u8(marker::STRING)?;
+    fn encode_boolean(&mut self, b: bool) -> EncodeResult<()> {
+        self.write_u8(marker::BOOLEAN)?;
+        self.write_u8(b as u8)?;
+        Ok(())
+    }
+    fn encode_string(&mut self, s: &str) -> EncodeResult<()> {
+        if s.len() <= 0xFFFF && !self.options.force_long_strings {
+            self.write_u8(marker::STRING)?;
             self.write_str_u16(s)?;
         } else {
-            self.inner.write_u8(marker::LONG_STRING)?;
+            self.write_u8(marker::LONG_STRING)?;
             self.write_str_u32(s)?;
         }
         Ok(())
@@ -72,88 +190,132 @@ where
         &mut self,
         class_name: &Option<String>,
         entries: &[Pair<String, Value>],
-    ) -> io::Result<()> {
-        assert!(entries.len() <= 0xFFFF_FFFF);
+    ) -> EncodeResult<()> {
+        check_len(entries.len(), 0xFFFF_FFFF)?;
         if let Some(class_name) = class_name.as_ref() {
-            self.inner.write_u8(marker::TYPED_OBJECT)?;
+            self.write_u8(marker::TYPED_OBJECT)?;
             self.write_str_u16(class_name)?;
         } else {
-            self.inner.write_u8(marker::OBJECT)?;
+            self.write_u8(marker::OBJECT)?;
         }
         self.encode_pairs(entries)?;
         Ok(())
     }
-    fn encode_null(&mut self) -> io::Result<()> {
-        self.inner.write_u8(marker::NULL)?;
+    fn encode_null(&mut self) -> EncodeResult<()> {
+        self.write_u8(marker::NULL)?;
+        Ok(())
+    }
+    fn encode_undefined(&mut self) -> EncodeResult<()> {
+        self.write_u8(marker::UNDEFINED)?;
         Ok(())
     }
-    fn encode_undefined(&mut self) -> io::Result<()> {
-        self.inner.write_u8(marker::UNDEFINED)?;
+    fn encode_unsupported(&mut self) -> EncodeResult<()> {
+        self.write_u8(marker::UNSUPPORTED)?;
         Ok(())
     }
-    fn encode_ecma_array(&mut self, entries: &[Pair<String, Value>]) -> io::Result<()> {
-        assert!(entries.len() <= 0xFFFF_FFFF);
-        self.inner.write_u8(marker::ECMA_ARRAY)?;
-        self.inner.write_u32::<BigEndian>(entries.len() as u32)?;
+    fn encode_ecma_array(
+        &mut self,
+        entries: &[Pair<String, Value>],
+        declared_count: Option<u32>,
+    ) -> EncodeResult<()> {
+        check_len(entries.len(), 0xFFFF_FFFF)?;
+        self.write_u8(marker::ECMA_ARRAY)?;
+        let count = match declared_count {
+            Some(count) if self.options.preserve_ecma_array_counts => count,
+            _ => entries.len() as u32,
+        };
+        self.write_u32(count)?;
         self.encode_pairs(entries)?;
         Ok(())
     }
-    fn encode_strict_array(&mut self, entries: &[Value]) -> io::Result<()> {
-        assert!(entries.len() <= 0xFFFF_FFFF);
-        self.inner.write_u8(marker::STRICT_ARRAY)?;
-        self.inner.write_u32::<BigEndian>(entries.len() as u32)?;
+    fn encode_strict_array(&mut self, entries: &[Value]) -> EncodeResult<()> {
+        check_len(entries.len(), 0xFFFF_FFFF)?;
+        self.write_u8(marker::STRICT_ARRAY)?;
+        self.write_u32(entries.len() as u32)?;
         for e in entries {
             self.encode(e)?;
         }
         Ok(())
     }
-    fn encode_date(&mut self, unix_time: time::Duration, time_zone: i16) -> io::Result<()> {
+    fn encode_date(&mut self, unix_time: time::Duration, time_zone: i16) -> EncodeResult<()> {
         let millis = unix_time.as_secs() * 1000 + (unix_time.subsec_nanos() as u64) / 1_000_000;
 
-        self.inner.write_u8(marker::DATE)?;
-        self.inner.write_f64::<BigEndian>(millis as f64)?;
-        self.inner.write_i16::<BigEndian>(time_zone)?;
+        self.write_u8(marker::DATE)?;
+        self.write_f64(millis as f64)?;
+        self.write_i16(self.options.amf0_date_time_zone.unwrap_or(time_zone))?;
         Ok(())
     }
-    fn encode_xml_document(&mut self, xml: &str) -> io::Result<()> {
-        self.inner.write_u8(marker::XML_DOCUMENT)?;
+    fn encode_xml_document(&mut self, xml: &str) -> EncodeResult<()> {
+        self.write_u8(marker::XML_DOCUMENT)?;
         self.write_str_u32(xml)?;
         Ok(())
     }
-    fn encode_avmplus(&mut self, value: &amf3::Value) -> io::Result<()> {
-        self.inner.write_u8(marker::AVMPLUS_OBJECT)?;
-        amf3::Encoder::new(&mut self.inner).encode(value)?;
+    pub(crate) fn encode_avmplus(&mut self, value: &amf3::Value) -> EncodeResult<()> {
+        self.write_u8(marker::AVMPLUS_OBJECT)?;
+        amf3::Encoder::with_options(&mut self.inner, self.options.clone()).encode(value)?;
+        Ok(())
+    }
+    fn encode_reference(&mut self, index: u16) -> EncodeResult<()> {
+        self.write_u8(marker::REFERENCE)?;
+        self.write_u16(index)?;
         Ok(())
     }
 
-    fn write_str_u32(&mut self, s: &str) -> io::Result<()> {
-        assert!(s.len() <= 0xFFFF_FFFF);
-        self.inner.write_u32::<BigEndian>(s.len() as u32)?;
+    fn write_str_u32(&mut self, s: &str) -> EncodeResult<()> {
+        check_len(s.len(), 0xFFFF_FFFF)?;
+        self.write_u32(s.len() as u32)?;
         self.inner.write_all(s.as_bytes())?;
         Ok(())
     }
-    fn write_str_u16(&mut self, s: &str) -> io::Result<()> {
-        assert!(s.len() <= 0xFFFF);
-        self.inner.write_u16::<BigEndian>(s.len() as u16)?;
+    fn write_str_u16(&mut self, s: &str) -> EncodeResult<()> {
+        check_len(s.len(), 0xFFFF)?;
+        self.write_u16(s.len() as u16)?;
         self.inner.write_all(s.as_bytes())?;
         Ok(())
     }
-    fn encode_pairs(&mut self, pairs: &[Pair<String, Value>]) -> io::Result<()> {
+    fn write_u8(&mut self, n: u8) -> io::Result<()> {
+        self.inner.write_all(&[n])
+    }
+    fn write_u16(&mut self, n: u16) -> io::Result<()> {
+        let mut buf = [0; 2];
+        BigEndian::write_u16(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn write_i16(&mut self, n: i16) -> io::Result<()> {
+        let mut buf = [0; 2];
+        BigEndian::write_i16(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn write_u32(&mut self, n: u32) -> io::Result<()> {
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn write_f64(&mut self, n: f64) -> io::Result<()> {
+        let mut buf = [0; 8];
+        BigEndian::write_f64(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn encode_pairs(&mut self, pairs: &[Pair<String, Value>]) -> EncodeResult<()> {
         for p in pairs {
-            self.write_str_u16(&p.key)?;
-            self.encode(&p.value)?;
+            self.write_pair(&p.key, &p.value)?;
         }
-        self.inner.write_u16::<BigEndian>(0)?;
-        self.inner.write_u8(marker::OBJECT_END_MARKER)?;
-        Ok(())
+        self.write_object_end()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    #![allow(clippy::approx_constant)]
+    #![allow(
+        clippy::approx_constant,
+        clippy::manual_repeat_n,
+        clippy::manual_str_repeat,
+        clippy::redundant_field_names
+    )]
     use super::super::Value;
+    use super::Encoder;
     use crate::amf3;
+    use crate::options::EncodeOptions;
     use crate::Pair;
     use std::iter;
     use std::time;
@@ -227,11 +389,43 @@ mod tests {
     fn encodes_ecma_array() {
         let entries = es(&[("0", s("a")), ("1", s("b")), ("2", s("c")), ("3", s("d"))][..]);
         encode_eq!(
-            Value::EcmaArray { entries: entries },
+            Value::EcmaArray {
+                entries: entries,
+                declared_count: None
+            },
             "amf0-ecma-ordinal-array.bin"
         );
     }
     #[test]
+    fn preserves_ecma_array_counts_if_enabled() {
+        let value = Value::EcmaArray {
+            entries: es(&[("a", s("b"))][..]),
+            declared_count: Some(5),
+        };
+        let expected = include_bytes!("../testdata/amf0-ecma-array-mismatched-count.bin");
+
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        assert_eq!(buf[1..5], [0, 0, 0, 1]);
+        assert_eq!(buf[5..], expected[5..]);
+
+        let options = EncodeOptions {
+            preserve_ecma_array_counts: true,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+        assert_eq!(buf, &expected[..]);
+
+        let value = Value::EcmaArray {
+            entries: es(&[("c", s("d")), ("a", s("b"))][..]),
+            declared_count: Some(0),
+        };
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+        assert_eq!(buf, &include_bytes!("../testdata/amf0-hash.bin")[..]);
+    }
+    #[test]
     fn encodes_string_array() {
         encode_eq!(
             Value::Array {
@@ -282,6 +476,51 @@ mod tests {
         };
         encode_eq!(Value::AvmPlus(value), "amf0-avmplus-object.bin");
     }
+    #[test]
+    fn encodes_with_force_long_strings() {
+        let options = EncodeOptions {
+            force_long_strings: true,
+            ..Default::default()
+        };
+        let value = s("foo");
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+        assert_eq!(buf, [0x0C, 0, 0, 0, 3, b'f', b'o', b'o']);
+        assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), value);
+    }
+    #[test]
+    fn encodes_with_object_references() {
+        let input = include_bytes!("../testdata/amf0-ref-test.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+
+        let mut plain = Vec::new();
+        value.write_to(&mut plain).unwrap();
+        assert!(plain.len() > input.len());
+
+        let options = EncodeOptions {
+            use_object_references: true,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+        assert_eq!(buf, &input[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn finish_flushes_writer() {
+        use std::io::BufWriter;
+
+        let expected = include_bytes!("../testdata/amf0-number.bin");
+
+        let mut encoder = Encoder::new(BufWriter::new(Vec::new()));
+        encoder.encode(&n(3.5)).unwrap();
+        assert!(encoder.into_inner().get_ref().is_empty());
+
+        let mut encoder = Encoder::new(BufWriter::new(Vec::new()));
+        encoder.encode(&n(3.5)).unwrap();
+        assert_eq!(encoder.finish().unwrap().get_ref(), &expected[..]);
+    }
 
     fn s(s: &str) -> Value {
         Value::String(s.to_string())
@@ -304,4 +543,75 @@ mod tests {
             })
             .collect()
     }
+
+    #[test]
+    fn composes_object_body() {
+        let mut buf = vec![0x03]; // OBJECT marker
+        let mut encoder = Encoder::new(&mut buf);
+        encoder.write_pair("a", &Value::Number(1.0)).unwrap();
+        encoder
+            .write_pair("b", &Value::String("x".to_string()))
+            .unwrap();
+        encoder.write_object_end().unwrap();
+
+        assert_eq!(
+            Value::read_from(&mut &buf[..]).unwrap(),
+            Value::Object {
+                class_name: None,
+                entries: vec![
+                    Pair {
+                        key: "a".to_string(),
+                        value: Value::Number(1.0),
+                    },
+                    Pair {
+                        key: "b".to_string(),
+                        value: Value::String("x".to_string()),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn overrides_date_time_zone_if_configured() {
+        let date = Value::Date {
+            unix_time: time::Duration::from_millis(1_590_796_800_000),
+            time_zone: 0,
+        };
+        let mut buf = Vec::new();
+        date.write_to_with_options(&mut buf, &EncodeOptions::default())
+            .unwrap();
+        assert_eq!(&buf[..], &include_bytes!("../testdata/amf0-date.bin")[..]);
+
+        let options = EncodeOptions {
+            amf0_date_time_zone: Some(-540),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        date.write_to_with_options(&mut buf, &options).unwrap();
+        assert_eq!(&buf[9..], &(-540i16).to_be_bytes()[..]);
+        assert_eq!(
+            Value::read_from(&mut &buf[..]).unwrap(),
+            Value::Date {
+                unix_time: time::Duration::from_millis(1_590_796_800_000),
+                time_zone: -540,
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", target_pointer_width = "64"))]
+    fn rejects_too_long_strings() {
+        use crate::error::{check_len, ValidationError};
+
+        // `encode_long_str` checks the length of a string with this before writing it.
+        assert_eq!(check_len(0xFFFF_FFFF, 0xFFFF_FFFF), Ok(()));
+        assert_eq!(
+            check_len(0x1_0000_0000, 0xFFFF_FFFF),
+            Err(ValidationError::TooLarge {
+                len: 0x1_0000_0000,
+                max: 0xFFFF_FFFF,
+            })
+        );
+    }
 }
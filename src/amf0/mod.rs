@@ -14,17 +14,27 @@
 //! assert_eq!(number, decoded);
 //! ```
 use crate::amf3;
-use crate::{DecodeResult, Pair};
-use std::io;
-use std::time;
+use crate::error::{ConversionError, DecodeError, EncodeError, ValidationError};
+use crate::io;
+use crate::options::EncodeOptions;
+use crate::{DecodeResult, EncodeResult, Pair, APPROX_NODE_SIZE};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use core::time;
 
 pub use self::decode::Decoder;
 pub use self::encode::Encoder;
+pub use self::value_ref::ValueRef;
 
 mod decode;
 mod encode;
+mod value_ref;
 
-mod marker {
+pub(crate) mod marker {
     pub const NUMBER: u8 = 0x00;
     pub const BOOLEAN: u8 = 0x01;
     pub const STRING: u8 = 0x02;
@@ -61,6 +71,7 @@ mod marker {
 /// assert_eq!(number, decoded);
 /// ```
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[non_exhaustive]
 pub enum Value {
     /// See [2.2 Number Type]
     /// (http://download.macromedia.com/pub/labs/amf/amf0_spec_121207.pdf#page=5&zoom=auto,90,667).
@@ -104,6 +115,20 @@ pub enum Value {
     EcmaArray {
         /// Entries of the associative array.
         entries: Vec<Pair<String, Value>>,
+
+        /// The entry count written in the encoded data, if it differs from `entries.len()`
+        /// and `DecodeOptions::preserve_ecma_array_counts` is enabled.
+        ///
+        /// Some encoders write an incorrect count (e.g., Flash always writes zero for a hash).
+        /// `None` means that the count is `entries.len()`.
+        /// The count is written instead of `entries.len()` only if
+        /// `EncodeOptions::preserve_ecma_array_counts` is enabled.
+        ///
+        /// Note that adding this field was a breaking change:
+        /// code which constructs or matches `EcmaArray` with all its fields has to specify it
+        /// (usually `declared_count: None` or `..`).
+        /// The field takes part in the comparisons (e.g., `==` and `cmp_canonical`).
+        declared_count: Option<u32>,
     },
 
     /// [2.12 Strict Array Type]
@@ -132,8 +157,88 @@ pub enum Value {
     /// See [3.1 AVM+ Type Marker]
     /// (http://download.macromedia.com/pub/labs/amf/amf0_spec_121207.pdf#page=8&zoom=auto,90,518).
     AvmPlus(amf3::Value),
+
+    /// A placeholder for a value of an unsupported type.
+    ///
+    /// This is produced only if `DecodeOptions::tolerate_unsupported` is enabled
+    /// (see [2.15 Unsupported Type]
+    /// (http://download.macromedia.com/pub/labs/amf/amf0_spec_121207.pdf#page=7&zoom=auto,90,249)).
+    /// It is always encoded with the `UNSUPPORTED` marker.
+    Unsupported {
+        /// The marker of the value (`MOVIECLIP`, `UNSUPPORTED` or `RECORDSET`).
+        marker: u8,
+    },
+
+    /// A placeholder for a long string whose bytes have been streamed into a sink.
+    ///
+    /// This is produced only by a decoder which has a sink (see `Decoder::with_byte_array_sink`).
+    /// The bytes of the string are at `offset..offset + len` of the output of the sink.
+    /// It cannot be encoded (`ValidationError::StreamedPlaceholder` is returned).
+    StreamedLongString {
+        /// The offset of the bytes in the output of the sink.
+        offset: usize,
+
+        /// The number of the bytes.
+        len: usize,
+    },
 }
 impl Value {
+    /// Makes an empty anonymous `Object` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf0::Value;
+    ///
+    /// let mut buf = Vec::new();
+    /// Value::empty_object().write_to(&mut buf).unwrap();
+    /// assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), Value::empty_object());
+    /// ```
+    pub fn empty_object() -> Self {
+        Value::Object {
+            class_name: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Makes an empty `EcmaArray` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf0::Value;
+    ///
+    /// let mut buf = Vec::new();
+    /// Value::empty_ecma_array().write_to(&mut buf).unwrap();
+    /// assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), Value::empty_ecma_array());
+    /// ```
+    pub fn empty_ecma_array() -> Self {
+        Value::EcmaArray {
+            entries: Vec::new(),
+            declared_count: None,
+        }
+    }
+
+    /// Makes a `Date` value from a Unix timestamp in milliseconds.
+    ///
+    /// The time zone is set to zero.
+    /// Like the decoder, this returns `DecodeError::InvalidDate` if `millis` is negative.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf0::Value;
+    ///
+    /// let date = Value::date_from_millis(1_590_796_800_000).unwrap();
+    /// assert_eq!(date.date_millis(), Some(1_590_796_800_000));
+    /// ```
+    pub fn date_from_millis(millis: i64) -> DecodeResult<Value> {
+        let millis = u64::try_from(millis).map_err(|_| DecodeError::InvalidDate {
+            millis: millis as f64,
+        })?;
+        Ok(Value::Date {
+            unix_time: time::Duration::from_millis(millis),
+            time_zone: 0,
+        })
+    }
+
     /// Reads an AMF0 encoded `Value` from `reader`.
     ///
     /// Note that reference objects are copied in the decoding phase
@@ -147,13 +252,136 @@ impl Value {
     }
 
     /// Writes the AMF0 encoded bytes of this value to `writer`.
-    pub fn write_to<W>(&self, writer: W) -> io::Result<()>
+    pub fn write_to<W>(&self, writer: W) -> EncodeResult<()>
     where
         W: io::Write,
     {
         Encoder::new(writer).encode(self)
     }
 
+    /// Writes the AMF0 encoded bytes of this value to `writer` with the given options.
+    pub fn write_to_with_options<W>(&self, writer: W, options: &EncodeOptions) -> EncodeResult<()>
+    where
+        W: io::Write,
+    {
+        Encoder::with_options(writer, options.clone()).encode(self)
+    }
+
+    /// Checks the structural invariants which `write_to` requires.
+    ///
+    /// `write_to` returns `EncodeError::Invalid` for a value which fails this check.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match Encoder::new(io::sink()).encode(self) {
+            Err(EncodeError::Invalid(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the AMF0 encoded bytes of this value to `writer`,
+    /// returning the number of bytes written.
+    pub fn write_to_counting<W>(&self, writer: W) -> EncodeResult<usize>
+    where
+        W: io::Write,
+    {
+        let mut writer = crate::CountingWriter::with_inner(writer);
+        self.write_to(&mut writer)?;
+        Ok(writer.count())
+    }
+
+    /// Returns a quick estimate of the number of bytes which `write_to` writes for this value.
+    ///
+    /// This is meant to reserve a buffer in advance, and is cheaper than actually encoding the value
+    /// (e.g., by `write_to_counting`).
+    /// The estimate is the sum of the lengths of the strings (and the byte arrays and the vectors)
+    /// plus a fixed overhead of 16 bytes for each value and each key.
+    /// It is not an exact size, but it is never smaller than the encoded size,
+    /// and at most 16 times of it.
+    pub fn approx_size(&self) -> usize {
+        let size = match *self {
+            Value::String(ref x) | Value::XmlDocument(ref x) => x.len(),
+            Value::Object {
+                ref class_name,
+                ref entries,
+            } => class_name.as_ref().map_or(0, String::len) + approx_pairs_size(entries),
+            Value::EcmaArray { ref entries, .. } => approx_pairs_size(entries),
+            Value::Array { ref entries } => entries.iter().map(Value::approx_size).sum(),
+            Value::AvmPlus(ref x) => x.approx_size(),
+            _ => 0,
+        };
+        APPROX_NODE_SIZE + size
+    }
+
+    /// Returns the maximum nesting level of this value.
+    ///
+    /// A scalar (or an empty collection) is `1`, an array of scalars is `2`,
+    /// an object which has an array of scalars is `3`, and so on.
+    /// An `AvmPlus` wrapper is not counted (as by the decoders),
+    /// so it has the depth of the wrapped AMF3 value (see `amf3::Value::depth`).
+    ///
+    /// The nesting limit of the decoders (`DecodeError::DepthLimitExceeded`) does not count scalars,
+    /// so a decoded value has a depth of at most 257.
+    pub fn depth(&self) -> usize {
+        match *self {
+            Value::Object { ref entries, .. } | Value::EcmaArray { ref entries, .. } => {
+                1 + pairs_depth(entries)
+            }
+            Value::Array { ref entries } => 1 + entries.iter().map(Value::depth).max().unwrap_or(0),
+            Value::AvmPlus(ref x) => x.depth(),
+            _ => 1,
+        }
+    }
+
+    /// Returns the number of the values in this value (including itself).
+    ///
+    /// The values of the entries of objects and ECMA arrays are counted (but not their keys).
+    /// An `AvmPlus` wrapper is counted as one value in addition to the nodes of the wrapped AMF3 value
+    /// (see `amf3::Value::count_nodes`).
+    pub fn count_nodes(&self) -> usize {
+        let children = match *self {
+            Value::Object { ref entries, .. } | Value::EcmaArray { ref entries, .. } => {
+                entries.iter().map(|p| p.value.count_nodes()).sum()
+            }
+            Value::Array { ref entries } => entries.iter().map(Value::count_nodes).sum(),
+            Value::AvmPlus(ref x) => x.count_nodes(),
+            _ => 0,
+        };
+        1 + children
+    }
+
+    /// Returns the name of the variant of this value (e.g., `"Number"` or `"EcmaArray"`).
+    ///
+    /// The name is the same as the variant name and is stable across versions.
+    /// Note that a typed object is also an `"Object"`.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Number(_) => "Number",
+            Value::Boolean(_) => "Boolean",
+            Value::String(_) => "String",
+            Value::Object { .. } => "Object",
+            Value::Null => "Null",
+            Value::Undefined => "Undefined",
+            Value::EcmaArray { .. } => "EcmaArray",
+            Value::Array { .. } => "Array",
+            Value::Date { .. } => "Date",
+            Value::XmlDocument(_) => "XmlDocument",
+            Value::AvmPlus(_) => "AvmPlus",
+            Value::Unsupported { .. } => "Unsupported",
+            Value::StreamedLongString { .. } => "StreamedLongString",
+        }
+    }
+
+    /// Returns `true` if this is a complex value which can be referenced
+    /// by a reference type (see [2.9 Reference Type]
+    /// (http://download.macromedia.com/pub/labs/amf/amf0_spec_121207.pdf#page=6)).
+    ///
+    /// Those are (typed and anonymous) objects, ECMA arrays and strict arrays.
+    pub fn is_reference_type(&self) -> bool {
+        matches!(
+            *self,
+            Value::Object { .. } | Value::EcmaArray { .. } | Value::Array { .. }
+        )
+    }
+
     /// Tries to convert the value as a `str` reference.
     pub fn try_as_str(&self) -> Option<&str> {
         match *self {
@@ -173,6 +401,45 @@ impl Value {
         }
     }
 
+    /// Returns `true` if this is a finite number (i.e., neither `NaN` nor an infinity).
+    ///
+    /// An `AvmPlus` value is checked as an AMF3 value.
+    /// See also `DecodeOptions::reject_non_finite_numbers`.
+    pub fn is_finite_number(&self) -> bool {
+        self.try_as_f64().is_some_and(f64::is_finite)
+    }
+
+    /// Returns the Unix timestamp in milliseconds if this is a `Date`.
+    ///
+    /// Returns `None` if the timestamp does not fit in an `i64`.
+    pub fn date_millis(&self) -> Option<i64> {
+        match *self {
+            Value::Date { unix_time, .. } => i64::try_from(unix_time.as_millis()).ok(),
+            Value::AvmPlus(ref x) => x.date_millis(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of the entries if this is a collection value.
+    ///
+    /// Returns `None` for scalar values.
+    pub fn len(&self) -> Option<usize> {
+        match *self {
+            Value::Object { ref entries, .. } => Some(entries.len()),
+            Value::EcmaArray { ref entries, .. } => Some(entries.len()),
+            Value::Array { ref entries } => Some(entries.len()),
+            Value::AvmPlus(ref x) => x.len(),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some(true)` if this is a collection value which has no entries.
+    ///
+    /// This is equivalent to `self.len().map(|n| n == 0)`.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|n| n == 0)
+    }
+
     /// Tries to convert the value as an iterator of the contained values.
     pub fn try_into_values(self) -> Result<Box<dyn Iterator<Item = super::Value>>, Self> {
         match self {
@@ -189,7 +456,7 @@ impl Value {
     /// Tries to convert the value as an iterator of the contained pairs.
     pub fn try_into_pairs(self) -> Result<Box<dyn Iterator<Item = (String, super::Value)>>, Self> {
         match self {
-            Value::EcmaArray { entries } => Ok(Box::new(
+            Value::EcmaArray { entries, .. } => Ok(Box::new(
                 entries
                     .into_iter()
                     .map(|p| (p.key, super::Value::Amf0(p.value))),
@@ -207,6 +474,576 @@ impl Value {
             _ => Err(self),
         }
     }
+
+    /// Merges the entries of `other` into this value.
+    ///
+    /// Both values must be an `Object` or an `EcmaArray`.
+    /// The value of an existing key is overwritten (keeping its position),
+    /// and new keys are appended in the order of `other`.
+    ///
+    /// If either value is not object-like, this value is left unchanged and `other` is returned as the error.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf0::{object, Value};
+    ///
+    /// let n = Value::Number;
+    /// let mut base = object(vec![("a", n(1.0)), ("b", n(2.0))].into_iter());
+    /// let overlay = object(vec![("b", n(3.0)), ("c", n(4.0))].into_iter());
+    /// base.merge(overlay).unwrap();
+    /// assert_eq!(base, object(vec![("a", n(1.0)), ("b", n(3.0)), ("c", n(4.0))].into_iter()));
+    /// ```
+    pub fn merge(&mut self, other: Value) -> Result<(), Value> {
+        let pairs = match *self {
+            Value::Object {
+                ref mut entries, ..
+            }
+            | Value::EcmaArray {
+                ref mut entries, ..
+            } => entries,
+            _ => return Err(other),
+        };
+        match other {
+            Value::Object { entries, .. } | Value::EcmaArray { entries, .. } => {
+                merge_pairs(pairs, entries);
+                Ok(())
+            }
+            _ => Err(other),
+        }
+    }
+
+    /// Removes the entries whose keys are rejected by `f` if this is an `Object` or an `EcmaArray`,
+    /// keeping the order of the rest.
+    ///
+    /// Only the entries of this value are filtered (use `walk_mut` to filter nested values too).
+    /// The `declared_count` of an `EcmaArray` is left unchanged.
+    /// `AvmPlus` values are filtered by `amf3::Value::retain_keys`, and other values are left unchanged.
+    pub fn retain_keys<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        match *self {
+            Value::Object {
+                ref mut entries, ..
+            }
+            | Value::EcmaArray {
+                ref mut entries, ..
+            } => entries.retain(|p| f(&p.key)),
+            Value::AvmPlus(ref mut x) => x.retain_keys(f),
+            _ => {}
+        }
+    }
+
+    /// Converts an `EcmaArray` whose keys are the contiguous indices `"0"`..`"n-1"` into a strict `Array`.
+    ///
+    /// The keys may appear in any order; the elements of the resulting array are in index order.
+    /// If this is not such an `EcmaArray` (e.g., it is sparse or has non-index keys),
+    /// the value is returned unchanged as the error.
+    pub fn ecma_array_to_dense(self) -> Result<Value, Value> {
+        let indices = match &self {
+            Value::EcmaArray { entries, .. } => dense_indices(entries),
+            _ => None,
+        };
+        let (entries, indices) = match (self, indices) {
+            (Value::EcmaArray { entries, .. }, Some(indices)) => (entries, indices),
+            (this, _) => return Err(this),
+        };
+        let mut slots = entries.iter().map(|_| None).collect::<Vec<_>>();
+        for (index, e) in indices.into_iter().zip(entries) {
+            slots[index] = Some(e.value);
+        }
+        Ok(Value::Array {
+            entries: slots.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Converts an `EcmaArray` or an `Object` into a strict `Array` by dropping the keys.
+    ///
+    /// The values are kept in entry order, regardless of what the keys are
+    /// (use `ecma_array_to_dense` to order the values by their index keys).
+    /// Other values are returned unchanged.
+    pub fn into_array_lossy(self) -> Value {
+        match self {
+            Value::EcmaArray { entries, .. } | Value::Object { entries, .. } => Value::Array {
+                entries: entries.into_iter().map(|p| p.value).collect(),
+            },
+            _ => self,
+        }
+    }
+
+    /// Converts this value into the equivalent AMF3 value.
+    ///
+    /// A `Number` becomes a `Double`, an `EcmaArray` becomes an `Array` which has only associative entries,
+    /// and an `AvmPlus` value is unwrapped.
+    /// A typed object becomes an AMF3 object whose members are all sealed (an anonymous one, all dynamic).
+    /// The time zone of a `Date` is dropped.
+    /// `DecodeError::UnrepresentableValue` is returned for an `Unsupported` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{amf0, amf3};
+    ///
+    /// let value = amf0::array(vec![amf0::number(1), amf0::string("foo")]);
+    /// assert_eq!(
+    ///     value.to_amf3().unwrap(),
+    ///     amf3::Value::Array {
+    ///         assoc_entries: Vec::new(),
+    ///         dense_entries: vec![amf3::Value::Double(1.0), amf3::Value::String("foo".to_string())],
+    ///     }
+    /// );
+    /// ```
+    pub fn to_amf3(&self) -> DecodeResult<amf3::Value> {
+        let pairs_to_amf3 = |entries: &[Pair<String, Value>]| {
+            entries
+                .iter()
+                .map(|p| {
+                    Ok(Pair {
+                        key: p.key.clone(),
+                        value: p.value.to_amf3()?,
+                    })
+                })
+                .collect::<DecodeResult<Vec<_>>>()
+        };
+        Ok(match *self {
+            Value::Number(x) => amf3::Value::Double(x),
+            Value::Boolean(x) => amf3::Value::Boolean(x),
+            Value::String(ref x) => amf3::Value::String(x.clone()),
+            Value::Object {
+                ref class_name,
+                ref entries,
+            } => amf3::Value::Object {
+                class_name: class_name.clone(),
+                sealed_count: if class_name.is_some() {
+                    entries.len()
+                } else {
+                    0
+                },
+                entries: pairs_to_amf3(entries)?,
+            },
+            Value::Null => amf3::Value::Null,
+            Value::Undefined => amf3::Value::Undefined,
+            Value::EcmaArray { ref entries, .. } => amf3::Value::Array {
+                assoc_entries: pairs_to_amf3(entries)?,
+                dense_entries: Vec::new(),
+            },
+            Value::Array { ref entries } => amf3::Value::Array {
+                assoc_entries: Vec::new(),
+                dense_entries: entries
+                    .iter()
+                    .map(Value::to_amf3)
+                    .collect::<DecodeResult<_>>()?,
+            },
+            Value::Date { unix_time, .. } => amf3::Value::Date { unix_time },
+            Value::XmlDocument(ref x) => amf3::Value::XmlDocument(x.clone()),
+            Value::AvmPlus(ref x) => x.clone(),
+            Value::Unsupported { .. } | Value::StreamedLongString { .. } => {
+                return Err(DecodeError::UnrepresentableValue {
+                    type_name: self.type_name(),
+                    version: crate::Version::Amf3,
+                })
+            }
+        })
+    }
+
+    /// Returns the entries as a map from index to value
+    /// if this is an `EcmaArray` or an `Object` whose keys are all array indices (e.g., `"0"` or `"5"`).
+    ///
+    /// This is how an ActionScript array with gaps is encoded.
+    /// Returns `None` if any key is not an index (keys with leading zeros such as `"01"` are not indices).
+    /// If a key is duplicated, the last entry wins.
+    pub fn as_sparse_array(&self) -> Option<BTreeMap<usize, &Value>> {
+        match *self {
+            Value::EcmaArray { ref entries, .. } | Value::Object { ref entries, .. } => entries
+                .iter()
+                .map(|p| parse_index(&p.key).map(|i| (i, &p.value)))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries as an insertion-ordered map from key to value
+    /// if this is an `Object` or an `EcmaArray`.
+    ///
+    /// The lookup takes constant time, unlike a linear scan of the entries.
+    /// If a key is duplicated, the last entry wins but the key keeps the position of its first entry.
+    #[cfg(feature = "indexmap")]
+    pub fn object_map(&self) -> Option<indexmap::IndexMap<&str, &Value>> {
+        match *self {
+            Value::EcmaArray { ref entries, .. } | Value::Object { ref entries, .. } => {
+                Some(entries.iter().map(|p| (p.key.as_str(), &p.value)).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Sorts the entries of the objects and the ECMA arrays in this value by key, recursively.
+    ///
+    /// The sort is stable, so entries with the same key keep their relative order.
+    /// `AvmPlus` values are sorted by `amf3::Value::sort_keys`.
+    pub fn sort_keys(&mut self) {
+        match *self {
+            Value::Object {
+                ref mut entries, ..
+            }
+            | Value::EcmaArray {
+                ref mut entries, ..
+            } => sort_pairs(entries),
+            Value::Array { ref mut entries } => entries.iter_mut().for_each(Value::sort_keys),
+            Value::AvmPlus(ref mut x) => x.sort_keys(),
+            _ => {}
+        }
+    }
+
+    /// Calls `f` on this value and all the values contained in it, recursively.
+    ///
+    /// The values are visited in pre-order (i.e., a collection is visited before its entries),
+    /// and the entries of a collection are visited in order.
+    /// The AMF3 value of an `AvmPlus` value is not visited (use `amf3::Value::walk` for it).
+    pub fn walk<F>(&self, mut f: F)
+    where
+        F: FnMut(&Value),
+    {
+        self.walk_with(&mut f);
+    }
+
+    fn walk_with<F>(&self, f: &mut F)
+    where
+        F: FnMut(&Value),
+    {
+        f(self);
+        match *self {
+            Value::Object { ref entries, .. } | Value::EcmaArray { ref entries, .. } => {
+                entries.iter().for_each(|p| p.value.walk_with(f))
+            }
+            Value::Array { ref entries } => entries.iter().for_each(|v| v.walk_with(f)),
+            _ => {}
+        }
+    }
+
+    /// Calls `f` on this value and all the values contained in it, recursively, with mutable access.
+    ///
+    /// The traversal order is the same as `walk`.
+    /// Since `f` is called on a collection before its entries,
+    /// the entries visited are the ones after `f` is applied to the collection.
+    pub fn walk_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Value),
+    {
+        self.walk_mut_with(&mut f);
+    }
+
+    fn walk_mut_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Value),
+    {
+        f(self);
+        match *self {
+            Value::Object {
+                ref mut entries, ..
+            }
+            | Value::EcmaArray {
+                ref mut entries, ..
+            } => entries.iter_mut().for_each(|p| p.value.walk_mut_with(f)),
+            Value::Array { ref mut entries } => entries.iter_mut().for_each(|v| v.walk_mut_with(f)),
+            _ => {}
+        }
+    }
+
+    /// Returns this value with the keys sorted by `sort_keys`.
+    pub fn sorted(mut self) -> Self {
+        self.sort_keys();
+        self
+    }
+
+    /// Compares two values with a total order.
+    ///
+    /// Values of different variants are ordered by the declaration order of the variants
+    /// (as `PartialOrd` does), and numbers are compared by `f64::total_cmp`.
+    /// So, unlike `PartialOrd`, `NaN` is comparable with any number
+    /// (a positive `NaN` is greater than any other number and a negative one is less)
+    /// and `-0.0` is less than `+0.0`.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b))
+            | (Value::XmlDocument(a), Value::XmlDocument(b)) => a.cmp(b),
+            (
+                Value::Object {
+                    class_name: a0,
+                    entries: a1,
+                },
+                Value::Object {
+                    class_name: b0,
+                    entries: b1,
+                },
+            ) => a0.cmp(b0).then_with(|| cmp_pairs(a1, b1)),
+            (
+                Value::EcmaArray {
+                    entries: a0,
+                    declared_count: a1,
+                },
+                Value::EcmaArray {
+                    entries: b0,
+                    declared_count: b1,
+                },
+            ) => cmp_pairs(a0, b0).then(a1.cmp(b1)),
+            (Value::Array { entries: a }, Value::Array { entries: b }) => {
+                crate::cmp_slices(a, b, Value::cmp_canonical)
+            }
+            (
+                Value::Date {
+                    unix_time: a0,
+                    time_zone: a1,
+                },
+                Value::Date {
+                    unix_time: b0,
+                    time_zone: b1,
+                },
+            ) => a0.cmp(b0).then(a1.cmp(b1)),
+            (Value::AvmPlus(a), Value::AvmPlus(b)) => a.cmp_canonical(b),
+            (Value::Unsupported { marker: a }, Value::Unsupported { marker: b }) => a.cmp(b),
+            (
+                Value::StreamedLongString {
+                    offset: a0,
+                    len: a1,
+                },
+                Value::StreamedLongString {
+                    offset: b0,
+                    len: b1,
+                },
+            ) => a0.cmp(b0).then(a1.cmp(b1)),
+            _ => self.variant_index().cmp(&other.variant_index()),
+        }
+    }
+
+    /// Feeds this value into `state` in a way consistent with `cmp_canonical`.
+    ///
+    /// That is, values which are equal by `cmp_canonical` produce the same hash.
+    /// Numbers are hashed by their bit patterns.
+    pub fn hash_canonical<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.variant_index());
+        match *self {
+            Value::Number(x) => x.to_bits().hash(state),
+            Value::Boolean(x) => x.hash(state),
+            Value::String(ref x) | Value::XmlDocument(ref x) => x.hash(state),
+            Value::Object {
+                ref class_name,
+                ref entries,
+            } => {
+                class_name.hash(state);
+                hash_pairs(entries, state);
+            }
+            Value::Null | Value::Undefined => {}
+            Value::EcmaArray {
+                ref entries,
+                declared_count,
+            } => {
+                hash_pairs(entries, state);
+                declared_count.hash(state);
+            }
+            Value::Array { ref entries } => {
+                state.write_usize(entries.len());
+                for v in entries {
+                    v.hash_canonical(state);
+                }
+            }
+            Value::Date {
+                unix_time,
+                time_zone,
+            } => {
+                unix_time.hash(state);
+                time_zone.hash(state);
+            }
+            Value::AvmPlus(ref x) => x.hash_canonical(state),
+            Value::Unsupported { marker } => marker.hash(state),
+            Value::StreamedLongString { offset, len } => {
+                offset.hash(state);
+                len.hash(state);
+            }
+        }
+    }
+
+    /// Returns `true` if this value is equal to `other` with a tolerance of `epsilon` for numbers.
+    ///
+    /// Two `Number` values are treated as equal if they are `==` or their difference is within `epsilon`.
+    /// The other values are compared structurally and their non-float leaves are compared by `==`.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => crate::approx_eq_f64(*a, *b, epsilon),
+            (
+                Value::Object {
+                    class_name: a0,
+                    entries: a1,
+                },
+                Value::Object {
+                    class_name: b0,
+                    entries: b1,
+                },
+            ) => a0 == b0 && approx_eq_pairs(a1, b1, epsilon),
+            (
+                Value::EcmaArray {
+                    entries: a0,
+                    declared_count: a1,
+                },
+                Value::EcmaArray {
+                    entries: b0,
+                    declared_count: b1,
+                },
+            ) => approx_eq_pairs(a0, b0, epsilon) && a1 == b1,
+            (Value::Array { entries: a }, Value::Array { entries: b }) => {
+                crate::approx_eq_slices(a, b, |a, b| a.approx_eq(b, epsilon))
+            }
+            (Value::AvmPlus(a), Value::AvmPlus(b)) => a.approx_eq(b, epsilon),
+            _ => self == other,
+        }
+    }
+
+    /// Returns `true` if this value is equal to `other`, treating `Null` and `Undefined` as equal.
+    ///
+    /// The other values are compared structurally and their leaves are compared by `==`
+    /// (including the AMF3 values of `AvmPlus`).
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf0::Value;
+    ///
+    /// assert_ne!(Value::Null, Value::Undefined);
+    /// assert!(Value::Null.loosely_eq(&Value::Undefined));
+    /// ```
+    pub fn loosely_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null | Value::Undefined, Value::Null | Value::Undefined) => true,
+            (
+                Value::Object {
+                    class_name: a0,
+                    entries: a1,
+                },
+                Value::Object {
+                    class_name: b0,
+                    entries: b1,
+                },
+            ) => a0 == b0 && loosely_eq_pairs(a1, b1),
+            (
+                Value::EcmaArray {
+                    entries: a0,
+                    declared_count: a1,
+                },
+                Value::EcmaArray {
+                    entries: b0,
+                    declared_count: b1,
+                },
+            ) => loosely_eq_pairs(a0, b0) && a1 == b1,
+            (Value::Array { entries: a }, Value::Array { entries: b }) => {
+                crate::approx_eq_slices(a, b, Value::loosely_eq)
+            }
+            _ => self == other,
+        }
+    }
+
+    fn variant_index(&self) -> u8 {
+        match *self {
+            Value::Number(_) => 0,
+            Value::Boolean(_) => 1,
+            Value::String(_) => 2,
+            Value::Object { .. } => 3,
+            Value::Null => 4,
+            Value::Undefined => 5,
+            Value::EcmaArray { .. } => 6,
+            Value::Array { .. } => 7,
+            Value::Date { .. } => 8,
+            Value::XmlDocument(_) => 9,
+            Value::AvmPlus(_) => 10,
+            Value::Unsupported { .. } => 11,
+            Value::StreamedLongString { .. } => 12,
+        }
+    }
+}
+impl Default for Value {
+    /// Returns `Null`.
+    ///
+    /// This allows `core::mem::take` to move a value out of a decoded structure.
+    fn default() -> Self {
+        Value::Null
+    }
+}
+impl IntoIterator for Value {
+    type Item = super::Value;
+    type IntoIter = Box<dyn Iterator<Item = super::Value>>;
+
+    /// Converts the value into an iterator of the contained values.
+    ///
+    /// Unlike `try_into_values`, this yields nothing for a non-collection value.
+    fn into_iter(self) -> Self::IntoIter {
+        self.try_into_values()
+            .unwrap_or_else(|_| Box::new(core::iter::empty()))
+    }
+}
+impl FromIterator<(String, Value)> for Value {
+    /// Makes an anonymous `Object` value which has the collected entries.
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        Value::Object {
+            class_name: None,
+            entries: iter.into_iter().map(Pair::from).collect(),
+        }
+    }
+}
+impl FromIterator<Value> for Value {
+    /// Makes a strict `Array` value which has the collected entries.
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        Value::Array {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = ConversionError;
+
+    /// Converts a `Number` value (or an `AvmPlus` value which is convertible to `f64`) into `f64`.
+    fn try_from(f: &Value) -> Result<Self, Self::Error> {
+        f.try_as_f64().ok_or(ConversionError {
+            expected: "Number",
+            found: f.type_name(),
+        })
+    }
+}
+impl TryFrom<&Value> for bool {
+    type Error = ConversionError;
+
+    /// Converts a `Boolean` value (or an `AvmPlus` value which is convertible to `bool`) into `bool`.
+    fn try_from(f: &Value) -> Result<Self, Self::Error> {
+        match *f {
+            Value::Boolean(x) => Ok(x),
+            Value::AvmPlus(ref x) => bool::try_from(x),
+            _ => Err(ConversionError {
+                expected: "Boolean",
+                found: f.type_name(),
+            }),
+        }
+    }
+}
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    /// Converts a `String` or `XmlDocument` value (or an `AvmPlus` value which is convertible to `String`)
+    /// into `String`.
+    fn try_from(f: Value) -> Result<Self, Self::Error> {
+        match f {
+            Value::String(x) | Value::XmlDocument(x) => Ok(x),
+            Value::AvmPlus(x) => String::try_from(x),
+            _ => Err(ConversionError {
+                expected: "String",
+                found: f.type_name(),
+            }),
+        }
+    }
 }
 
 /// Makes a `String` value.
@@ -246,3 +1083,731 @@ where
 pub fn array(entries: Vec<Value>) -> Value {
     Value::Array { entries }
 }
+
+/// Returns `true` if `marker` can be the first byte of an AMF0 encoded value.
+///
+/// The unsupported types, the object end marker and references (which need preceding values) are excluded.
+pub(crate) fn is_leading_marker(marker: u8) -> bool {
+    !matches!(
+        marker,
+        marker::MOVIECLIP
+            | marker::REFERENCE
+            | marker::OBJECT_END_MARKER
+            | marker::UNSUPPORTED
+            | marker::RECORDSET
+    ) && marker <= marker::AVMPLUS_OBJECT
+}
+
+fn approx_pairs_size(pairs: &[Pair<String, Value>]) -> usize {
+    pairs
+        .iter()
+        .map(|p| APPROX_NODE_SIZE + p.key.len() + p.value.approx_size())
+        .sum()
+}
+
+fn pairs_depth(pairs: &[Pair<String, Value>]) -> usize {
+    pairs.iter().map(|p| p.value.depth()).max().unwrap_or(0)
+}
+
+fn approx_eq_pairs(a: &[Pair<String, Value>], b: &[Pair<String, Value>], epsilon: f64) -> bool {
+    crate::approx_eq_slices(a, b, |a, b| {
+        a.key == b.key && a.value.approx_eq(&b.value, epsilon)
+    })
+}
+
+fn loosely_eq_pairs(a: &[Pair<String, Value>], b: &[Pair<String, Value>]) -> bool {
+    crate::approx_eq_slices(a, b, |a, b| a.key == b.key && a.value.loosely_eq(&b.value))
+}
+
+fn hash_pairs<H: Hasher>(pairs: &[Pair<String, Value>], state: &mut H) {
+    state.write_usize(pairs.len());
+    for p in pairs {
+        p.key.hash(state);
+        p.value.hash_canonical(state);
+    }
+}
+
+fn cmp_pairs(a: &[Pair<String, Value>], b: &[Pair<String, Value>]) -> Ordering {
+    crate::cmp_slices(a, b, |a, b| {
+        a.key
+            .cmp(&b.key)
+            .then_with(|| a.value.cmp_canonical(&b.value))
+    })
+}
+
+fn sort_pairs(pairs: &mut [Pair<String, Value>]) {
+    pairs.sort_by(|a, b| a.key.cmp(&b.key));
+    for p in pairs {
+        p.value.sort_keys();
+    }
+}
+
+fn merge_pairs(pairs: &mut Vec<Pair<String, Value>>, others: Vec<Pair<String, Value>>) {
+    for other in others {
+        match pairs.iter_mut().find(|p| p.key == other.key) {
+            Some(p) => p.value = other.value,
+            None => pairs.push(other),
+        }
+    }
+}
+
+/// Returns the index of each entry if the keys are exactly `"0"`..`"n-1"` (in any order).
+fn dense_indices(pairs: &[Pair<String, Value>]) -> Option<Vec<usize>> {
+    let mut seen = alloc::vec![false; pairs.len()];
+    pairs
+        .iter()
+        .map(|p| {
+            let index = parse_index(&p.key).filter(|&i| i < pairs.len())?;
+            if core::mem::replace(&mut seen[index], true) {
+                return None;
+            }
+            Some(index)
+        })
+        .collect()
+}
+
+/// Parses `key` as an array index (a decimal integer without a sign or leading zeros).
+fn parse_index(key: &str) -> Option<usize> {
+    if key.is_empty() || (key.len() > 1 && key.starts_with('0')) {
+        return None;
+    }
+    if !key.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    key.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+    use crate::amf3;
+    use crate::error::ValidationError;
+    use std::f64;
+
+    #[test]
+    fn sorts_mixed_values() {
+        let mut values = [
+            Value::AvmPlus(amf3::Value::Integer(1)),
+            Value::Null,
+            Value::String("a".to_string()),
+            Value::Number(f64::NAN),
+            Value::Number(1.0),
+            Value::Boolean(true),
+            Value::Number(f64::NEG_INFINITY),
+            Value::Boolean(false),
+        ];
+        values.sort_by(Value::cmp_canonical);
+
+        let expected = [
+            Value::Number(f64::NEG_INFINITY),
+            Value::Number(1.0),
+            Value::Number(f64::NAN),
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::String("a".to_string()),
+            Value::Null,
+            Value::AvmPlus(amf3::Value::Integer(1)),
+        ];
+        assert_eq!(values.len(), expected.len());
+        for (a, b) in values.iter().zip(&expected) {
+            assert!(a.cmp_canonical(b).is_eq(), "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn approx_eq_works() {
+        assert_ne!(Value::Number(0.1 + 0.2), Value::Number(0.3));
+        assert!(Value::Number(0.1 + 0.2).approx_eq(&Value::Number(0.3), 1e-9));
+        assert!(!Value::Number(0.1).approx_eq(&Value::Number(0.3), 1e-9));
+
+        let object =
+            |n: f64| super::object(vec![("x", super::array(vec![super::number(n)]))].into_iter());
+        assert!(object(0.1 + 0.2).approx_eq(&object(0.3), 1e-9));
+        assert!(!object(0.1).approx_eq(&object(0.3), 1e-9));
+
+        let avmplus = |n: f64| Value::AvmPlus(amf3::Value::Double(n));
+        assert!(avmplus(0.1 + 0.2).approx_eq(&avmplus(0.3), 1e-9));
+        assert!(!avmplus(0.1).approx_eq(&avmplus(0.3), 1e-9));
+    }
+
+    #[test]
+    fn loosely_eq_works() {
+        assert_ne!(Value::Null, Value::Undefined);
+        assert!(Value::Null.loosely_eq(&Value::Undefined));
+        assert!(Value::Undefined.loosely_eq(&Value::Null));
+        assert!(!Value::Null.loosely_eq(&Value::Number(0.0)));
+
+        let command = |v: Value| {
+            super::object(vec![("args", super::array(vec![v, super::string("foo")]))].into_iter())
+        };
+        assert_ne!(command(Value::Null), command(Value::Undefined));
+        assert!(command(Value::Null).loosely_eq(&command(Value::Undefined)));
+        assert!(!command(Value::Null).loosely_eq(&command(super::string("bar"))));
+
+        let avmplus = |v: amf3::Value| Value::AvmPlus(v);
+        assert!(!avmplus(amf3::Value::Null).loosely_eq(&avmplus(amf3::Value::Undefined)));
+    }
+
+    #[test]
+    fn len_works() {
+        let object = super::object(vec![("a", Value::Null), ("b", Value::Null)].into_iter());
+        assert_eq!(object.len(), Some(2));
+        assert_eq!(object.is_empty(), Some(false));
+        assert_eq!(Value::empty_object().is_empty(), Some(true));
+        assert_eq!(Value::empty_ecma_array().len(), Some(0));
+        assert_eq!(super::array(vec![Value::Null]).len(), Some(1));
+        assert_eq!(Value::AvmPlus(amf3::Value::empty_array()).len(), Some(0));
+        assert_eq!(Value::AvmPlus(amf3::Value::Null).len(), None);
+        assert_eq!(Value::Null.len(), None);
+        assert_eq!(super::string("foo").is_empty(), None);
+    }
+
+    #[test]
+    fn iterates_over_decoded_array() {
+        let input = include_bytes!("../testdata/amf0-strict-array.bin");
+        let array = Value::read_from(&mut &input[..]).unwrap();
+        let mut values = Vec::new();
+        for v in array {
+            values.push(v);
+        }
+        assert_eq!(
+            values,
+            [
+                crate::Value::Amf0(super::number(1)),
+                crate::Value::Amf0(super::string("2")),
+                crate::Value::Amf0(super::number(3)),
+            ]
+        );
+
+        let input = include_bytes!("../testdata/amf0-avmplus-object.bin");
+        let avmplus = Value::read_from(&mut &input[..]).unwrap();
+        assert_eq!(avmplus.into_iter().count(), 3);
+        assert_eq!(Value::Null.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn is_consistent_with_partial_ord_for_comparable_values() {
+        let values = [
+            super::number(-1),
+            super::number(2),
+            super::string("foo"),
+            super::object(vec![("a", super::number(1))].into_iter()),
+            super::object(vec![("a", super::number(2))].into_iter()),
+            super::array(vec![super::number(1)]),
+            Value::Undefined,
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(Some(a.cmp_canonical(b)), a.partial_cmp(b));
+            }
+        }
+    }
+
+    #[test]
+    fn validates_class_name_length() {
+        let value = Value::Object {
+            class_name: Some("a".repeat(0x10000)),
+            entries: vec![],
+        };
+        assert_eq!(
+            value.validate(),
+            Err(ValidationError::TooLarge {
+                len: 0x10000,
+                max: 0xFFFF
+            })
+        );
+        assert_eq!(Value::String("a".repeat(0x10000)).validate(), Ok(()));
+    }
+
+    #[test]
+    fn returns_type_names() {
+        let values = [
+            (Value::Number(1.0), "Number"),
+            (Value::Boolean(true), "Boolean"),
+            (Value::String("a".to_string()), "String"),
+            (Value::empty_object(), "Object"),
+            (Value::Null, "Null"),
+            (Value::Undefined, "Undefined"),
+            (Value::empty_ecma_array(), "EcmaArray"),
+            (Value::Array { entries: vec![] }, "Array"),
+            (
+                Value::Date {
+                    unix_time: std::time::Duration::from_secs(0),
+                    time_zone: 0,
+                },
+                "Date",
+            ),
+            (Value::XmlDocument("<a/>".to_string()), "XmlDocument"),
+            (Value::AvmPlus(amf3::Value::Null), "AvmPlus"),
+        ];
+        for (value, name) in &values {
+            assert_eq!(value.type_name(), *name);
+        }
+    }
+
+    #[test]
+    fn distinguishes_reference_types() {
+        let references = [
+            Value::empty_object(),
+            Value::Object {
+                class_name: Some("a".to_string()),
+                entries: vec![],
+            },
+            Value::empty_ecma_array(),
+            Value::Array { entries: vec![] },
+        ];
+        for value in &references {
+            assert!(value.is_reference_type(), "{:?}", value);
+        }
+
+        let others = [
+            Value::Number(1.0),
+            Value::Boolean(true),
+            Value::String("a".to_string()),
+            Value::Null,
+            Value::Undefined,
+            Value::Date {
+                unix_time: std::time::Duration::from_secs(0),
+                time_zone: 0,
+            },
+            Value::XmlDocument("<a/>".to_string()),
+            Value::AvmPlus(amf3::Value::empty_array()),
+        ];
+        for value in &others {
+            assert!(!value.is_reference_type(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn sorts_keys() {
+        let pair = |key: &str, value| crate::Pair {
+            key: key.to_string(),
+            value,
+        };
+        let value = Value::Array {
+            entries: vec![Value::EcmaArray {
+                entries: vec![
+                    pair("b", Value::Number(1.0)),
+                    pair(
+                        "a",
+                        Value::Object {
+                            class_name: None,
+                            entries: vec![pair("y", Value::Null), pair("x", Value::Null)],
+                        },
+                    ),
+                    pair("b", Value::Number(2.0)),
+                ],
+                declared_count: None,
+            }],
+        };
+        assert_eq!(
+            value.sorted(),
+            Value::Array {
+                entries: vec![Value::EcmaArray {
+                    entries: vec![
+                        pair(
+                            "a",
+                            Value::Object {
+                                class_name: None,
+                                entries: vec![pair("x", Value::Null), pair("y", Value::Null)],
+                            }
+                        ),
+                        pair("b", Value::Number(1.0)),
+                        pair("b", Value::Number(2.0)),
+                    ],
+                    declared_count: None,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn merges_objects() {
+        let pair = |key: &str, n| crate::Pair {
+            key: key.to_string(),
+            value: Value::Number(n),
+        };
+
+        let mut value = Value::EcmaArray {
+            entries: vec![pair("a", 1.0), pair("b", 2.0)],
+            declared_count: None,
+        };
+        value
+            .merge(Value::Object {
+                class_name: None,
+                entries: vec![pair("c", 3.0), pair("a", 4.0)],
+            })
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::EcmaArray {
+                entries: vec![pair("a", 4.0), pair("b", 2.0), pair("c", 3.0)],
+                declared_count: None,
+            }
+        );
+
+        let mut value = Value::empty_object();
+        value.merge(Value::empty_ecma_array()).unwrap();
+        assert_eq!(value, Value::empty_object());
+
+        assert_eq!(value.merge(Value::Null), Err(Value::Null));
+        assert_eq!(
+            Value::Null.merge(Value::empty_object()),
+            Err(Value::empty_object())
+        );
+    }
+
+    #[test]
+    fn retains_keys() {
+        let mut value = super::object(
+            vec![
+                ("a", Value::Null),
+                ("secret", Value::Null),
+                ("b", Value::Null),
+            ]
+            .into_iter(),
+        );
+        value.retain_keys(|k| k != "secret");
+        assert_eq!(
+            value,
+            super::object(vec![("a", Value::Null), ("b", Value::Null)].into_iter())
+        );
+
+        let mut value = Value::AvmPlus(amf3::Value::anonymous_object(vec![(
+            "a",
+            amf3::Value::Null,
+        )]));
+        value.retain_keys(|_| false);
+        assert_eq!(value, Value::AvmPlus(amf3::Value::empty_object()));
+
+        let mut value = super::array(vec![Value::Null]);
+        value.retain_keys(|_| false);
+        assert_eq!(value, super::array(vec![Value::Null]));
+    }
+
+    #[test]
+    fn converts_ordinal_ecma_array_to_dense() {
+        let input = include_bytes!("../testdata/amf0-ecma-ordinal-array.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        let s = |s: &str| Value::String(s.to_string());
+        assert_eq!(
+            value.ecma_array_to_dense(),
+            Ok(Value::Array {
+                entries: vec![s("a"), s("b"), s("c"), s("d")]
+            })
+        );
+
+        let ecma = |keys: &[&str]| Value::EcmaArray {
+            entries: keys
+                .iter()
+                .map(|k| crate::Pair {
+                    key: k.to_string(),
+                    value: s(k),
+                })
+                .collect(),
+            declared_count: None,
+        };
+        assert_eq!(
+            ecma(&["1", "0"]).ecma_array_to_dense(),
+            Ok(Value::Array {
+                entries: vec![s("0"), s("1")]
+            })
+        );
+        assert_eq!(
+            ecma(&[]).ecma_array_to_dense(),
+            Ok(Value::Array { entries: vec![] })
+        );
+        for keys in [
+            &["0", "2"][..],
+            &["0", "a"],
+            &["0", "0"],
+            &["00", "1"],
+            &["+0"],
+        ] {
+            assert_eq!(ecma(keys).ecma_array_to_dense(), Err(ecma(keys)));
+        }
+        assert_eq!(Value::Null.ecma_array_to_dense(), Err(Value::Null));
+    }
+
+    #[test]
+    fn collects_objects_and_arrays() {
+        let object: Value = vec![
+            ("a".to_string(), Value::Number(1.0)),
+            ("b".to_string(), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            object,
+            Value::Object {
+                class_name: None,
+                entries: vec![
+                    crate::Pair {
+                        key: "a".to_string(),
+                        value: Value::Number(1.0),
+                    },
+                    crate::Pair {
+                        key: "b".to_string(),
+                        value: Value::Null,
+                    },
+                ],
+            }
+        );
+
+        let array: Value = vec![Value::Number(1.0), Value::Null].into_iter().collect();
+        assert_eq!(
+            array,
+            Value::Array {
+                entries: vec![Value::Number(1.0), Value::Null],
+            }
+        );
+
+        for value in [object, array] {
+            let mut buf = Vec::new();
+            value.write_to(&mut buf).unwrap();
+            assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn walks_values_in_pre_order() {
+        let s = |s: &str| Value::String(s.to_string());
+        let mut value = crate::amf0::object(
+            vec![
+                ("user", s("foo")),
+                ("password", s("secret")),
+                (
+                    "accounts",
+                    crate::amf0::array(vec![crate::amf0::object(
+                        vec![("password", s("secret")), ("id", Value::Number(1.0))].into_iter(),
+                    )]),
+                ),
+            ]
+            .into_iter(),
+        );
+
+        let mut visited = Vec::new();
+        value.walk(|v| visited.push(v.type_name()));
+        assert_eq!(
+            visited,
+            ["Object", "String", "String", "Array", "Object", "String", "Number"]
+        );
+
+        value.walk_mut(|v| {
+            if let Value::Object { entries, .. } = v {
+                for p in entries.iter_mut().filter(|p| p.key == "password") {
+                    p.value = Value::Null;
+                }
+            }
+        });
+        let mut strings = Vec::new();
+        value.walk(|v| strings.extend(v.try_as_str().map(|s| s.to_string())));
+        assert_eq!(strings, ["foo"]);
+        let mut nulls = 0;
+        value.walk(|v| nulls += usize::from(*v == Value::Null));
+        assert_eq!(nulls, 2);
+    }
+
+    #[test]
+    fn converts_values_into_rust_types() {
+        use crate::error::ConversionError;
+
+        assert_eq!(f64::try_from(&Value::Number(1.5)), Ok(1.5));
+        assert_eq!(bool::try_from(&Value::Boolean(true)), Ok(true));
+        assert_eq!(
+            String::try_from(Value::String("foo".to_string())),
+            Ok("foo".to_string())
+        );
+        assert_eq!(
+            f64::try_from(&amf3::Value::Integer(2).into_amf0_avmplus()),
+            Ok(2.0)
+        );
+
+        assert_eq!(
+            f64::try_from(&Value::String("1.5".to_string())),
+            Err(ConversionError {
+                expected: "Number",
+                found: "String"
+            })
+        );
+        assert_eq!(
+            bool::try_from(&Value::Null),
+            Err(ConversionError {
+                expected: "Boolean",
+                found: "Null"
+            })
+        );
+        assert_eq!(
+            String::try_from(Value::Number(1.0)),
+            Err(ConversionError {
+                expected: "String",
+                found: "Number"
+            })
+        );
+        assert_eq!(
+            String::try_from(amf3::Value::Null.into_amf0_avmplus()),
+            Err(ConversionError {
+                expected: "String",
+                found: "Null"
+            })
+        );
+    }
+
+    #[test]
+    fn reads_sparse_arrays() {
+        let n = Value::Number;
+        let ecma = |entries: Vec<(&str, Value)>| Value::EcmaArray {
+            entries: entries
+                .into_iter()
+                .map(|(k, v)| crate::Pair {
+                    key: k.to_string(),
+                    value: v,
+                })
+                .collect(),
+            declared_count: None,
+        };
+
+        let sparse = ecma(vec![("5", n(5.0)), ("0", n(0.0)), ("2", n(2.0))]);
+        let map = sparse.as_sparse_array().unwrap();
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [0, 2, 5]);
+        assert_eq!(map[&2], &n(2.0));
+
+        let object = crate::amf0::object(vec![("1", n(1.0))].into_iter());
+        assert_eq!(
+            object
+                .as_sparse_array()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [(1, &n(1.0))]
+        );
+        assert_eq!(ecma(vec![]).as_sparse_array(), Some(Default::default()));
+
+        for key in ["length", "-1", "01", "1.5", ""] {
+            assert_eq!(
+                ecma(vec![("0", n(0.0)), (key, n(1.0))]).as_sparse_array(),
+                None
+            );
+        }
+        assert_eq!(n(0.0).as_sparse_array(), None);
+    }
+
+    #[test]
+    fn converts_dates_from_and_to_millis() {
+        use crate::error::DecodeError;
+
+        let epoch = Value::date_from_millis(0).unwrap();
+        assert_eq!(
+            epoch,
+            Value::Date {
+                unix_time: std::time::Duration::from_secs(0),
+                time_zone: 0,
+            }
+        );
+        assert_eq!(epoch.date_millis(), Some(0));
+
+        let date = Value::date_from_millis(1_590_796_800_000).unwrap();
+        let mut buf = Vec::new();
+        date.write_to(&mut buf).unwrap();
+        let decoded = Value::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(decoded.date_millis(), Some(1_590_796_800_000));
+
+        assert_eq!(
+            Value::date_from_millis(-1),
+            Err(DecodeError::InvalidDate { millis: -1.0 })
+        );
+        assert_eq!(Value::Null.date_millis(), None);
+    }
+
+    #[test]
+    fn converts_into_array_lossy() {
+        let input = include_bytes!("../testdata/amf0-ecma-ordinal-array.bin");
+        let ecma = Value::read_from(&mut &input[..]).unwrap();
+        let values = match ecma {
+            Value::EcmaArray { ref entries, .. } => {
+                entries.iter().map(|p| p.value.clone()).collect::<Vec<_>>()
+            }
+            _ => panic!(),
+        };
+        assert_eq!(ecma.into_array_lossy(), Value::Array { entries: values });
+
+        let object = crate::amf0::object(
+            vec![
+                ("b", Value::Number(2.0)),
+                ("a", Value::String("x".to_string())),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(
+            object.into_array_lossy(),
+            Value::Array {
+                entries: vec![Value::Number(2.0), Value::String("x".to_string())]
+            }
+        );
+        assert_eq!(Value::Null.into_array_lossy(), Value::Null);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn views_objects_as_maps() {
+        let value = super::object(
+            [
+                ("b", super::number(1)),
+                ("a", super::number(2)),
+                ("b", super::number(3)),
+            ]
+            .into_iter(),
+        );
+        let map = value.object_map().unwrap();
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), ["b", "a"]);
+        assert_eq!(map.get("a"), Some(&&super::number(2)));
+        assert_eq!(map.get("b"), Some(&&super::number(3)));
+        assert_eq!(map.get("c"), None);
+
+        assert!(Value::empty_ecma_array().object_map().unwrap().is_empty());
+        assert_eq!(Value::Null.object_map(), None);
+    }
+
+    #[test]
+    fn measures_depth() {
+        assert_eq!(super::number(1).depth(), 1);
+        assert_eq!(Value::empty_object().depth(), 1);
+        assert_eq!(super::array(vec![super::number(1)]).depth(), 2);
+
+        let nested = super::object(
+            [
+                ("a", super::string("foo")),
+                ("b", super::array(vec![super::number(1), Value::Null])),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(nested.depth(), 3);
+        assert_eq!(
+            Value::AvmPlus(amf3::Value::Array {
+                assoc_entries: Vec::new(),
+                dense_entries: vec![amf3::Value::Integer(1)],
+            })
+            .depth(),
+            2
+        );
+
+        // The deepest value which the decoder accepts.
+        let mut input = [0x0A, 0, 0, 0, 1].repeat(256);
+        input.extend_from_slice(&[0x05]);
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        assert_eq!(value.depth(), 257);
+    }
+
+    #[test]
+    fn counts_nodes() {
+        assert_eq!(Value::Null.count_nodes(), 1);
+
+        // An object which has two references to the same object of two members.
+        let input = include_bytes!("../testdata/amf0-ref-test.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        assert_eq!(value.count_nodes(), 7);
+
+        let avmplus = Value::AvmPlus(amf3::Value::int_vector([1, 2], false));
+        assert_eq!(avmplus.count_nodes(), 4);
+    }
+}
@@ -0,0 +1,428 @@
+use super::marker;
+use super::Value;
+use crate::amf3;
+use crate::error::{DecodeError, StringContext};
+use crate::io;
+use crate::{DecodeResult, Pair, MAX_DECODE_DEPTH, MAX_PREALLOCATED_ENTRIES};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::cmp;
+use core::str;
+use core::time;
+
+/// AMF0 value which borrows its strings from the decoded buffer.
+///
+/// This is the borrowed counterpart of [`Value`](super::Value).
+///
+/// # Examples
+/// ```
+/// use amf::amf0::{Value, ValueRef};
+///
+/// let mut buf = Vec::new();
+/// Value::String("foo".to_owned()).write_to(&mut buf).unwrap();
+///
+/// let decoded = ValueRef::read_from_slice(&buf).unwrap();
+/// assert_eq!(decoded, ValueRef::String("foo"));
+/// assert_eq!(decoded.to_owned(), Value::String("foo".to_owned()));
+/// ```
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum ValueRef<'a> {
+    /// See [`Value::Number`](super::Value::Number).
+    Number(f64),
+
+    /// See [`Value::Boolean`](super::Value::Boolean).
+    Boolean(bool),
+
+    /// See [`Value::String`](super::Value::String).
+    String(&'a str),
+
+    /// See [`Value::Object`](super::Value::Object).
+    Object {
+        /// The class name of the object.
+        /// `None` means it is an anonymous object.
+        class_name: Option<&'a str>,
+
+        /// Properties of the object.
+        entries: Vec<Pair<&'a str, ValueRef<'a>>>,
+    },
+
+    /// See [`Value::Null`](super::Value::Null).
+    Null,
+
+    /// See [`Value::Undefined`](super::Value::Undefined).
+    Undefined,
+
+    /// See [`Value::EcmaArray`](super::Value::EcmaArray).
+    EcmaArray {
+        /// Entries of the associative array.
+        entries: Vec<Pair<&'a str, ValueRef<'a>>>,
+    },
+
+    /// See [`Value::Array`](super::Value::Array).
+    Array {
+        /// Entries of the array.
+        entries: Vec<ValueRef<'a>>,
+    },
+
+    /// See [`Value::Date`](super::Value::Date).
+    Date {
+        /// Unix timestamp with milliseconds precision.
+        unix_time: time::Duration,
+
+        /// Time zone offset.
+        ///
+        /// Note that this is a reserved field and the value should be zero.
+        time_zone: i16,
+    },
+
+    /// See [`Value::XmlDocument`](super::Value::XmlDocument).
+    XmlDocument(&'a str),
+
+    /// See [`Value::AvmPlus`](super::Value::AvmPlus).
+    AvmPlus(amf3::ValueRef<'a>),
+}
+impl<'a> ValueRef<'a> {
+    /// Reads an AMF0 encoded `ValueRef` from `input` without copying strings.
+    ///
+    /// Reference objects are copied in the same way as [`Value::read_from`](super::Value::read_from).
+    pub fn read_from_slice(input: &'a [u8]) -> DecodeResult<Self> {
+        SliceDecoder::new(input).decode()
+    }
+
+    /// Tries to convert the value as a `str` reference.
+    pub fn try_as_str(&self) -> Option<&'a str> {
+        match *self {
+            ValueRef::String(x) => Some(x),
+            ValueRef::XmlDocument(x) => Some(x),
+            ValueRef::AvmPlus(ref x) => x.try_as_str(),
+            _ => None,
+        }
+    }
+
+    /// Converts this value into the equivalent owned [`Value`](super::Value).
+    pub fn to_owned(&self) -> Value {
+        match *self {
+            ValueRef::Number(x) => Value::Number(x),
+            ValueRef::Boolean(x) => Value::Boolean(x),
+            ValueRef::String(x) => Value::String(x.to_owned()),
+            ValueRef::Object {
+                class_name,
+                ref entries,
+            } => Value::Object {
+                class_name: class_name.map(ToOwned::to_owned),
+                entries: pairs_to_owned(entries),
+            },
+            ValueRef::Null => Value::Null,
+            ValueRef::Undefined => Value::Undefined,
+            ValueRef::EcmaArray { ref entries } => Value::EcmaArray {
+                entries: pairs_to_owned(entries),
+                declared_count: None,
+            },
+            ValueRef::Array { ref entries } => Value::Array {
+                entries: entries.iter().map(ValueRef::to_owned).collect(),
+            },
+            ValueRef::Date {
+                unix_time,
+                time_zone,
+            } => Value::Date {
+                unix_time,
+                time_zone,
+            },
+            ValueRef::XmlDocument(x) => Value::XmlDocument(x.to_owned()),
+            ValueRef::AvmPlus(ref x) => Value::AvmPlus(x.to_owned()),
+        }
+    }
+}
+
+fn pairs_to_owned(pairs: &[Pair<&str, ValueRef>]) -> Vec<Pair<String, Value>> {
+    pairs
+        .iter()
+        .map(|p| Pair {
+            key: p.key.to_owned(),
+            value: p.value.to_owned(),
+        })
+        .collect()
+}
+
+/// AMF0 decoder which reads values directly from a byte slice.
+#[derive(Debug)]
+struct SliceDecoder<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize,
+    complexes: Vec<ValueRef<'a>>,
+}
+impl<'a> SliceDecoder<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        SliceDecoder {
+            input,
+            position: 0,
+            depth: 0,
+            complexes: Vec::new(),
+        }
+    }
+
+    fn decode(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_value()
+    }
+
+    fn decode_value(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let marker = self.read_u8()?;
+        self.decode_marked_value(marker)
+    }
+    fn decode_marked_value(&mut self, marker: u8) -> DecodeResult<ValueRef<'a>> {
+        let offset = self.position - 1;
+        match marker {
+            marker::NUMBER => self.decode_number(),
+            marker::BOOLEAN => self.decode_boolean(),
+            marker::STRING => self.decode_string(),
+            marker::OBJECT => self.decode_object(),
+            marker::MOVIECLIP => Err(DecodeError::Unsupported { marker, offset }),
+            marker::NULL => Ok(ValueRef::Null),
+            marker::UNDEFINED => Ok(ValueRef::Undefined),
+            marker::REFERENCE => self.decode_reference(),
+            marker::ECMA_ARRAY => self.decode_ecma_array(),
+            marker::OBJECT_END_MARKER => Err(DecodeError::UnexpectedObjectEnd),
+            marker::STRICT_ARRAY => self.decode_strict_array(),
+            marker::DATE => self.decode_date(),
+            marker::LONG_STRING => self.decode_long_string(),
+            marker::UNSUPPORTED => Err(DecodeError::Unsupported { marker, offset }),
+            marker::RECORDSET => Err(DecodeError::Unsupported { marker, offset }),
+            marker::XML_DOCUMENT => self.decode_xml_document(),
+            marker::TYPED_OBJECT => self.decode_typed_object(),
+            marker::AVMPLUS_OBJECT => self.decode_avmplus(),
+            _ => Err(DecodeError::Unknown { marker, offset }),
+        }
+    }
+    fn decode_number(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let n = self.read_f64()?;
+        Ok(ValueRef::Number(n))
+    }
+    fn decode_boolean(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let b = self.read_u8()? != 0;
+        Ok(ValueRef::Boolean(b))
+    }
+    fn decode_string(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let len = self.read_u16()? as usize;
+        self.read_utf8(len, StringContext::Value)
+            .map(ValueRef::String)
+    }
+    fn decode_object(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this| {
+            let entries = this.decode_pairs()?;
+            Ok(ValueRef::Object {
+                class_name: None,
+                entries,
+            })
+        })
+    }
+    fn decode_reference(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let index = self.read_u16()? as usize;
+        self.complexes
+            .get(index)
+            .ok_or(DecodeError::OutOfRangeReference { index })
+            .and_then(|v| {
+                if *v == ValueRef::Null {
+                    Err(DecodeError::CircularReference { index })
+                } else {
+                    Ok(v.clone())
+                }
+            })
+    }
+    fn decode_ecma_array(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this| {
+            let _count = this.read_u32()? as usize;
+            let entries = this.decode_pairs()?;
+            Ok(ValueRef::EcmaArray { entries })
+        })
+    }
+    fn decode_strict_array(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this| {
+            let count = this.read_u32()? as usize;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.decode_value()?);
+            }
+            Ok(ValueRef::Array { entries })
+        })
+    }
+    fn decode_date(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let millis = self.read_f64()?;
+        let time_zone = self.read_i16()?;
+        if !(millis.is_finite() && millis.is_sign_positive()) {
+            Err(DecodeError::InvalidDate { millis })
+        } else {
+            Ok(ValueRef::Date {
+                unix_time: time::Duration::from_millis(millis as u64),
+                time_zone,
+            })
+        }
+    }
+    fn decode_long_string(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let len = self.read_u32()? as usize;
+        self.read_utf8(len, StringContext::Value)
+            .map(ValueRef::String)
+    }
+    fn decode_xml_document(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let len = self.read_u32()? as usize;
+        self.read_utf8(len, StringContext::Value)
+            .map(ValueRef::XmlDocument)
+    }
+    fn decode_typed_object(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this| {
+            let len = this.read_u16()? as usize;
+            let class_name = this.read_utf8(len, StringContext::ClassName)?;
+            let entries = this.decode_pairs()?;
+            Ok(ValueRef::Object {
+                class_name: Some(class_name),
+                entries,
+            })
+        })
+    }
+    fn decode_avmplus(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let mut decoder = amf3::SliceDecoder::new_at(self.input, self.position, self.depth);
+        let value = decoder.decode();
+        self.position = decoder.position();
+        Ok(ValueRef::AvmPlus(value?))
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_slice(1)?[0])
+    }
+    fn read_u16(&mut self) -> io::Result<u16> {
+        Ok(BigEndian::read_u16(self.read_slice(2)?))
+    }
+    fn read_i16(&mut self) -> io::Result<i16> {
+        Ok(BigEndian::read_i16(self.read_slice(2)?))
+    }
+    fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(BigEndian::read_u32(self.read_slice(4)?))
+    }
+    fn read_f64(&mut self) -> io::Result<f64> {
+        Ok(BigEndian::read_f64(self.read_slice(8)?))
+    }
+    fn read_slice(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let rest = &self.input[self.position..];
+        if rest.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+    fn read_utf8(&mut self, len: usize, context: StringContext) -> DecodeResult<&'a str> {
+        let offset = self.position;
+        let bytes = self.read_slice(len)?;
+        match str::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(DecodeError::Utf8 {
+                error: String::from_utf8(bytes.to_owned()).unwrap_err(),
+                offset,
+                context,
+            }),
+        }
+    }
+    fn decode_pairs(&mut self) -> DecodeResult<Vec<Pair<&'a str, ValueRef<'a>>>> {
+        let mut entries = Vec::new();
+        loop {
+            let len = self.read_u16()? as usize;
+            let key = self.read_utf8(len, StringContext::Key)?;
+            let marker = self.read_u8()?;
+            if key.is_empty() && marker == marker::OBJECT_END_MARKER {
+                break;
+            }
+            let value = self.decode_marked_value(marker)?;
+            entries.push(Pair { key, value });
+        }
+        Ok(entries)
+    }
+    fn decode_complex_type<F>(&mut self, f: F) -> DecodeResult<ValueRef<'a>>
+    where
+        F: FnOnce(&mut Self) -> DecodeResult<ValueRef<'a>>,
+    {
+        if self.depth == MAX_DECODE_DEPTH {
+            return Err(DecodeError::DepthLimitExceeded {
+                offset: self.position,
+            });
+        }
+        let index = self.complexes.len();
+        self.complexes.push(ValueRef::Null);
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        let value = value?;
+        self.complexes[index] = value.clone();
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Value;
+    use super::ValueRef;
+    use crate::error::DecodeError;
+    use crate::io;
+
+    macro_rules! decode_same {
+        ($file:expr) => {{
+            let input = include_bytes!(concat!("../testdata/", $file));
+            let expected = Value::read_from(&mut &input[..]);
+            let value = ValueRef::read_from_slice(&input[..]).map(|v| v.to_owned());
+            assert_eq!(value, expected);
+        }};
+    }
+
+    #[test]
+    fn decodes_same_as_owned_value() {
+        decode_same!("amf0-avmplus-object.bin");
+        decode_same!("amf0-boolean-true.bin");
+        decode_same!("amf0-complex-encoded-string.bin");
+        decode_same!("amf0-date.bin");
+        decode_same!("amf0-ecma-ordinal-array.bin");
+        decode_same!("amf0-hash.bin");
+        decode_same!("amf0-long-string.bin");
+        decode_same!("amf0-null.bin");
+        decode_same!("amf0-number.bin");
+        decode_same!("amf0-object.bin");
+        decode_same!("amf0-ref-test.bin");
+        decode_same!("amf0-strict-array.bin");
+        decode_same!("amf0-string.bin");
+        decode_same!("amf0-typed-object.bin");
+        decode_same!("amf0-undefined.bin");
+        decode_same!("amf0-untyped-object.bin");
+        decode_same!("amf0-xml-doc.bin");
+    }
+    #[test]
+    fn reports_same_errors_as_owned_value() {
+        decode_same!("amf0-avmplus-unknown-marker.bin");
+        decode_same!("amf0-bad-object-end.bin");
+        decode_same!("amf0-bad-reference.bin");
+        decode_same!("amf0-bad-utf8-key.bin");
+        decode_same!("amf0-circular-reference.bin");
+        decode_same!("amf0-date-invalid.bin");
+        decode_same!("amf0-movieclip.bin");
+        decode_same!("amf0-strict-array-unknown-marker.bin");
+        decode_same!("amf0-unknown-marker.bin");
+    }
+    #[test]
+    fn borrows_from_input() {
+        let input = include_bytes!("../testdata/amf0-string.bin");
+        let value = ValueRef::read_from_slice(&input[..]).unwrap();
+        let s = match value {
+            ValueRef::String(s) => s,
+            _ => panic!(),
+        };
+        assert_eq!(s, "this is a テスト");
+        assert!(input.as_ptr_range().contains(&s.as_ptr()));
+    }
+    #[test]
+    fn unexpected_eof() {
+        let input = include_bytes!("../testdata/amf0-object-partial.bin");
+        match ValueRef::read_from_slice(&input[..]) {
+            Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            _ => panic!(),
+        }
+    }
+}
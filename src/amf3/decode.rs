@@ -1,8 +1,20 @@
-use crate::error::DecodeError;
-use crate::{DecodeResult, Pair};
-use byteorder::{BigEndian, ReadBytesExt};
-use std::io;
-use std::time;
+use crate::error::{DecodeError, StringContext, Warning};
+use crate::intern::{self, Interner};
+use crate::io;
+use crate::options::{DecodeLimits, DecodeOptions};
+use crate::registry::ClassRegistry;
+use crate::{
+    DecodeResult, Pair, MAX_DECODE_DEPTH, MAX_PREALLOCATED_BYTES, MAX_PREALLOCATED_ENTRIES,
+};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::any::Any;
+use core::cmp;
+use core::mem;
+use core::str;
+use core::time;
 
 use super::marker;
 use super::Value;
@@ -20,27 +32,118 @@ enum SizeOrIndex {
     Index(usize),
 }
 
+/// The reference tables of an AMF3 decoder which can outlive the decoder.
+#[derive(Debug, Default)]
+pub(crate) struct ReferenceTables {
+    traits: Vec<Trait>,
+    strings: Vec<String>,
+    complexes: Vec<Value>,
+}
+
 /// AMF3 decoder.
+///
+/// `S` is the type of the sink which byte arrays are streamed into (see `with_byte_array_sink`).
 #[derive(Debug)]
-pub struct Decoder<R> {
+pub struct Decoder<R, S = io::Sink> {
     inner: R,
+    position: usize,
+    depth: usize,
     traits: Vec<Trait>,
     strings: Vec<String>,
     complexes: Vec<Value>,
+    scratch: Vec<u8>,
+    interner: Interner,
+    options: DecodeOptions,
+    byte_array_sink: Option<S>,
+    streamed: usize,
+    warnings: Vec<Warning>,
+    allocated: usize,
 }
 impl<R> Decoder<R> {
+    /// Makes this decoder stream the bytes of `ByteArray` values into `sink` as they are read.
+    ///
+    /// This avoids buffering large byte arrays in memory.
+    /// A streamed byte array is decoded as a `StreamedByteArray` placeholder
+    /// which locates its bytes in the output of `sink`,
+    /// and the bytes of consecutive byte arrays are written back-to-back to `sink`.
+    /// The offsets of the placeholders are counted across `decode` calls.
+    /// A reference to a streamed byte array is decoded as the same placeholder
+    /// (i.e., the bytes are not written again but can be found at the same offset).
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::{Decoder, Value};
+    ///
+    /// let mut buf = Vec::new();
+    /// Value::ByteArray(vec![1, 2, 3]).write_to(&mut buf).unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+    /// assert_eq!(
+    ///     decoder.decode().unwrap(),
+    ///     Value::StreamedByteArray { offset: 0, len: 3 }
+    /// );
+    /// assert_eq!(decoder.into_byte_array_sink(), Some(vec![1, 2, 3]));
+    /// ```
+    pub fn with_byte_array_sink<S>(self, sink: S) -> Decoder<R, S> {
+        Decoder {
+            inner: self.inner,
+            position: self.position,
+            depth: self.depth,
+            traits: self.traits,
+            strings: self.strings,
+            complexes: self.complexes,
+            scratch: self.scratch,
+            interner: self.interner,
+            options: self.options,
+            byte_array_sink: Some(sink),
+            streamed: 0,
+            warnings: self.warnings,
+            allocated: self.allocated,
+        }
+    }
+}
+impl<R, S> Decoder<R, S> {
+    /// Unwraps this `Decoder`, returning the sink of the byte arrays (if any).
+    pub fn into_byte_array_sink(self) -> Option<S> {
+        self.byte_array_sink
+    }
     /// Unwraps this `Decoder`, returning the underlying reader.
     pub fn into_inner(self) -> R {
         self.inner
     }
     /// Returns an immutable reference to the underlying reader.
-    pub fn inner(&mut self) -> &R {
+    pub fn inner(&self) -> &R {
         &self.inner
     }
     /// Returns a mutable reference to the underlying reader.
     pub fn inner_mut(&mut self) -> &mut R {
         &mut self.inner
     }
+    /// Returns a mutable reference to the sink of the byte arrays (if any).
+    pub fn byte_array_sink_mut(&mut self) -> Option<&mut S> {
+        self.byte_array_sink.as_mut()
+    }
+    /// Returns the number of bytes consumed by this decoder so far.
+    ///
+    /// Note that bytes read directly from the underlying reader (e.g., via `inner_mut`)
+    /// are not counted.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Takes the warnings collected so far (see `DecodeOptions::collect_warnings`).
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Replaces the limits of this decoder with `limits`.
+    ///
+    /// The new limits apply to the subsequent `decode` calls.
+    /// This is useful to decode a trusted message leniently and then untrusted ones strictly
+    /// without rebuilding the decoder (and its reference tables).
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        self.options.set_limits(limits);
+    }
 }
 impl<R> Decoder<R>
 where
@@ -48,19 +151,101 @@ where
 {
     /// Makes a new instance.
     pub fn new(inner: R) -> Self {
+        Self::with_options(inner, DecodeOptions::default())
+    }
+
+    /// Makes a new instance with the given options.
+    ///
+    /// Note that only `max_object_entries`, `lenient_amf3_dates`, `reject_non_finite_numbers`,
+    /// `collect_warnings`, `reject_duplicate_keys` and `max_total_bytes` affect AMF3 decoding.
+    pub fn with_options(inner: R, options: DecodeOptions) -> Self {
+        Self::new_at(inner, 0, 0, options, None)
+    }
+}
+impl<R, S> Decoder<R, S>
+where
+    R: io::Read,
+    S: io::Write,
+{
+    /// Makes a new instance which starts counting the consumed bytes from `position`
+    /// and the nesting level from `depth`.
+    pub(crate) fn new_at(
+        inner: R,
+        position: usize,
+        depth: usize,
+        options: DecodeOptions,
+        byte_array_sink: Option<S>,
+    ) -> Self {
         Decoder {
             inner,
+            position,
+            depth,
             traits: Vec::new(),
             strings: Vec::new(),
             complexes: Vec::new(),
+            scratch: Vec::new(),
+            interner: Interner::default(),
+            options,
+            byte_array_sink,
+            streamed: 0,
+            warnings: Vec::new(),
+            allocated: 0,
         }
     }
 
     /// Decodes a AMF3 value.
     pub fn decode(&mut self) -> DecodeResult<Value> {
+        self.allocated = 0;
         self.decode_value()
     }
 
+    /// Decodes a AMF3 value nested in an enclosing value,
+    /// sharing the allocation budget (see `DecodeOptions::max_total_bytes`),
+    /// the number of the bytes streamed into the sink and the interned strings with it.
+    pub(crate) fn decode_nested(
+        &mut self,
+        allocated: &mut usize,
+        streamed: &mut usize,
+        interner: &mut Interner,
+    ) -> DecodeResult<Value> {
+        self.allocated = *allocated;
+        self.streamed = *streamed;
+        mem::swap(&mut self.interner, interner);
+        let value = self.decode_value();
+        *allocated = self.allocated;
+        *streamed = self.streamed;
+        mem::swap(&mut self.interner, interner);
+        value
+    }
+
+    /// Decodes a AMF3 value and converts it into a `T` using `registry`.
+    ///
+    /// If the value is not a typed object of the class registered for `T`,
+    /// the decoded value is returned as `Ok(Err(value))` (see `ClassRegistry::from_value`).
+    pub fn decode_with_registry<T: Any>(
+        &mut self,
+        registry: &ClassRegistry<Value>,
+    ) -> DecodeResult<Result<T, Value>> {
+        let value = self.decode()?;
+        Ok(registry.from_value(value))
+    }
+
+    /// Replaces the reference tables of this decoder with `tables`.
+    pub(crate) fn set_reference_tables(&mut self, tables: ReferenceTables) {
+        self.traits = tables.traits;
+        self.strings = tables.strings;
+        self.complexes = tables.complexes;
+    }
+
+    /// Takes the reference tables out of this decoder, leaving empty ones.
+    pub(crate) fn take_reference_tables(&mut self) -> ReferenceTables {
+        ReferenceTables {
+            traits: mem::take(&mut self.traits),
+            strings: mem::take(&mut self.strings),
+            complexes: mem::take(&mut self.complexes),
+        }
+    }
+
     /// Clear the reference tables of this decoder.
     ///
     /// > Similar to AFM 0, AMF 3 object reference tables, object trait reference tables
@@ -74,7 +259,8 @@ where
     }
 
     fn decode_value(&mut self) -> DecodeResult<Value> {
-        let marker = self.inner.read_u8()?;
+        let marker = self.read_u8()?;
+        let offset = self.position - 1;
         match marker {
             marker::UNDEFINED => Ok(Value::Undefined),
             marker::NULL => Ok(Value::Null),
@@ -94,7 +280,7 @@ where
             marker::VECTOR_DOUBLE => self.decode_vector_double(),
             marker::VECTOR_OBJECT => self.decode_vector_object(),
             marker::DICTIONARY => self.decode_dictionary(),
-            _ => Err(DecodeError::Unknown { marker }),
+            _ => Err(DecodeError::Unknown { marker, offset }),
         }
     }
 
@@ -104,7 +290,9 @@ where
         Ok(Value::Integer(n))
     }
     fn decode_double(&mut self) -> DecodeResult<Value> {
-        let n = self.inner.read_f64::<BigEndian>()?;
+        let offset = self.position;
+        let n = self.read_f64()?;
+        self.check_finite_number(n, offset)?;
         Ok(Value::Double(n))
     }
     fn decode_string(&mut self) -> DecodeResult<Value> {
@@ -112,11 +300,17 @@ where
         Ok(Value::String(s))
     }
     fn decode_xml_doc(&mut self) -> DecodeResult<Value> {
-        self.decode_complex_type(|this, len| this.read_utf8(len).map(Value::XmlDocument))
+        self.decode_complex_type(|this, len| {
+            this.read_utf8(len, StringContext::Value)
+                .map(Value::XmlDocument)
+        })
     }
     fn decode_date(&mut self) -> DecodeResult<Value> {
-        self.decode_complex_type(|this, _| {
-            let millis = this.inner.read_f64::<BigEndian>()?;
+        self.decode_complex_type(|this, size| {
+            if size != 0 && !this.options.lenient_amf3_dates {
+                return Err(DecodeError::InvalidDateHeader { size });
+            }
+            let millis = this.read_f64()?;
             if !(millis.is_finite() && millis.is_sign_positive()) {
                 Err(DecodeError::InvalidDate { millis })
             } else {
@@ -128,10 +322,13 @@ where
     }
     fn decode_array(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, count| {
-            let assoc = this.decode_pairs()?;
-            let dense = (0..count)
-                .map(|_| this.decode_value())
-                .collect::<DecodeResult<_>>()?;
+            let mut assoc = Vec::new();
+            this.decode_pairs(&mut assoc)?;
+            this.allocate_entries::<Value>(count)?;
+            let mut dense = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                dense.push(this.decode_value()?);
+            }
             Ok(Value::Array {
                 assoc_entries: assoc,
                 dense_entries: dense,
@@ -141,6 +338,8 @@ where
     fn decode_object(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, u28| {
             let amf_trait = this.decode_trait(u28)?;
+            this.allocate_entries::<Pair<String, Value>>(amf_trait.fields.len())?;
+            this.allocate(amf_trait.fields.iter().map(String::len).sum())?;
             let mut entries = amf_trait
                 .fields
                 .iter()
@@ -152,7 +351,7 @@ where
                 })
                 .collect::<DecodeResult<Vec<_>>>()?;
             if amf_trait.is_dynamic {
-                entries.extend(this.decode_pairs()?);
+                this.decode_pairs(&mut entries)?;
             }
             Ok(Value::Object {
                 class_name: amf_trait.class_name,
@@ -162,45 +361,55 @@ where
         })
     }
     fn decode_xml(&mut self) -> DecodeResult<Value> {
-        self.decode_complex_type(|this, len| this.read_utf8(len).map(Value::Xml))
+        self.decode_complex_type(|this, len| {
+            this.read_utf8(len, StringContext::Value).map(Value::Xml)
+        })
     }
     fn decode_byte_array(&mut self) -> DecodeResult<Value> {
-        self.decode_complex_type(|this, len| this.read_bytes(len).map(Value::ByteArray))
+        self.decode_complex_type(|this, len| {
+            if this.byte_array_sink.is_some() {
+                let offset = this.streamed;
+                this.stream_bytes(len)?;
+                Ok(Value::StreamedByteArray { offset, len })
+            } else {
+                this.read_bytes(len).map(Value::ByteArray)
+            }
+        })
     }
     fn decode_vector_int(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, count| {
-            let is_fixed = this.inner.read_u8()? != 0;
-            let entries = (0..count)
-                .map(|_| this.inner.read_i32::<BigEndian>())
-                .collect::<Result<_, _>>()?;
+            let is_fixed = this.read_u8()? != 0;
+            let entries = this.read_numeric_vector(count, BigEndian::read_i32_into)?;
             Ok(Value::IntVector { is_fixed, entries })
         })
     }
     fn decode_vector_uint(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, count| {
-            let is_fixed = this.inner.read_u8()? != 0;
-            let entries = (0..count)
-                .map(|_| this.inner.read_u32::<BigEndian>())
-                .collect::<Result<_, _>>()?;
+            let is_fixed = this.read_u8()? != 0;
+            let entries = this.read_numeric_vector(count, BigEndian::read_u32_into)?;
             Ok(Value::UintVector { is_fixed, entries })
         })
     }
     fn decode_vector_double(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, count| {
-            let is_fixed = this.inner.read_u8()? != 0;
-            let entries = (0..count)
-                .map(|_| this.inner.read_f64::<BigEndian>())
-                .collect::<Result<_, _>>()?;
+            let is_fixed = this.read_u8()? != 0;
+            let offset = this.position;
+            let entries = this.read_numeric_vector(count, BigEndian::read_f64_into)?;
+            for (i, &n) in entries.iter().enumerate() {
+                this.check_finite_number(n, offset + i * 8)?;
+            }
             Ok(Value::DoubleVector { is_fixed, entries })
         })
     }
     fn decode_vector_object(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, count| {
-            let is_fixed = this.inner.read_u8()? != 0;
-            let class_name = this.decode_utf8()?;
-            let entries = (0..count)
-                .map(|_| this.decode_value())
-                .collect::<DecodeResult<_>>()?;
+            let is_fixed = this.read_u8()? != 0;
+            let class_name = this.decode_utf8_in(StringContext::ClassName)?;
+            this.allocate_entries::<Value>(count)?;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.decode_value()?);
+            }
             Ok(Value::ObjectVector {
                 class_name: if class_name == "*" {
                     None
@@ -214,7 +423,8 @@ where
     }
     fn decode_dictionary(&mut self) -> DecodeResult<Value> {
         self.decode_complex_type(|this, count| {
-            let is_weak = this.inner.read_u8()? == 1;
+            let is_weak = this.read_u8()? == 1;
+            this.allocate_entries::<Pair<Value, Value>>(count)?;
             let entries = (0..count)
                 .map(|_| {
                     Ok(Pair {
@@ -232,10 +442,18 @@ where
     /// Use this if you need to decode an AMF3 string outside of value context.
     /// An example for this is reading keys in Local Shared Object file.
     pub fn decode_utf8(&mut self) -> DecodeResult<String> {
+        self.decode_utf8_in(StringContext::Value)
+    }
+    fn decode_utf8_in(&mut self, context: StringContext) -> DecodeResult<String> {
         match self.decode_size_or_index()? {
             SizeOrIndex::Size(len) => {
-                let bytes = self.read_bytes(len)?;
-                let s = String::from_utf8(bytes)?;
+                // A string value is kept as is, so only the keys and the class names
+                // (and the short values to be interned) are read through the scratch buffer.
+                let interned = self.options.intern_strings && len <= intern::MAX_LEN;
+                let s = match context {
+                    StringContext::Value if !interned => self.read_utf8(len, context)?,
+                    _ => self.read_utf8_via_scratch(len, context)?,
+                };
                 if !s.is_empty() {
                     self.strings.push(s.clone());
                 }
@@ -245,21 +463,29 @@ where
                 let s = self
                     .strings
                     .get(index)
-                    .ok_or(DecodeError::OutOfRangeReference { index })?;
-                Ok(s.clone())
+                    .ok_or(DecodeError::OutOfRangeReference { index })?
+                    .clone();
+                self.allocate(s.len())?;
+                Ok(s)
             }
         }
     }
+    /// Reads a 29-bit unsigned integer.
+    ///
+    /// The first three bytes have 7 bits each and the fourth has 8 bits,
+    /// so the result is at most `0x1FFF_FFFF` (and a size or an index is at most `(1 << 28) - 1`).
+    /// The collections and the buffers are not reserved from such an untrusted size as is
+    /// (see `MAX_PREALLOCATED_ENTRIES` and `MAX_PREALLOCATED_BYTES`).
     fn decode_u29(&mut self) -> DecodeResult<u32> {
         let mut n = 0;
         for _ in 0..3 {
-            let b = self.inner.read_u8()? as u32;
+            let b = self.read_u8()? as u32;
             n = (n << 7) | (b & 0b0111_1111);
             if (b & 0b1000_0000) == 0 {
                 return Ok(n);
             }
         }
-        let b = self.inner.read_u8()? as u32;
+        let b = self.read_u8()? as u32;
         n = (n << 8) | b;
         Ok(n)
     }
@@ -278,54 +504,117 @@ where
         F: FnOnce(&mut Self, usize) -> DecodeResult<Value>,
     {
         match self.decode_size_or_index()? {
-            SizeOrIndex::Index(index) => self
-                .complexes
-                .get(index)
-                .ok_or(DecodeError::OutOfRangeReference { index })
-                .and_then(|v| {
-                    if *v == Value::Null {
-                        Err(DecodeError::CircularReference { index })
-                    } else {
-                        Ok(v.clone())
-                    }
-                }),
+            SizeOrIndex::Index(index) => {
+                let size = match self.complexes.get(index) {
+                    None => return Err(DecodeError::OutOfRangeReference { index }),
+                    Some(Value::Null) => return Err(DecodeError::CircularReference { index }),
+                    Some(v) => v.approx_size(),
+                };
+                self.allocate(size)?;
+                Ok(self.complexes[index].clone())
+            }
             SizeOrIndex::Size(u28) => {
+                if self.depth == MAX_DECODE_DEPTH {
+                    return Err(DecodeError::DepthLimitExceeded {
+                        offset: self.position,
+                    });
+                }
                 let index = self.complexes.len();
                 self.complexes.push(Value::Null);
-                let value = f(self, u28)?;
+                self.depth += 1;
+                let value = f(self, u28);
+                self.depth -= 1;
+                let value = value?;
                 self.complexes[index] = value.clone();
                 Ok(value)
             }
         }
     }
-    fn decode_pairs(&mut self) -> DecodeResult<Vec<Pair<String, Value>>> {
-        let mut pairs = Vec::new();
+    /// Decodes the pairs terminated by an empty key, appending them to `pairs`.
+    fn decode_pairs(&mut self, pairs: &mut Vec<Pair<String, Value>>) -> DecodeResult<()> {
+        let mut n = 0;
         loop {
-            let key = self.decode_utf8()?;
+            let offset = self.position;
+            let key = self.decode_utf8_in(StringContext::Key)?;
             if key.is_empty() {
-                return Ok(pairs);
+                return Ok(());
             }
+            n += 1;
+            self.check_object_entries(n)?;
+            self.check_duplicate_key(pairs, &key, offset)?;
+            self.allocate_entries::<Pair<String, Value>>(1)?;
             let value = self.decode_value()?;
             pairs.push(Pair { key, value });
         }
     }
+    fn check_duplicate_key(
+        &mut self,
+        entries: &[Pair<String, Value>],
+        key: &str,
+        offset: usize,
+    ) -> DecodeResult<()> {
+        let check = self.options.reject_duplicate_keys || self.options.collect_warnings;
+        if !(check && entries.iter().any(|p| p.key == key)) {
+            return Ok(());
+        }
+        if self.options.reject_duplicate_keys {
+            return Err(DecodeError::DuplicateKey {
+                key: key.to_owned(),
+            });
+        }
+        self.warnings.push(Warning::DuplicateKey {
+            key: key.to_owned(),
+            offset,
+        });
+        Ok(())
+    }
+    fn check_finite_number(&self, n: f64, offset: usize) -> DecodeResult<()> {
+        if self.options.reject_non_finite_numbers && !n.is_finite() {
+            Err(DecodeError::NonFiniteNumber { offset })
+        } else {
+            Ok(())
+        }
+    }
+    fn check_object_entries(&self, count: usize) -> DecodeResult<()> {
+        match self.options.max_object_entries {
+            Some(max) if count > max => Err(DecodeError::CollectionTooLarge {
+                max,
+                offset: self.position,
+            }),
+            _ => Ok(()),
+        }
+    }
+    /// Charges `bytes` to the allocation budget (see `DecodeOptions::max_total_bytes`).
+    fn allocate(&mut self, bytes: usize) -> DecodeResult<()> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        match self.options.max_total_bytes {
+            Some(max) if self.allocated > max => Err(DecodeError::AllocationBudgetExceeded {
+                max,
+                offset: self.position,
+            }),
+            _ => Ok(()),
+        }
+    }
+    fn allocate_entries<T>(&mut self, count: usize) -> DecodeResult<()> {
+        self.allocate(count.saturating_mul(mem::size_of::<T>()))
+    }
     fn decode_trait(&mut self, u28: usize) -> DecodeResult<Trait> {
         if (u28 & 0b1) == 0 {
-            let i = (u28 >> 1) as usize;
+            let i = u28 >> 1;
             let t = self
                 .traits
                 .get(i)
                 .ok_or(DecodeError::OutOfRangeReference { index: i })?;
             Ok(t.clone())
         } else if (u28 & 0b10) != 0 {
-            let class_name = self.decode_utf8()?;
+            let class_name = self.decode_utf8_in(StringContext::ClassName)?;
             Err(DecodeError::ExternalizableType { name: class_name })
         } else {
             let is_dynamic = (u28 & 0b100) != 0;
             let field_num = u28 >> 3;
-            let class_name = self.decode_utf8()?;
+            let class_name = self.decode_utf8_in(StringContext::ClassName)?;
             let fields = (0..field_num)
-                .map(|_| self.decode_utf8())
+                .map(|_| self.decode_utf8_in(StringContext::Key))
                 .collect::<DecodeResult<_>>()?;
 
             let t = Trait {
@@ -341,23 +630,129 @@ where
             Ok(t)
         }
     }
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+    fn read_f64(&mut self) -> io::Result<f64> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(BigEndian::read_f64(&buf))
+    }
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(buf)?;
+        self.position += buf.len();
+        Ok(())
+    }
     fn read_bytes(&mut self, len: usize) -> DecodeResult<Vec<u8>> {
-        let mut buf = vec![0; len];
-        self.inner.read_exact(&mut buf)?;
+        self.allocate(len)?;
+        let mut buf = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED_BYTES));
+        while buf.len() < len {
+            let start = buf.len();
+            buf.resize(cmp::min(len, start + MAX_PREALLOCATED_BYTES), 0);
+            self.read_exact(&mut buf[start..])?;
+        }
         Ok(buf)
     }
-    fn read_utf8(&mut self, len: usize) -> DecodeResult<String> {
-        self.read_bytes(len).and_then(|b| Ok(String::from_utf8(b)?))
+    /// Reads `count` numeric elements of a vector in chunks through the scratch buffer.
+    ///
+    /// This is faster than reading the elements one by one.
+    fn read_numeric_vector<T>(
+        &mut self,
+        count: usize,
+        convert: fn(&[u8], &mut [T]),
+    ) -> DecodeResult<Vec<T>>
+    where
+        T: Copy + Default,
+    {
+        self.allocate_entries::<T>(count)?;
+        let chunk_len = MAX_PREALLOCATED_BYTES / mem::size_of::<T>();
+        let mut entries = Vec::with_capacity(cmp::min(count, chunk_len));
+        while entries.len() < count {
+            let start = entries.len();
+            let n = cmp::min(count - start, chunk_len);
+            self.scratch.clear();
+            self.scratch.resize(n * mem::size_of::<T>(), 0);
+            self.inner.read_exact(&mut self.scratch)?;
+            self.position += self.scratch.len();
+            entries.resize(start + n, T::default());
+            convert(&self.scratch, &mut entries[start..]);
+        }
+        Ok(entries)
+    }
+    /// Copies `len` bytes from the reader to the byte array sink through the scratch buffer.
+    fn stream_bytes(&mut self, mut len: usize) -> DecodeResult<()> {
+        while len > 0 {
+            let n = cmp::min(len, MAX_PREALLOCATED_BYTES);
+            self.scratch.clear();
+            self.scratch.resize(n, 0);
+            self.inner.read_exact(&mut self.scratch)?;
+            self.position += n;
+            if let Some(sink) = self.byte_array_sink.as_mut() {
+                sink.write_all(&self.scratch)?;
+            }
+            self.streamed += n;
+            len -= n;
+        }
+        Ok(())
+    }
+    /// Reads a key or a class name through the scratch buffer
+    /// to avoid allocating a zero-filled buffer for each of them.
+    fn read_utf8_via_scratch(
+        &mut self,
+        len: usize,
+        context: StringContext,
+    ) -> DecodeResult<String> {
+        if len > MAX_PREALLOCATED_BYTES {
+            return self.read_utf8(len, context);
+        }
+        self.allocate(len)?;
+        let offset = self.position;
+        self.scratch.clear();
+        self.scratch.resize(len, 0);
+        self.inner.read_exact(&mut self.scratch)?;
+        self.position += len;
+        if self.options.intern_strings {
+            if let Some(s) = self.interner.get(&self.scratch) {
+                return Ok(s);
+            }
+        }
+        match str::from_utf8(&self.scratch) {
+            Ok(s) => {
+                if self.options.intern_strings {
+                    self.interner.insert(s);
+                }
+                Ok(s.to_owned())
+            }
+            Err(_) => Err(DecodeError::Utf8 {
+                error: String::from_utf8(self.scratch.clone()).unwrap_err(),
+                offset,
+                context,
+            }),
+        }
+    }
+    fn read_utf8(&mut self, len: usize, context: StringContext) -> DecodeResult<String> {
+        let offset = self.position;
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes).map_err(|error| DecodeError::Utf8 {
+            error,
+            offset,
+            context,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::marker;
     use super::super::Value;
-    use crate::error::DecodeError;
+    use super::Decoder;
+    use crate::error::{DecodeError, StringContext, ValidationError};
+    use crate::io;
     use crate::Pair;
     use std::f64;
-    use std::io;
+    use std::io::Read;
     use std::time;
 
     macro_rules! decode {
@@ -619,6 +1014,13 @@ mod tests {
         ];
         decode_eq!("amf3-dictionary.bin", dic(&entries));
         decode_eq!("amf3-empty-dictionary.bin", dic(&[][..]));
+
+        // A `NaN` key is valid on the wire, though it cannot be encoded again.
+        let value = decode!("amf3-dictionary-nan-key.bin").unwrap();
+        assert_eq!(
+            value.validate(),
+            Err(ValidationError::NanDictionaryKey { index: 0 })
+        );
     }
     #[test]
     fn decodes_vector() {
@@ -668,6 +1070,16 @@ mod tests {
                 entries: objects,
             }
         );
+
+        // Flash writes vectors of non-object types (e.g., `Vector.<*>`) with the same marker.
+        decode_eq!(
+            "amf3-vector-object-non-object.bin",
+            Value::ObjectVector {
+                class_name: None,
+                is_fixed: false,
+                entries: vec![i(1)],
+            }
+        );
     }
     #[test]
     fn other_errors() {
@@ -689,7 +1101,17 @@ mod tests {
         );
         assert_eq!(
             decode!("amf3-unknown-marker.bin"),
-            Err(DecodeError::Unknown { marker: 123 })
+            Err(DecodeError::Unknown {
+                marker: 123,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            decode!("amf3-array-unknown-marker.bin"),
+            Err(DecodeError::Unknown {
+                marker: 123,
+                offset: 5
+            })
         );
         assert_eq!(
             decode!("amf3-date-invalid-millis.bin"),
@@ -701,6 +1123,18 @@ mod tests {
             decode!("amf3-date-minus-millis.bin"),
             Err(DecodeError::InvalidDate { millis: -1.0 })
         );
+        assert_eq!(
+            decode!("amf3-date-bad-header.bin"),
+            Err(DecodeError::InvalidDateHeader { size: 1 })
+        );
+        assert!(matches!(
+            decode!("amf3-bad-utf8-key.bin"),
+            Err(DecodeError::Utf8 {
+                offset: 4,
+                context: StringContext::Key,
+                ..
+            })
+        ));
         decode_unexpected_eof!("amf3-empty.bin");
         decode_unexpected_eof!("amf3-double-partial.bin");
         decode_unexpected_eof!("amf3-date-partial.bin");
@@ -769,4 +1203,350 @@ mod tests {
                 .collect(),
         }
     }
+
+    #[test]
+    fn reads_trailing_bytes_from_borrowed_reader() {
+        let input = [0x04, 0x01, 0xAB, 0xCD];
+        let mut decoder = Decoder::new(&input[..]);
+        assert_eq!(decoder.decode().unwrap(), Value::Integer(1));
+        assert_eq!(decoder.inner(), &[0xAB, 0xCD]);
+
+        let mut byte = [0];
+        decoder.inner_mut().read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [0xAB]);
+        assert_eq!(decoder.inner(), &[0xCD]);
+    }
+
+    #[test]
+    fn limits_object_entries_if_enabled() {
+        use crate::options::DecodeOptions;
+
+        let options = DecodeOptions {
+            max_object_entries: Some(2),
+            ..Default::default()
+        };
+        let input = [
+            0x0A, 0x0B, 0x01, 0x03, b'a', 0x01, 0x03, b'b', 0x01, 0x03, b'c', 0x01, 0x01,
+        ];
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode(),
+            Err(DecodeError::CollectionTooLarge { max: 2, offset: 11 })
+        );
+
+        let options = DecodeOptions {
+            max_object_entries: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode(),
+            Value::read_from(&input[..])
+        );
+    }
+
+    #[test]
+    fn limits_total_allocation_if_enabled() {
+        use crate::options::{DecodeOptions, EncodeOptions};
+
+        // The copies of the referenced string are also charged.
+        let array = dense_array(&vec![Value::String("x".repeat(1000)); 64]);
+        let mut input = Vec::new();
+        let encode_options = EncodeOptions {
+            use_string_references: true,
+            ..Default::default()
+        };
+        array
+            .write_to_with_options(&mut input, &encode_options)
+            .unwrap();
+        assert!(input.len() < 2000);
+
+        let options = DecodeOptions {
+            max_total_bytes: Some(32 * 1024),
+            ..Default::default()
+        };
+        let result = Decoder::with_options(&input[..], options).decode();
+        assert!(
+            matches!(
+                result,
+                Err(DecodeError::AllocationBudgetExceeded { max: 32768, .. })
+            ),
+            "{:?}",
+            result
+        );
+
+        let options = DecodeOptions {
+            max_total_bytes: Some(128 * 1024),
+            ..Default::default()
+        };
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode(),
+            Ok(array)
+        );
+    }
+
+    #[test]
+    fn ignores_date_header_size_if_lenient() {
+        use crate::options::DecodeOptions;
+
+        let options = DecodeOptions {
+            lenient_amf3_dates: true,
+            ..Default::default()
+        };
+        let input = include_bytes!("../testdata/amf3-date-bad-header.bin");
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode(),
+            Ok(Value::Date {
+                unix_time: time::Duration::from_millis(0)
+            })
+        );
+    }
+
+    #[test]
+    fn streams_byte_arrays_into_sink() {
+        let bytes = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+        let value = Value::Array {
+            assoc_entries: Vec::new(),
+            dense_entries: vec![
+                Value::ByteArray(bytes[..3].to_vec()),
+                Value::Integer(1),
+                Value::ByteArray(bytes[3..].to_vec()),
+            ],
+        };
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+
+        let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Value::Array {
+                assoc_entries: Vec::new(),
+                dense_entries: vec![
+                    Value::StreamedByteArray { offset: 0, len: 3 },
+                    Value::Integer(1),
+                    Value::StreamedByteArray {
+                        offset: 3,
+                        len: bytes.len() - 3,
+                    },
+                ],
+            }
+        );
+        assert_eq!(decoder.position(), buf.len());
+        assert_eq!(decoder.into_byte_array_sink(), Some(bytes));
+    }
+
+    #[test]
+    fn streams_referenced_byte_arrays_once() {
+        use crate::options::EncodeOptions;
+
+        let bytes = Value::ByteArray(vec![1, 2, 3]);
+        let value = Value::Array {
+            assoc_entries: Vec::new(),
+            dense_entries: vec![Value::ByteArray(vec![4]), bytes.clone(), bytes],
+        };
+        let options = EncodeOptions {
+            use_object_references: true,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+
+        let mut decoder = Decoder::new(&buf[..]).with_byte_array_sink(Vec::new());
+        let placeholder = Value::StreamedByteArray { offset: 1, len: 3 };
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Value::Array {
+                assoc_entries: Vec::new(),
+                dense_entries: vec![
+                    Value::StreamedByteArray { offset: 0, len: 1 },
+                    placeholder.clone(),
+                    placeholder,
+                ],
+            }
+        );
+        assert_eq!(decoder.into_byte_array_sink(), Some(vec![4, 1, 2, 3]));
+    }
+
+    #[test]
+    fn interns_strings_if_enabled() {
+        use crate::options::DecodeOptions;
+
+        let value = dense_array(
+            &[
+                typed_obj("foo", &[("code", s("bar"))][..]),
+                typed_obj("foo", &[("code", s("bar"))][..]),
+                obj(&[("code", s(&"x".repeat(0x1000)))][..]),
+            ][..],
+        );
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        value.write_to(&mut buf).unwrap();
+
+        let options = DecodeOptions {
+            intern_strings: true,
+            ..Default::default()
+        };
+        let mut decoder = Decoder::with_options(&buf[..], options);
+        assert_eq!(decoder.decode(), Ok(value.clone()));
+        assert_eq!(decoder.decode(), Ok(value));
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers_if_enabled() {
+        use crate::options::DecodeOptions;
+
+        let options = DecodeOptions {
+            reject_non_finite_numbers: true,
+            ..Default::default()
+        };
+        let file = include_bytes!("../testdata/amf3-double-positive-infinity.bin");
+        assert!(!Value::read_from(&file[..]).unwrap().is_finite_number());
+        assert_eq!(
+            Decoder::with_options(&file[..], options.clone()).decode(),
+            Err(DecodeError::NonFiniteNumber { offset: 1 })
+        );
+
+        let vector = Value::double_vector([1.0, f64::NAN], false);
+        let mut buf = Vec::new();
+        vector.write_to(&mut buf).unwrap();
+        assert_eq!(
+            Decoder::with_options(&buf[..], options.clone()).decode(),
+            Err(DecodeError::NonFiniteNumber { offset: 11 })
+        );
+
+        let value = Value::Integer(1);
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        let decoded = Decoder::with_options(&buf[..], options).decode().unwrap();
+        assert!(decoded.is_finite_number());
+    }
+
+    #[test]
+    fn collects_duplicate_keys_if_enabled() {
+        use crate::error::Warning;
+        use crate::options::DecodeOptions;
+
+        let options = DecodeOptions {
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let input = [0x0A, 0x0B, 0x01, 0x03, b'a', 0x01, 0x03, b'a', 0x01, 0x01];
+        let mut decoder = Decoder::with_options(&input[..], options.clone());
+        decoder.decode().unwrap();
+        assert_eq!(
+            decoder.take_warnings(),
+            [Warning::DuplicateKey {
+                key: "a".to_string(),
+                offset: 6
+            }]
+        );
+        assert_eq!(decoder.take_warnings(), []);
+
+        // A dynamic member which has the same key as a sealed member.
+        let input = [0x0A, 0x1B, 0x01, 0x03, b'a', 0x01, 0x00, 0x01, 0x01];
+        let mut decoder = Decoder::with_options(&input[..], options);
+        decoder.decode().unwrap();
+        assert_eq!(
+            decoder.take_warnings(),
+            [Warning::DuplicateKey {
+                key: "a".to_string(),
+                offset: 6
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_keys_if_enabled() {
+        use crate::options::DecodeOptions;
+
+        let options = DecodeOptions {
+            reject_duplicate_keys: true,
+            ..Default::default()
+        };
+        let input = [0x09, 0x01, 0x03, b'a', 0x01, 0x00, 0x01, 0x01];
+        assert_eq!(
+            Decoder::with_options(&input[..], options).decode(),
+            Err(DecodeError::DuplicateKey {
+                key: "a".to_string()
+            })
+        );
+        assert!(Value::read_from(&input[..]).is_ok());
+    }
+
+    #[test]
+    fn reports_invalid_utf8_with_context() {
+        use crate::error::StringContext;
+
+        // An anonymous dynamic object whose only key is `[0xFF]`.
+        let input = [0x0A, 0x0B, 0x01, 0x03, 0xFF, 0x01, 0x01];
+        match Value::read_from(&input[..]) {
+            Err(DecodeError::Utf8 {
+                error,
+                offset: 4,
+                context: StringContext::Key,
+            }) => assert_eq!(error.as_bytes(), [0xFF]),
+            other => panic!("{:?}", other),
+        }
+
+        // A typed object whose class name is `[0xC0]`.
+        let input = [0x0A, 0x03, 0x03, 0xC0, 0x01];
+        assert!(matches!(
+            Value::read_from(&input[..]),
+            Err(DecodeError::Utf8 {
+                offset: 3,
+                context: StringContext::ClassName,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn decodes_u29_boundaries() {
+        let decode = |input: &[u8]| Value::read_from(input);
+
+        // The boundaries of the 1-byte to 4-byte forms, and the sign boundary of integers.
+        let integers: [(&[u8], i32); 8] = [
+            (&[0x7F], 0x7F),
+            (&[0x81, 0x00], 0x80),
+            (&[0xFF, 0x7F], 0x3FFF),
+            (&[0x81, 0x80, 0x00], 0x4000),
+            (&[0xFF, 0xFF, 0x7F], 0x1F_FFFF),
+            (&[0x80, 0xC0, 0x80, 0x00], 0x20_0000),
+            (&[0xC0, 0x80, 0x80, 0x00], -0x1000_0000),
+            (&[0xFF, 0xFF, 0xFF, 0xFF], -1),
+        ];
+        for (bytes, n) in integers {
+            let input = [&[0x04][..], bytes].concat();
+            assert_eq!(decode(&input), Ok(Value::Integer(n)));
+
+            let mut buf = Vec::new();
+            Value::Integer(n).write_to(&mut buf).unwrap();
+            assert_eq!(buf, input);
+        }
+
+        // The largest size (`(1 << 28) - 1`) is rejected when the input ends,
+        // without reserving the declared size in advance.
+        let max_size = [0xFF, 0xFF, 0xFF, 0xFF];
+        for marker in [
+            marker::STRING,
+            marker::XML_DOC,
+            marker::ARRAY,
+            marker::BYTE_ARRAY,
+            marker::VECTOR_INT,
+            marker::VECTOR_DOUBLE,
+            marker::VECTOR_OBJECT,
+            marker::DICTIONARY,
+        ] {
+            let input = [&[marker][..], &max_size, &[0x01, 0x01]].concat();
+            match decode(&input) {
+                Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                other => panic!("marker {}: {:?}", marker, other),
+            }
+        }
+
+        // The same goes for the largest number of sealed members.
+        let input = [0x0A, 0xFF, 0xFF, 0xFF, 0xF3, 0x01];
+        match decode(&input) {
+            Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("{:?}", other),
+        }
+    }
 }
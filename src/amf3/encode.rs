@@ -1,17 +1,63 @@
 use super::marker;
-use super::Value;
-use crate::Pair;
-use byteorder::{BigEndian, WriteBytesExt};
-use std::io;
-use std::time;
+use super::{Value, MAX_INTEGER, MIN_INTEGER};
+use crate::error::{check_len, EncodeError, ValidationError};
+use crate::io;
+use crate::options::EncodeOptions;
+use crate::registry::ClassRegistry;
+use crate::{EncodeResult, Pair};
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::any::{type_name, Any};
+use core::cmp::Ordering;
+use core::time;
+
+/// The maximum value of the 28-bit sizes and reference indices.
+const MAX_SIZE: usize = (1 << 28) - 1;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct TraitKey {
+    class_name: Option<String>,
+    is_dynamic: bool,
+    fields: Vec<String>,
+}
+
+/// A key of the object reference table which is compared by `Value::cmp_canonical`.
+#[derive(Debug)]
+struct ComplexKey(Value);
+impl PartialEq for ComplexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for ComplexKey {}
+impl PartialOrd for ComplexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for ComplexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_canonical(&other.0)
+    }
+}
 
 /// AMF3 encoder.
 #[derive(Debug)]
 pub struct Encoder<W> {
     inner: W,
+    options: EncodeOptions,
+    traits: BTreeMap<TraitKey, usize>,
+    strings: BTreeMap<String, usize>,
+    complexes: BTreeMap<ComplexKey, usize>,
+    complex_count: usize,
 }
 impl<W> Encoder<W> {
     /// Unwraps this `Encoder`, returning the underlying writer.
+    ///
+    /// Note that the writer is not flushed (use `finish` to flush it).
     pub fn into_inner(self) -> W {
         self.inner
     }
@@ -30,11 +76,60 @@ where
 {
     /// Makes a new instance.
     pub fn new(inner: W) -> Self {
-        Encoder { inner }
+        Self::with_options(inner, EncodeOptions::default())
+    }
+
+    /// Makes a new instance with the given options.
+    pub fn with_options(inner: W, options: EncodeOptions) -> Self {
+        Encoder {
+            inner,
+            options,
+            traits: BTreeMap::new(),
+            strings: BTreeMap::new(),
+            complexes: BTreeMap::new(),
+            complex_count: 0,
+        }
+    }
+
+    /// Flushes the underlying writer and unwraps this `Encoder`, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Clear the reference tables of this encoder.
+    ///
+    /// The tables are only used if references are enabled by `EncodeOptions`.
+    /// They are kept across `encode` calls, so the values encoded by one encoder
+    /// can refer to each other until this method is called.
+    ///
+    /// > Similar to AFM 0, AMF 3 object reference tables, object trait reference tables
+    /// > and string reference tables must be reset each time a new context header or message is processed.
+    /// >
+    /// > [AMF 3 Specification: 4.1 NetConnection and AMF 3](https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf)
+    ///
+    /// So this should be called between message bodies
+    /// (or use a new encoder for each of them).
+    pub fn clear_reference_table(&mut self) {
+        self.traits.clear();
+        self.strings.clear();
+        self.complexes.clear();
+        self.complex_count = 0;
     }
 
     /// Encodes a AMF3 value.
-    pub fn encode(&mut self, value: &Value) -> io::Result<()> {
+    pub fn encode(&mut self, value: &Value) -> EncodeResult<()> {
+        if self.options.use_object_references {
+            if let Some(marker) = complex_marker(value) {
+                let key = ComplexKey(value.clone());
+                if let Some(&index) = self.complexes.get(&key) {
+                    self.write_u8(marker)?;
+                    return self.encode_reference(index);
+                }
+                self.complexes.insert(key, self.complex_count);
+                self.complex_count += 1;
+            }
+        }
         match *value {
             Value::Undefined => self.encode_undefined(),
             Value::Null => self.encode_null(),
@@ -76,27 +171,51 @@ where
                 is_weak,
                 ref entries,
             } => self.encode_dictionary(is_weak, entries),
+            Value::StreamedByteArray { .. } => {
+                Err(EncodeError::Invalid(ValidationError::StreamedPlaceholder {
+                    type_name: value.type_name(),
+                }))
+            }
         }
     }
 
-    fn encode_undefined(&mut self) -> io::Result<()> {
-        self.inner.write_u8(marker::UNDEFINED)?;
+    /// Converts `x` into a typed object using `registry` and encodes it.
+    ///
+    /// `EncodeError::UnregisteredType` is returned if `T` is not registered.
+    pub fn encode_with_registry<T: Any>(
+        &mut self,
+        x: &T,
+        registry: &ClassRegistry<Value>,
+    ) -> EncodeResult<()> {
+        let value = registry
+            .to_value(x)
+            .ok_or_else(|| EncodeError::UnregisteredType {
+                type_name: type_name::<T>(),
+            })?;
+        self.encode(&value)
+    }
+
+    fn encode_undefined(&mut self) -> EncodeResult<()> {
+        self.write_u8(marker::UNDEFINED)?;
         Ok(())
     }
-    fn encode_null(&mut self) -> io::Result<()> {
-        self.inner.write_u8(marker::NULL)?;
+    fn encode_null(&mut self) -> EncodeResult<()> {
+        self.write_u8(marker::NULL)?;
         Ok(())
     }
-    fn encode_boolean(&mut self, b: bool) -> io::Result<()> {
+    fn encode_boolean(&mut self, b: bool) -> EncodeResult<()> {
         if b {
-            self.inner.write_u8(marker::TRUE)?;
+            self.write_u8(marker::TRUE)?;
         } else {
-            self.inner.write_u8(marker::FALSE)?;
+            self.write_u8(marker::FALSE)?;
         }
         Ok(())
     }
-    fn encode_integer(&mut self, i: i32) -> io::Result<()> {
-        self.inner.write_u8(marker::INTEGER)?;
+    fn encode_integer(&mut self, i: i32) -> EncodeResult<()> {
+        if !(MIN_INTEGER..=MAX_INTEGER).contains(&i) {
+            return self.encode_double(f64::from(i));
+        }
+        self.write_u8(marker::INTEGER)?;
         let u29 = if i >= 0 {
             i as u32
         } else {
@@ -105,36 +224,36 @@ where
         self.encode_u29(u29)?;
         Ok(())
     }
-    fn encode_double(&mut self, d: f64) -> io::Result<()> {
-        self.inner.write_u8(marker::DOUBLE)?;
-        self.inner.write_f64::<BigEndian>(d)?;
+    fn encode_double(&mut self, d: f64) -> EncodeResult<()> {
+        self.write_u8(marker::DOUBLE)?;
+        self.write_f64(d)?;
         Ok(())
     }
-    fn encode_string(&mut self, s: &str) -> io::Result<()> {
-        self.inner.write_u8(marker::STRING)?;
+    fn encode_string(&mut self, s: &str) -> EncodeResult<()> {
+        self.write_u8(marker::STRING)?;
         self.encode_utf8(s)?;
         Ok(())
     }
-    fn encode_xml_document(&mut self, xml: &str) -> io::Result<()> {
-        self.inner.write_u8(marker::XML_DOC)?;
-        self.encode_utf8(xml)?;
+    fn encode_xml_document(&mut self, xml: &str) -> EncodeResult<()> {
+        self.write_u8(marker::XML_DOC)?;
+        self.write_utf8_inline(xml)?;
         Ok(())
     }
-    fn encode_date(&mut self, unix_time: time::Duration) -> io::Result<()> {
+    fn encode_date(&mut self, unix_time: time::Duration) -> EncodeResult<()> {
         let millis = unix_time.as_secs() * 1000 + (unix_time.subsec_nanos() as u64) / 1_000_000;
-        self.inner.write_u8(marker::DATE)?;
+        self.write_u8(marker::DATE)?;
         self.encode_size(0)?;
-        self.inner.write_f64::<BigEndian>(millis as f64)?;
+        self.write_f64(millis as f64)?;
         Ok(())
     }
-    fn encode_array(&mut self, assoc: &[Pair<String, Value>], dense: &[Value]) -> io::Result<()> {
-        self.inner.write_u8(marker::ARRAY)?;
+    fn encode_array(&mut self, assoc: &[Pair<String, Value>], dense: &[Value]) -> EncodeResult<()> {
+        self.write_u8(marker::ARRAY)?;
         self.encode_size(dense.len())?;
         self.encode_pairs(assoc)?;
         dense
             .iter()
             .map(|v| self.encode(v))
-            .collect::<io::Result<Vec<_>>>()?;
+            .collect::<EncodeResult<Vec<_>>>()?;
         Ok(())
     }
     fn encode_object(
@@ -142,8 +261,8 @@ where
         class_name: &Option<String>,
         sealed_count: usize,
         entries: &[Pair<String, Value>],
-    ) -> io::Result<()> {
-        self.inner.write_u8(marker::OBJECT)?;
+    ) -> EncodeResult<()> {
+        self.write_u8(marker::OBJECT)?;
         self.encode_trait(class_name, sealed_count, entries)?;
         for e in entries.iter().take(sealed_count) {
             self.encode(&e.value)?;
@@ -153,41 +272,41 @@ where
         }
         Ok(())
     }
-    fn encode_xml(&mut self, xml: &str) -> io::Result<()> {
-        self.inner.write_u8(marker::XML)?;
-        self.encode_utf8(xml)?;
+    fn encode_xml(&mut self, xml: &str) -> EncodeResult<()> {
+        self.write_u8(marker::XML)?;
+        self.write_utf8_inline(xml)?;
         Ok(())
     }
-    fn encode_byte_array(&mut self, bytes: &[u8]) -> io::Result<()> {
-        self.inner.write_u8(marker::BYTE_ARRAY)?;
+    fn encode_byte_array(&mut self, bytes: &[u8]) -> EncodeResult<()> {
+        self.write_u8(marker::BYTE_ARRAY)?;
         self.encode_size(bytes.len())?;
         self.inner.write_all(bytes)?;
         Ok(())
     }
-    fn encode_int_vector(&mut self, is_fixed: bool, vec: &[i32]) -> io::Result<()> {
-        self.inner.write_u8(marker::VECTOR_INT)?;
+    fn encode_int_vector(&mut self, is_fixed: bool, vec: &[i32]) -> EncodeResult<()> {
+        self.write_u8(marker::VECTOR_INT)?;
         self.encode_size(vec.len())?;
-        self.inner.write_u8(is_fixed as u8)?;
+        self.write_u8(is_fixed as u8)?;
         for &x in vec {
-            self.inner.write_i32::<BigEndian>(x)?;
+            self.write_i32(x)?;
         }
         Ok(())
     }
-    fn encode_uint_vector(&mut self, is_fixed: bool, vec: &[u32]) -> io::Result<()> {
-        self.inner.write_u8(marker::VECTOR_UINT)?;
+    fn encode_uint_vector(&mut self, is_fixed: bool, vec: &[u32]) -> EncodeResult<()> {
+        self.write_u8(marker::VECTOR_UINT)?;
         self.encode_size(vec.len())?;
-        self.inner.write_u8(is_fixed as u8)?;
+        self.write_u8(is_fixed as u8)?;
         for &x in vec {
-            self.inner.write_u32::<BigEndian>(x)?;
+            self.write_u32(x)?;
         }
         Ok(())
     }
-    fn encode_double_vector(&mut self, is_fixed: bool, vec: &[f64]) -> io::Result<()> {
-        self.inner.write_u8(marker::VECTOR_DOUBLE)?;
+    fn encode_double_vector(&mut self, is_fixed: bool, vec: &[f64]) -> EncodeResult<()> {
+        self.write_u8(marker::VECTOR_DOUBLE)?;
         self.encode_size(vec.len())?;
-        self.inner.write_u8(is_fixed as u8)?;
+        self.write_u8(is_fixed as u8)?;
         for &x in vec {
-            self.inner.write_f64::<BigEndian>(x)?;
+            self.write_f64(x)?;
         }
         Ok(())
     }
@@ -196,10 +315,20 @@ where
         class_name: &Option<String>,
         is_fixed: bool,
         vec: &[Value],
-    ) -> io::Result<()> {
-        self.inner.write_u8(marker::VECTOR_OBJECT)?;
+    ) -> EncodeResult<()> {
+        let non_object = vec
+            .iter()
+            .position(|x| !matches!(*x, Value::Object { .. } | Value::Null));
+        if let Some(index) = non_object {
+            return Err(ValidationError::NonObjectVectorElement {
+                index,
+                type_name: vec[index].type_name(),
+            }
+            .into());
+        }
+        self.write_u8(marker::VECTOR_OBJECT)?;
         self.encode_size(vec.len())?;
-        self.inner.write_u8(is_fixed as u8)?;
+        self.write_u8(is_fixed as u8)?;
         self.encode_utf8(class_name.as_ref().map_or("*", |s| s))?;
         for x in vec {
             self.encode(x)?;
@@ -210,10 +339,16 @@ where
         &mut self,
         is_weak: bool,
         entries: &[Pair<Value, Value>],
-    ) -> io::Result<()> {
-        self.inner.write_u8(marker::DICTIONARY)?;
+    ) -> EncodeResult<()> {
+        let nan_key = entries
+            .iter()
+            .position(|e| matches!(e.key, Value::Double(n) if n.is_nan()));
+        if let Some(index) = nan_key {
+            return Err(ValidationError::NanDictionaryKey { index }.into());
+        }
+        self.write_u8(marker::DICTIONARY)?;
         self.encode_size(entries.len())?;
-        self.inner.write_u8(is_weak as u8)?;
+        self.write_u8(is_weak as u8)?;
         for e in entries {
             self.encode(&e.key)?;
             self.encode(&e.value)?;
@@ -225,8 +360,32 @@ where
         class_name: &Option<String>,
         sealed_count: usize,
         entries: &[Pair<String, Value>],
-    ) -> io::Result<()> {
-        assert!(sealed_count <= entries.len());
+    ) -> EncodeResult<()> {
+        if sealed_count > entries.len() {
+            return Err(ValidationError::SealedCountOutOfRange {
+                sealed_count,
+                entries: entries.len(),
+            }
+            .into());
+        }
+        if self.options.use_trait_references {
+            let key = TraitKey {
+                class_name: class_name.clone(),
+                is_dynamic: sealed_count < entries.len(),
+                fields: entries
+                    .iter()
+                    .take(sealed_count)
+                    .map(|e| e.key.clone())
+                    .collect(),
+            };
+            if let Some(&index) = self.traits.get(&key) {
+                let not_trait_reference = 0;
+                return self.encode_size((index << 1) | not_trait_reference);
+            }
+            let index = self.traits.len();
+            self.traits.insert(key, index);
+        }
+
         let not_reference = 1;
         let is_externalizable = false as usize;
         let is_dynamic = (sealed_count < entries.len()) as usize;
@@ -241,35 +400,63 @@ where
         }
         Ok(())
     }
-    fn encode_size(&mut self, size: usize) -> io::Result<()> {
-        assert!(size < (1 << 28));
+    fn write_u8(&mut self, n: u8) -> io::Result<()> {
+        self.inner.write_all(&[n])
+    }
+    fn write_i32(&mut self, n: i32) -> io::Result<()> {
+        let mut buf = [0; 4];
+        BigEndian::write_i32(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn write_u32(&mut self, n: u32) -> io::Result<()> {
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn write_f64(&mut self, n: f64) -> io::Result<()> {
+        let mut buf = [0; 8];
+        BigEndian::write_f64(&mut buf, n);
+        self.inner.write_all(&buf)
+    }
+    fn encode_size(&mut self, size: usize) -> EncodeResult<()> {
+        check_len(size, MAX_SIZE)?;
         let not_reference = 1;
-        self.encode_u29(((size << 1) | not_reference) as u32)
+        self.encode_u29(((size << 1) | not_reference) as u32)?;
+        Ok(())
+    }
+    fn encode_reference(&mut self, index: usize) -> EncodeResult<()> {
+        check_len(index, MAX_SIZE)?;
+        self.encode_u29((index << 1) as u32)?;
+        Ok(())
     }
     #[allow(clippy::zero_prefixed_literal, clippy::identity_op)]
-    fn encode_u29(&mut self, u29: u32) -> io::Result<()> {
+    /// Writes a 29-bit unsigned integer.
+    ///
+    /// Sizes and indices are checked against `MAX_SIZE` and integers against `MAX_INTEGER`/`MIN_INTEGER`
+    /// beforehand, so `u29` always fits in 29 bits.
+    fn encode_u29(&mut self, u29: u32) -> EncodeResult<()> {
         if u29 < 0x80 {
-            self.inner.write_u8(u29 as u8)?;
+            self.write_u8(u29 as u8)?;
         } else if u29 < 0x4000 {
             let b1 = ((u29 >> 0) & 0b0111_1111) as u8;
             let b2 = ((u29 >> 7) | 0b1000_0000) as u8;
             for b in &[b2, b1] {
-                self.inner.write_u8(*b)?;
+                self.write_u8(*b)?;
             }
         } else if u29 < 0x20_0000 {
             let b1 = ((u29 >> 00) & 0b0111_1111) as u8;
             let b2 = ((u29 >> 07) | 0b1000_0000) as u8;
             let b3 = ((u29 >> 14) | 0b1000_0000) as u8;
             for b in &[b3, b2, b1] {
-                self.inner.write_u8(*b)?;
+                self.write_u8(*b)?;
             }
-        } else if u29 < 0x4000_0000 {
+        } else if u29 < 0x2000_0000 {
             let b1 = ((u29 >> 00) & 0b1111_1111) as u8;
             let b2 = ((u29 >> 08) | 0b1000_0000) as u8;
             let b3 = ((u29 >> 15) | 0b1000_0000) as u8;
             let b4 = ((u29 >> 22) | 0b1000_0000) as u8;
             for b in &[b4, b3, b2, b1] {
-                self.inner.write_u8(*b)?;
+                self.write_u8(*b)?;
             }
         } else {
             panic!("Too large number: {}", u29);
@@ -280,12 +467,22 @@ where
     ///
     /// Use this if you need to encode an AMF3 string outside of value context.
     /// An example of this is writing keys in Local Shared Object file.
-    pub fn encode_utf8(&mut self, s: &str) -> io::Result<()> {
+    pub fn encode_utf8(&mut self, s: &str) -> EncodeResult<()> {
+        if self.options.use_string_references && !s.is_empty() {
+            if let Some(&index) = self.strings.get(s) {
+                return self.encode_reference(index);
+            }
+            let index = self.strings.len();
+            self.strings.insert(s.to_owned(), index);
+        }
+        self.write_utf8_inline(s)
+    }
+    fn write_utf8_inline(&mut self, s: &str) -> EncodeResult<()> {
         self.encode_size(s.len())?;
         self.inner.write_all(s.as_bytes())?;
         Ok(())
     }
-    fn encode_pairs(&mut self, pairs: &[Pair<String, Value>]) -> io::Result<()> {
+    fn encode_pairs(&mut self, pairs: &[Pair<String, Value>]) -> EncodeResult<()> {
         for p in pairs {
             self.encode_utf8(&p.key)?;
             self.encode(&p.value)?;
@@ -295,9 +492,28 @@ where
     }
 }
 
+fn complex_marker(value: &Value) -> Option<u8> {
+    match *value {
+        Value::XmlDocument(_) => Some(marker::XML_DOC),
+        Value::Date { .. } => Some(marker::DATE),
+        Value::Array { .. } => Some(marker::ARRAY),
+        Value::Object { .. } => Some(marker::OBJECT),
+        Value::Xml(_) => Some(marker::XML),
+        Value::ByteArray(_) => Some(marker::BYTE_ARRAY),
+        Value::IntVector { .. } => Some(marker::VECTOR_INT),
+        Value::UintVector { .. } => Some(marker::VECTOR_UINT),
+        Value::DoubleVector { .. } => Some(marker::VECTOR_DOUBLE),
+        Value::ObjectVector { .. } => Some(marker::VECTOR_OBJECT),
+        Value::Dictionary { .. } => Some(marker::DICTIONARY),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::Value;
+    use super::Encoder;
+    use crate::options::EncodeOptions;
     use crate::Pair;
     use std::time;
 
@@ -309,12 +525,18 @@ mod tests {
             assert_eq!(buf, &expected[..]);
         }};
     }
+    macro_rules! reencode_with_options_eq {
+        ($options:expr, $file:expr) => {{
+            let input = include_bytes!(concat!("../testdata/", $file));
+            let value = Value::read_from(&mut &input[..]).unwrap();
+            let mut buf = Vec::new();
+            value.write_to_with_options(&mut buf, &$options).unwrap();
+            assert_eq!(buf, &input[..]);
+        }};
+    }
     macro_rules! encode_and_decode {
         ($value:expr) => {{
-            let v = $value;
-            let mut buf = Vec::new();
-            v.write_to(&mut buf).unwrap();
-            assert_eq!(v, Value::read_from(&mut &buf[..]).unwrap());
+            crate::testing::assert_roundtrip(&crate::Value::Amf3($value), crate::Version::Amf3);
         }};
     }
 
@@ -343,6 +565,18 @@ mod tests {
         encode_eq!(Value::Integer(0xFFF_FFFF), "amf3-max.bin");
     }
     #[test]
+    fn encodes_out_of_range_integer_as_double() {
+        encode_eq!(Value::Integer(-0x1000_0001), "amf3-large-min.bin");
+        encode_eq!(Value::Integer(0x1000_0000), "amf3-large-max.bin");
+
+        let mut buf = Vec::new();
+        Value::Integer(i32::MAX).write_to(&mut buf).unwrap();
+        assert_eq!(
+            Value::read_from(&mut &buf[..]).unwrap(),
+            Value::Double(f64::from(i32::MAX))
+        );
+    }
+    #[test]
     fn encodes_double() {
         encode_eq!(Value::Double(3.5), "amf3-float.bin");
         encode_eq!(Value::Double(2f64.powf(1000f64)), "amf3-bignum.bin");
@@ -474,6 +708,131 @@ mod tests {
             entries: objects,
         });
     }
+    #[test]
+    fn encodes_with_string_references() {
+        let options = EncodeOptions {
+            use_string_references: true,
+            ..Default::default()
+        };
+        reencode_with_options_eq!(options, "amf3-string-ref.bin");
+        reencode_with_options_eq!(options, "amf3-encoded-string-ref.bin");
+        reencode_with_options_eq!(options, "amf3-empty-string-ref.bin");
+    }
+    #[test]
+    fn encodes_with_trait_references() {
+        let options = EncodeOptions {
+            use_string_references: true,
+            use_trait_references: true,
+            ..Default::default()
+        };
+        reencode_with_options_eq!(options, "amf3-trait-ref.bin");
+    }
+    #[test]
+    fn encodes_with_object_references() {
+        let options = EncodeOptions {
+            use_object_references: true,
+            ..Default::default()
+        };
+        reencode_with_options_eq!(options, "amf3-array-ref.bin");
+        reencode_with_options_eq!(options, "amf3-date-ref.bin");
+        reencode_with_options_eq!(options, "amf3-xml-ref.bin");
+        reencode_with_options_eq!(options, "amf3-byte-array-ref.bin");
+    }
+    #[test]
+    fn encodes_date_references() {
+        let options = EncodeOptions {
+            use_object_references: true,
+            ..Default::default()
+        };
+        let date = Value::date_from_unix_millis(1_590_796_800_000);
+        let mut encoder = Encoder::with_options(Vec::new(), options);
+        encoder.encode(&date).unwrap();
+        let len = encoder.inner().len();
+        encoder.encode(&date).unwrap();
+        encoder
+            .encode(&Value::date_from_unix_millis(1_590_796_800_001))
+            .unwrap();
+        let buf = encoder.into_inner();
+        assert_eq!(&buf[len..len + 2], [0x08, 0x00]);
+        assert_eq!(buf.len(), len * 2 + 2);
+    }
+    #[test]
+    fn keeps_reference_tables_until_cleared() {
+        let options = EncodeOptions {
+            use_string_references: true,
+            use_object_references: true,
+            ..Default::default()
+        };
+        let body = dense_array(&[s("foo")][..]);
+        let encode_bodies = |clear: bool| {
+            let mut encoder = Encoder::with_options(Vec::new(), options.clone());
+            encoder.encode(&body).unwrap();
+            if clear {
+                encoder.clear_reference_table();
+            }
+            encoder.encode(&body).unwrap();
+            encoder.into_inner()
+        };
+
+        let mut single = Vec::new();
+        body.write_to_with_options(&mut single, &options).unwrap();
+
+        // The second body is a reference to the first one.
+        let shared = encode_bodies(false);
+        assert_eq!(&shared[..single.len()], &single[..]);
+        assert_eq!(&shared[single.len()..], &[0x09, 0x00][..]);
+
+        // The second body is encoded from scratch.
+        let reset = encode_bodies(true);
+        assert_eq!(reset, [&single[..], &single[..]].concat());
+    }
+    #[test]
+    fn each_option_changes_output() {
+        let object = typed_obj("org.amf.ASClass", &[("foo", s("bar"))][..]);
+        let value = dense_array(&[object.clone(), object][..]);
+
+        let mut plain = Vec::new();
+        value.write_to(&mut plain).unwrap();
+        let mut default = Vec::new();
+        value
+            .write_to_with_options(&mut default, &EncodeOptions::default())
+            .unwrap();
+        assert_eq!(default, plain);
+
+        let options = [
+            EncodeOptions {
+                use_string_references: true,
+                ..Default::default()
+            },
+            EncodeOptions {
+                use_object_references: true,
+                ..Default::default()
+            },
+            EncodeOptions {
+                use_trait_references: true,
+                ..Default::default()
+            },
+        ];
+        for options in &options {
+            let mut buf = Vec::new();
+            value.write_to_with_options(&mut buf, options).unwrap();
+            assert!(buf.len() < plain.len(), "{:?}", options);
+            assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), value);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn finish_flushes_writer() {
+        use std::io::BufWriter;
+
+        let expected = include_bytes!("../testdata/amf3-integer-2byte.bin");
+
+        let mut encoder = Encoder::new(BufWriter::new(Vec::new()));
+        encoder.encode(&i(0b1000_0000)).unwrap();
+        assert!(encoder.inner().get_ref().is_empty());
+        assert_eq!(encoder.finish().unwrap().get_ref(), &expected[..]);
+    }
 
     fn i(i: i32) -> Value {
         Value::Integer(i)
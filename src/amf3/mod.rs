@@ -13,17 +13,29 @@
 //! let decoded = Value::read_from(&mut &buf[..]).unwrap();
 //! assert_eq!(integer, decoded);
 //! ```
-use crate::{DecodeResult, Pair};
-use std::io;
-use std::time;
+use crate::error::{ConversionError, DecodeError, EncodeError, ValidationError};
+use crate::io;
+use crate::options::EncodeOptions;
+use crate::{DecodeResult, EncodeResult, Pair, APPROX_NODE_SIZE};
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::{self, Ordering};
+use core::hash::{Hash, Hasher};
+use core::time;
 
 pub use self::decode::Decoder;
 pub use self::encode::Encoder;
+pub use self::value_ref::ValueRef;
+
+pub(crate) use self::decode::ReferenceTables;
+pub(crate) use self::value_ref::SliceDecoder;
 
 mod decode;
 mod encode;
+mod value_ref;
 
-mod marker {
+pub(crate) mod marker {
     pub const UNDEFINED: u8 = 0x00;
     pub const NULL: u8 = 0x01;
     pub const FALSE: u8 = 0x02;
@@ -44,6 +56,12 @@ mod marker {
     pub const DICTIONARY: u8 = 0x11;
 }
 
+/// The minimum value of `Value::Integer`.
+const MIN_INTEGER: i32 = -0x1000_0000;
+
+/// The maximum value of `Value::Integer`.
+const MAX_INTEGER: i32 = 0x0FFF_FFFF;
+
 /// AMF3 value.
 ///
 /// # Examples
@@ -60,6 +78,7 @@ mod marker {
 /// assert_eq!(integer, decoded);
 /// ```
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[non_exhaustive]
 pub enum Value {
     /// See [3.2 undefined Type]
     /// (https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf#page=6&zoom=auto,88,264).
@@ -78,6 +97,10 @@ pub enum Value {
 
     /// See [3.6 integer Type]
     /// (https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf#page=7&zoom=auto,88,499).
+    ///
+    /// The encodable range is 29-bit signed (i.e., `-0x1000_0000..=0x0FFF_FFFF`).
+    /// Use `Value::integer_or_double` to make a value from an integer which may be out of the range.
+    /// An out-of-range value is encoded as a double.
     Integer(i32),
 
     /// See [3.7 double Type]
@@ -167,13 +190,17 @@ pub enum Value {
     /// (https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf#page=12&zoom=auto,88,534).
     ObjectVector {
         /// The base type name of entries in the vector.
-        /// `None` means it is the ANY type.
+        /// `None` means it is the ANY type, whose entries may be objects of different classes.
         class_name: Option<String>,
 
         /// If `true`, this is a fixed-length vector.
         is_fixed: bool,
 
         /// The entries of the vector.
+        ///
+        /// Each entry must be an `Object` or `Null` to be encoded.
+        /// The class names of the entries are not checked against `class_name`
+        /// (they may be subclasses of it).
         entries: Vec<Value>,
     },
 
@@ -184,10 +211,309 @@ pub enum Value {
         is_weak: bool,
 
         /// The entries of the dictionary.
+        ///
+        /// A key must not be `Double(NaN)` to be encoded, because such a key can never be looked up.
         entries: Vec<Pair<Value, Value>>,
     },
+
+    /// A placeholder for a byte array whose bytes have been streamed into a sink.
+    ///
+    /// This is produced only by a decoder which has a sink (see `Decoder::with_byte_array_sink`).
+    /// The bytes of the array are at `offset..offset + len` of the output of the sink.
+    /// It cannot be encoded (`ValidationError::StreamedPlaceholder` is returned).
+    StreamedByteArray {
+        /// The offset of the bytes in the output of the sink.
+        offset: usize,
+
+        /// The number of the bytes.
+        len: usize,
+    },
 }
 impl Value {
+    /// Makes an empty anonymous `Object` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// let mut buf = Vec::new();
+    /// Value::empty_object().write_to(&mut buf).unwrap();
+    /// assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), Value::empty_object());
+    /// ```
+    pub fn empty_object() -> Self {
+        Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Makes an empty `Array` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// let mut buf = Vec::new();
+    /// Value::empty_array().write_to(&mut buf).unwrap();
+    /// assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), Value::empty_array());
+    /// ```
+    pub fn empty_array() -> Self {
+        Value::Array {
+            assoc_entries: Vec::new(),
+            dense_entries: Vec::new(),
+        }
+    }
+
+    /// Makes an anonymous `Object` value which has the given entries as its dynamic members.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// let object = Value::anonymous_object(vec![("foo", Value::Integer(1))]);
+    /// let mut buf = Vec::new();
+    /// object.write_to(&mut buf).unwrap();
+    /// assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), object);
+    /// ```
+    pub fn anonymous_object<I, K>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = (K, Value)>,
+        String: From<K>,
+    {
+        Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: entries
+                .into_iter()
+                .map(|(k, v)| Pair {
+                    key: From::from(k),
+                    value: v,
+                })
+                .collect(),
+        }
+    }
+
+    /// Makes an `Integer` value if `n` is in the 29-bit signed range, otherwise a `Double` value.
+    ///
+    /// This is the same as how ActionScript encodes numbers.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// assert_eq!(Value::integer_or_double(0x0FFF_FFFF), Value::Integer(0x0FFF_FFFF));
+    /// assert_eq!(Value::integer_or_double(0x1000_0000), Value::Double(268435456.0));
+    /// ```
+    pub fn integer_or_double(n: i64) -> Self {
+        if (i64::from(MIN_INTEGER)..=i64::from(MAX_INTEGER)).contains(&n) {
+            Value::Integer(n as i32)
+        } else {
+            Value::Double(n as f64)
+        }
+    }
+
+    /// Makes an `IntVector` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// let vector = Value::int_vector(vec![1, -2, 3], true);
+    /// let mut buf = Vec::new();
+    /// vector.write_to(&mut buf).unwrap();
+    /// assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), vector);
+    /// ```
+    pub fn int_vector<I>(entries: I, is_fixed: bool) -> Self
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        Value::IntVector {
+            is_fixed,
+            entries: entries.into_iter().collect(),
+        }
+    }
+
+    /// Makes a `UintVector` value.
+    pub fn uint_vector<I>(entries: I, is_fixed: bool) -> Self
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        Value::UintVector {
+            is_fixed,
+            entries: entries.into_iter().collect(),
+        }
+    }
+
+    /// Makes a `DoubleVector` value.
+    pub fn double_vector<I>(entries: I, is_fixed: bool) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        Value::DoubleVector {
+            is_fixed,
+            entries: entries.into_iter().collect(),
+        }
+    }
+
+    /// Makes an `ObjectVector` value.
+    ///
+    /// `None` as `class_name` means the vector is of the ANY type.
+    pub fn object_vector<I>(class_name: Option<String>, entries: I, is_fixed: bool) -> Self
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        Value::ObjectVector {
+            class_name,
+            is_fixed,
+            entries: entries.into_iter().collect(),
+        }
+    }
+
+    /// Makes a `Date` value from a Unix timestamp in milliseconds.
+    ///
+    /// Like the decoder, this returns `DecodeError::InvalidDate` if `millis` is negative.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// let date = Value::date_from_millis(1_590_796_800_000).unwrap();
+    /// assert_eq!(date.date_millis(), Some(1_590_796_800_000));
+    /// ```
+    pub fn date_from_millis(millis: i64) -> DecodeResult<Value> {
+        let millis = u64::try_from(millis).map_err(|_| DecodeError::InvalidDate {
+            millis: millis as f64,
+        })?;
+        Ok(Value::Date {
+            unix_time: time::Duration::from_millis(millis),
+        })
+    }
+
+    /// Makes a `Date` value from a Unix timestamp in seconds.
+    pub fn date_from_unix_secs(secs: u64) -> Value {
+        Value::Date {
+            unix_time: time::Duration::from_secs(secs),
+        }
+    }
+
+    /// Makes a `Date` value from a Unix timestamp in milliseconds.
+    ///
+    /// Unlike `date_from_millis`, this never fails.
+    pub fn date_from_unix_millis(millis: u64) -> Value {
+        Value::Date {
+            unix_time: time::Duration::from_millis(millis),
+        }
+    }
+
+    /// Makes an `Xml` value (an E4X XML document, encoded with the `XML` marker `0x0B`).
+    ///
+    /// Use `xml_document` for a legacy `flash.xml.XMLDocument`.
+    pub fn xml<T>(text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Value::Xml(text.into())
+    }
+
+    /// Makes an `XmlDocument` value (a legacy `flash.xml.XMLDocument`, encoded with the `XML_DOC` marker `0x07`).
+    ///
+    /// Use `xml` for an E4X XML document.
+    pub fn xml_document<T>(text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Value::XmlDocument(text.into())
+    }
+
+    /// Wraps this value in an AMF0 `AvmPlus` value.
+    ///
+    /// This is how an AMF3 value is sent in AMF0 contexts.
+    pub fn into_amf0_avmplus(self) -> crate::amf0::Value {
+        crate::amf0::Value::AvmPlus(self)
+    }
+
+    /// Converts this value into the equivalent AMF0 value.
+    ///
+    /// An `Integer` and a `Double` become a `Number`, and an `Xml` becomes an `XmlDocument`.
+    /// An `Array` which has associative entries becomes an `EcmaArray`
+    /// whose dense entries are keyed by their indices (e.g., `"0"`) and followed by the associative ones.
+    /// The values which have no AMF0 counterpart (byte arrays, vectors and dictionaries)
+    /// are wrapped in `AvmPlus` values as they are.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{amf0, amf3};
+    ///
+    /// assert_eq!(amf3::Value::Integer(1).to_amf0(), amf0::number(1));
+    /// assert_eq!(
+    ///     amf3::Value::ByteArray(vec![1]).to_amf0(),
+    ///     amf0::Value::AvmPlus(amf3::Value::ByteArray(vec![1]))
+    /// );
+    /// ```
+    pub fn to_amf0(&self) -> crate::amf0::Value {
+        use crate::amf0;
+
+        let pairs_to_amf0 = |entries: &[Pair<String, Value>]| {
+            entries
+                .iter()
+                .map(|p| Pair {
+                    key: p.key.clone(),
+                    value: p.value.to_amf0(),
+                })
+                .collect::<Vec<_>>()
+        };
+        match *self {
+            Value::Undefined => amf0::Value::Undefined,
+            Value::Null => amf0::Value::Null,
+            Value::Boolean(x) => amf0::Value::Boolean(x),
+            Value::Integer(x) => amf0::Value::Number(f64::from(x)),
+            Value::Double(x) => amf0::Value::Number(x),
+            Value::String(ref x) => amf0::Value::String(x.clone()),
+            Value::XmlDocument(ref x) | Value::Xml(ref x) => amf0::Value::XmlDocument(x.clone()),
+            Value::Date { unix_time } => amf0::Value::Date {
+                unix_time,
+                time_zone: 0,
+            },
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } if assoc_entries.is_empty() => amf0::Value::Array {
+                entries: dense_entries.iter().map(Value::to_amf0).collect(),
+            },
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => amf0::Value::EcmaArray {
+                entries: dense_entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| Pair {
+                        key: i.to_string(),
+                        value: v.to_amf0(),
+                    })
+                    .chain(pairs_to_amf0(assoc_entries))
+                    .collect(),
+                declared_count: None,
+            },
+            Value::Object {
+                ref class_name,
+                ref entries,
+                ..
+            } => amf0::Value::Object {
+                class_name: class_name.clone(),
+                entries: pairs_to_amf0(entries),
+            },
+            Value::ByteArray(_)
+            | Value::IntVector { .. }
+            | Value::UintVector { .. }
+            | Value::DoubleVector { .. }
+            | Value::ObjectVector { .. }
+            | Value::Dictionary { .. }
+            | Value::StreamedByteArray { .. } => amf0::Value::AvmPlus(self.clone()),
+        }
+    }
+
     /// Reads an AMF3 encoded `Value` from `reader`.
     ///
     /// Note that reference objects are copied in the decoding phase
@@ -201,13 +527,213 @@ impl Value {
     }
 
     /// Writes the AMF3 encoded bytes of this value to `writer`.
-    pub fn write_to<W>(&self, writer: W) -> io::Result<()>
+    pub fn write_to<W>(&self, writer: W) -> EncodeResult<()>
     where
         W: io::Write,
     {
         Encoder::new(writer).encode(self)
     }
 
+    /// Writes the AMF3 encoded bytes of this value to `writer` with the given options.
+    pub fn write_to_with_options<W>(&self, writer: W, options: &EncodeOptions) -> EncodeResult<()>
+    where
+        W: io::Write,
+    {
+        Encoder::with_options(writer, options.clone()).encode(self)
+    }
+
+    /// Checks the structural invariants which `write_to` requires
+    /// (e.g., the `sealed_count` of an object must not exceed the number of its entries,
+    /// the entries of an `ObjectVector` must be objects or nulls,
+    /// and the keys of a `Dictionary` must not be `Double(NaN)`).
+    ///
+    /// `write_to` returns `EncodeError::Invalid` for a value which fails this check.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match Encoder::new(io::sink()).encode(self) {
+            Err(EncodeError::Invalid(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the AMF3 encoded bytes of this value to `writer`,
+    /// returning the number of bytes written.
+    pub fn write_to_counting<W>(&self, writer: W) -> EncodeResult<usize>
+    where
+        W: io::Write,
+    {
+        let mut writer = crate::CountingWriter::with_inner(writer);
+        self.write_to(&mut writer)?;
+        Ok(writer.count())
+    }
+
+    /// Returns a quick estimate of the number of bytes which `write_to` writes for this value.
+    ///
+    /// This is meant to reserve a buffer in advance, and is cheaper than actually encoding the value
+    /// (e.g., by `write_to_counting`).
+    /// The estimate is the sum of the lengths of the strings (and the byte arrays and the vectors)
+    /// plus a fixed overhead of 16 bytes for each value and each key.
+    /// It is not an exact size, but it is never smaller than the encoded size,
+    /// and at most 16 times of it.
+    pub fn approx_size(&self) -> usize {
+        let size = match *self {
+            Value::String(ref x) | Value::XmlDocument(ref x) | Value::Xml(ref x) => x.len(),
+            Value::ByteArray(ref x) => x.len(),
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => {
+                approx_pairs_size(assoc_entries)
+                    + dense_entries.iter().map(Value::approx_size).sum::<usize>()
+            }
+            Value::Object {
+                ref class_name,
+                ref entries,
+                ..
+            } => class_name.as_ref().map_or(0, String::len) + approx_pairs_size(entries),
+            Value::IntVector { ref entries, .. } => entries.len() * 4,
+            Value::UintVector { ref entries, .. } => entries.len() * 4,
+            Value::DoubleVector { ref entries, .. } => entries.len() * 8,
+            Value::ObjectVector {
+                ref class_name,
+                ref entries,
+                ..
+            } => {
+                class_name.as_ref().map_or(0, String::len)
+                    + entries.iter().map(Value::approx_size).sum::<usize>()
+            }
+            Value::Dictionary { ref entries, .. } => entries
+                .iter()
+                .map(|p| p.key.approx_size() + p.value.approx_size())
+                .sum(),
+            _ => 0,
+        };
+        APPROX_NODE_SIZE + size
+    }
+
+    /// Returns the maximum nesting level of this value.
+    ///
+    /// A scalar (or an empty collection) is `1`, an array of scalars is `2`,
+    /// an object which has an array of scalars is `3`, and so on.
+    /// The elements of the vectors and the keys of the dictionaries are also counted,
+    /// and a `Date` is a scalar.
+    pub fn depth(&self) -> usize {
+        let children = match *self {
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => cmp::max(
+                pairs_depth(assoc_entries),
+                dense_entries.iter().map(Value::depth).max().unwrap_or(0),
+            ),
+            Value::Object { ref entries, .. } => pairs_depth(entries),
+            Value::IntVector { ref entries, .. } => usize::from(!entries.is_empty()),
+            Value::UintVector { ref entries, .. } => usize::from(!entries.is_empty()),
+            Value::DoubleVector { ref entries, .. } => usize::from(!entries.is_empty()),
+            Value::ObjectVector { ref entries, .. } => {
+                entries.iter().map(Value::depth).max().unwrap_or(0)
+            }
+            Value::Dictionary { ref entries, .. } => entries
+                .iter()
+                .map(|p| cmp::max(p.key.depth(), p.value.depth()))
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        };
+        1 + children
+    }
+
+    /// Returns the number of the values in this value (including itself).
+    ///
+    /// The values of the entries of objects and arrays are counted (but not their keys),
+    /// and so are both the keys and the values of dictionaries.
+    /// Each element of a numeric vector is counted as a node, as is each element of an `ObjectVector`.
+    pub fn count_nodes(&self) -> usize {
+        let children = match *self {
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => {
+                assoc_entries
+                    .iter()
+                    .map(|p| p.value.count_nodes())
+                    .sum::<usize>()
+                    + dense_entries.iter().map(Value::count_nodes).sum::<usize>()
+            }
+            Value::Object { ref entries, .. } => {
+                entries.iter().map(|p| p.value.count_nodes()).sum()
+            }
+            Value::IntVector { ref entries, .. } => entries.len(),
+            Value::UintVector { ref entries, .. } => entries.len(),
+            Value::DoubleVector { ref entries, .. } => entries.len(),
+            Value::ObjectVector { ref entries, .. } => entries.iter().map(Value::count_nodes).sum(),
+            Value::Dictionary { ref entries, .. } => entries
+                .iter()
+                .map(|p| p.key.count_nodes() + p.value.count_nodes())
+                .sum(),
+            _ => 0,
+        };
+        1 + children
+    }
+
+    /// Returns the name of the variant of this value (e.g., `"Double"` or `"ByteArray"`).
+    ///
+    /// The name is the same as the variant name and is stable across versions.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Undefined => "Undefined",
+            Value::Null => "Null",
+            Value::Boolean(_) => "Boolean",
+            Value::Integer(_) => "Integer",
+            Value::Double(_) => "Double",
+            Value::String(_) => "String",
+            Value::XmlDocument(_) => "XmlDocument",
+            Value::Date { .. } => "Date",
+            Value::Array { .. } => "Array",
+            Value::Object { .. } => "Object",
+            Value::Xml(_) => "Xml",
+            Value::ByteArray(_) => "ByteArray",
+            Value::IntVector { .. } => "IntVector",
+            Value::UintVector { .. } => "UintVector",
+            Value::DoubleVector { .. } => "DoubleVector",
+            Value::ObjectVector { .. } => "ObjectVector",
+            Value::Dictionary { .. } => "Dictionary",
+            Value::StreamedByteArray { .. } => "StreamedByteArray",
+        }
+    }
+
+    /// Returns `true` if this is a complex value which is stored in the object reference table
+    /// (see the description of references in [3 AMF 3 Data Types]
+    /// (https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf)).
+    ///
+    /// Those are all values other than `Undefined`, `Null`, `Boolean`, `Integer`, `Double` and `String`.
+    /// Note that strings have their own reference table.
+    pub fn is_reference_type(&self) -> bool {
+        !matches!(
+            *self,
+            Value::Undefined
+                | Value::Null
+                | Value::Boolean(_)
+                | Value::Integer(_)
+                | Value::Double(_)
+                | Value::String(_)
+        )
+    }
+
+    /// Returns `true` if this is an `Xml` or an `XmlDocument`.
+    pub fn is_xml(&self) -> bool {
+        matches!(*self, Value::Xml(_) | Value::XmlDocument(_))
+    }
+
+    /// Returns the text of the document if this is an `Xml` or an `XmlDocument`.
+    ///
+    /// Unlike `try_as_str`, this returns `None` for a `String`.
+    pub fn as_xml_str(&self) -> Option<&str> {
+        match *self {
+            Value::Xml(ref x) | Value::XmlDocument(ref x) => Some(x.as_str()),
+            _ => None,
+        }
+    }
+
     /// Tries to convert the value as a `str` reference.
     pub fn try_as_str(&self) -> Option<&str> {
         match *self {
@@ -227,7 +753,67 @@ impl Value {
         }
     }
 
+    /// Returns `true` if this is an `Integer` or a finite `Double` (i.e., neither `NaN` nor an infinity).
+    ///
+    /// See also `DecodeOptions::reject_non_finite_numbers`.
+    pub fn is_finite_number(&self) -> bool {
+        self.try_as_f64().is_some_and(f64::is_finite)
+    }
+
+    /// Returns the Unix timestamp in milliseconds if this is a `Date`.
+    ///
+    /// Returns `None` if the timestamp does not fit in an `i64`.
+    pub fn date_millis(&self) -> Option<i64> {
+        match *self {
+            Value::Date { unix_time } => i64::try_from(unix_time.as_millis()).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of the entries if this is a collection value.
+    ///
+    /// For an `Array`, only the dense part is counted (use `assoc_len` for the associative part).
+    /// Returns `None` for scalar values (note that `ByteArray` is also treated as a scalar).
+    pub fn len(&self) -> Option<usize> {
+        match *self {
+            Value::Array {
+                ref dense_entries, ..
+            } => Some(dense_entries.len()),
+            Value::Object { ref entries, .. } => Some(entries.len()),
+            Value::IntVector { ref entries, .. } => Some(entries.len()),
+            Value::UintVector { ref entries, .. } => Some(entries.len()),
+            Value::DoubleVector { ref entries, .. } => Some(entries.len()),
+            Value::ObjectVector { ref entries, .. } => Some(entries.len()),
+            Value::Dictionary { ref entries, .. } => Some(entries.len()),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some(true)` if this is a collection value which has no entries.
+    ///
+    /// This is equivalent to `self.len().map(|n| n == 0)`.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|n| n == 0)
+    }
+
+    /// Returns the number of the entries in the associative part if this is an `Array`.
+    pub fn assoc_len(&self) -> Option<usize> {
+        match *self {
+            Value::Array {
+                ref assoc_entries, ..
+            } => Some(assoc_entries.len()),
+            _ => None,
+        }
+    }
+
     /// Tries to convert the value as an iterator of the contained values.
+    ///
+    /// For an `Array`, only the dense entries are yielded and the associative entries are dropped
+    /// (use `try_into_assoc` or `try_into_all` to get them).
+    /// The entries of an `IntVector` are converted to `Integer`s.
+    /// The entries of a `UintVector` are converted as `integer_or_double` does
+    /// (i.e., to `Integer`s if they fit in the 29-bit range, and to `Double`s otherwise),
+    /// so every `u32` is represented without loss.
     pub fn try_into_values(self) -> Result<Box<dyn Iterator<Item = Value>>, Self> {
         match self {
             Value::Array { dense_entries, .. } => Ok(Box::new(dense_entries.into_iter())),
@@ -235,7 +821,9 @@ impl Value {
                 Ok(Box::new(entries.into_iter().map(Value::Integer)))
             }
             Value::UintVector { entries, .. } => Ok(Box::new(
-                entries.into_iter().map(|n| Value::Double(n as f64)),
+                entries
+                    .into_iter()
+                    .map(|n| Value::integer_or_double(i64::from(n))),
             )),
             Value::DoubleVector { entries, .. } => {
                 Ok(Box::new(entries.into_iter().map(Value::Double)))
@@ -245,7 +833,58 @@ impl Value {
         }
     }
 
+    /// Tries to convert the value as an iterator of the associative entries if this is an `Array`.
+    ///
+    /// The dense entries are dropped (use `try_into_all` to get both).
+    pub fn try_into_assoc(self) -> Result<Box<dyn Iterator<Item = (String, Value)>>, Self> {
+        match self {
+            Value::Array { assoc_entries, .. } => Ok(Box::new(
+                assoc_entries.into_iter().map(|p| (p.key, p.value)),
+            )),
+            _ => Err(self),
+        }
+    }
+
+    /// Tries to convert the value as an iterator of all the entries if this is an `Array`.
+    ///
+    /// The dense entries are yielded first with their indices as keys (e.g., `"0"`),
+    /// followed by the associative entries (as `to_amf0` does).
+    /// Note that an associative key may be equal to the index of a dense entry.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    /// use amf::Pair;
+    ///
+    /// let array = Value::Array {
+    ///     assoc_entries: vec![Pair { key: "foo".to_owned(), value: Value::Null }],
+    ///     dense_entries: vec![Value::Integer(1)],
+    /// };
+    /// assert_eq!(
+    ///     array.try_into_all().unwrap().collect::<Vec<_>>(),
+    ///     [("0".to_owned(), Value::Integer(1)), ("foo".to_owned(), Value::Null)]
+    /// );
+    /// ```
+    pub fn try_into_all(self) -> Result<Box<dyn Iterator<Item = (String, Value)>>, Self> {
+        match self {
+            Value::Array {
+                assoc_entries,
+                dense_entries,
+            } => Ok(Box::new(
+                dense_entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| (i.to_string(), v))
+                    .chain(assoc_entries.into_iter().map(|p| (p.key, p.value))),
+            )),
+            _ => Err(self),
+        }
+    }
+
     /// Tries to convert the value as an iterator of the contained pairs.
+    ///
+    /// A `Dictionary` is converted only if all of its keys are `String`s or `Integer`s.
+    /// `Integer` keys are converted to their decimal representations (e.g., `Integer(-1)` to `"-1"`).
     pub fn try_into_pairs(self) -> Result<Box<dyn Iterator<Item = (String, Value)>>, Self> {
         match self {
             Value::Array { assoc_entries, .. } => Ok(Box::new(
@@ -254,7 +893,1997 @@ impl Value {
             Value::Object { entries, .. } => {
                 Ok(Box::new(entries.into_iter().map(|p| (p.key, p.value))))
             }
+            Value::Dictionary { is_weak, entries } => {
+                let is_stringifiable =
+                    |k: &Value| matches!(k, Value::String(_) | Value::Integer(_));
+                if !entries.iter().all(|p| is_stringifiable(&p.key)) {
+                    return Err(Value::Dictionary { is_weak, entries });
+                }
+                Ok(Box::new(entries.into_iter().filter_map(|p| match p.key {
+                    Value::String(key) => Some((key, p.value)),
+                    Value::Integer(key) => Some((key.to_string(), p.value)),
+                    _ => None,
+                })))
+            }
+            _ => Err(self),
+        }
+    }
+
+    /// Returns the value associated with `key` if this is a `Dictionary`.
+    ///
+    /// The entries are scanned linearly and the keys are compared by `==`.
+    /// If there are multiple entries with the same key, the first one is returned.
+    pub fn dictionary_get(&self, key: &Value) -> Option<&Value> {
+        match *self {
+            Value::Dictionary { ref entries, .. } => {
+                entries.iter().find(|p| p.key == *key).map(|p| &p.value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the keys if this is a `Dictionary`.
+    pub fn dictionary_keys(&self) -> Option<impl Iterator<Item = &Value>> {
+        match *self {
+            Value::Dictionary { ref entries, .. } => Some(entries.iter().map(|p| &p.key)),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the values if this is a `Dictionary`.
+    pub fn dictionary_values(&self) -> Option<impl Iterator<Item = &Value>> {
+        match *self {
+            Value::Dictionary { ref entries, .. } => Some(entries.iter().map(|p| &p.value)),
+            _ => None,
+        }
+    }
+
+    /// Returns the sealed members (i.e., the first `sealed_count` entries) if this is an `Object`.
+    ///
+    /// If `sealed_count` exceeds the number of the entries, all the entries are returned.
+    pub fn sealed_entries(&self) -> Option<&[Pair<String, Value>]> {
+        match *self {
+            Value::Object {
+                sealed_count,
+                ref entries,
+                ..
+            } => Some(&entries[..sealed_count.min(entries.len())]),
+            _ => None,
+        }
+    }
+
+    /// Returns the dynamic members (i.e., the entries after the sealed ones) if this is an `Object`.
+    pub fn dynamic_entries(&self) -> Option<&[Pair<String, Value>]> {
+        match *self {
+            Value::Object {
+                sealed_count,
+                ref entries,
+                ..
+            } => Some(&entries[sealed_count.min(entries.len())..]),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some(true)` if this is an `Object` which has dynamic members.
+    ///
+    /// Like the encoder, an object is regarded as dynamic iff `sealed_count` is less than the number of the entries.
+    pub fn is_dynamic(&self) -> Option<bool> {
+        self.dynamic_entries().map(|x| !x.is_empty())
+    }
+
+    /// Returns the members (sealed and dynamic) as an insertion-ordered map from key to value
+    /// if this is an `Object`.
+    ///
+    /// The lookup takes constant time, unlike a linear scan of the entries.
+    /// If a key is duplicated, the last entry wins but the key keeps the position of its first entry.
+    #[cfg(feature = "indexmap")]
+    pub fn object_map(&self) -> Option<indexmap::IndexMap<&str, &Value>> {
+        match *self {
+            Value::Object { ref entries, .. } => {
+                Some(entries.iter().map(|p| (p.key.as_str(), &p.value)).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the standard base64 encoding (with padding) of the bytes if this is a `ByteArray`.
+    ///
+    /// The bytes are encoded as is (i.e., they are not interpreted as AMF).
+    #[cfg(feature = "base64")]
+    pub fn byte_array_base64(&self) -> Option<String> {
+        use base64::Engine;
+
+        match *self {
+            Value::ByteArray(ref x) => Some(base64::engine::general_purpose::STANDARD.encode(x)),
+            _ => None,
+        }
+    }
+
+    /// Makes a `ByteArray` value from the standard base64 encoding (with padding) of its bytes.
+    ///
+    /// This is the inverse of `byte_array_base64`.
+    #[cfg(feature = "base64")]
+    pub fn byte_array_from_base64(text: &str) -> DecodeResult<Value> {
+        use base64::Engine;
+
+        let bytes = base64::engine::general_purpose::STANDARD.decode(text)?;
+        Ok(Value::ByteArray(bytes))
+    }
+
+    /// Tries to convert a `Dictionary` whose keys are all `String`s into a vector of string-keyed pairs.
+    ///
+    /// The order of the entries is preserved, and duplicate keys (if any) are kept as is.
+    /// If this is not a `Dictionary` or it has a non-string key, the value is returned unchanged.
+    pub fn try_into_string_dictionary(self) -> Result<Vec<(String, Value)>, Self> {
+        match self {
+            Value::Dictionary { is_weak, entries } => {
+                if !entries.iter().all(|p| matches!(p.key, Value::String(_))) {
+                    return Err(Value::Dictionary { is_weak, entries });
+                }
+                Ok(entries
+                    .into_iter()
+                    .filter_map(|p| match p.key {
+                        Value::String(key) => Some((key, p.value)),
+                        _ => None,
+                    })
+                    .collect())
+            }
             _ => Err(self),
         }
     }
+
+    /// Decodes the content of a `ByteArray` value as an AMF3 value.
+    ///
+    /// The content is decoded by a new `Decoder`, so it has its own reference tables.
+    /// Returns `None` if this is not a `ByteArray`.
+    pub fn decode_byte_array_as_amf3(&self) -> Option<DecodeResult<Value>> {
+        match *self {
+            Value::ByteArray(ref x) => Some(Decoder::new(&x[..]).decode()),
+            _ => None,
+        }
+    }
+
+    /// Merges the members of `other` into this value.
+    ///
+    /// Both values must be an `Object`.
+    /// The value of an existing member (sealed or dynamic) is overwritten,
+    /// and new members are appended as dynamic members in the order of `other`.
+    ///
+    /// If either value is not an `Object`, this value is left unchanged and `other` is returned as the error.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    ///
+    /// let mut base = Value::anonymous_object(vec![("a", Value::Integer(1))]);
+    /// base.merge(Value::anonymous_object(vec![("b", Value::Integer(2))])).unwrap();
+    /// assert_eq!(
+    ///     base,
+    ///     Value::anonymous_object(vec![("a", Value::Integer(1)), ("b", Value::Integer(2))])
+    /// );
+    /// ```
+    pub fn merge(&mut self, other: Value) -> Result<(), Value> {
+        let pairs = match *self {
+            Value::Object {
+                ref mut entries, ..
+            } => entries,
+            _ => return Err(other),
+        };
+        match other {
+            Value::Object { entries, .. } => {
+                merge_pairs(pairs, entries);
+                Ok(())
+            }
+            _ => Err(other),
+        }
+    }
+
+    /// Removes the entries whose keys are rejected by `f` if this is an `Object`
+    /// (or the associative entries if this is an `Array`), keeping the order of the rest.
+    ///
+    /// The `sealed_count` of an object is decreased by the number of the removed sealed members,
+    /// so the remaining sealed members stay in front and the value stays encodable
+    /// (an object whose sealed members are all removed becomes a dynamic-only object
+    /// with `sealed_count: 0`).
+    /// Note that removing a sealed member changes the trait of the object,
+    /// so a peer which knows the class may reject it.
+    ///
+    /// Only the entries of this value are filtered (use `walk_mut` to filter nested values too).
+    /// Other values are left unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::amf3::Value;
+    /// use amf::Pair;
+    ///
+    /// let pair = |key: &str| Pair { key: key.to_owned(), value: Value::Null };
+    /// let mut object = Value::Object {
+    ///     class_name: Some("User".to_owned()),
+    ///     sealed_count: 2,
+    ///     entries: vec![pair("name"), pair("password"), pair("extra")],
+    /// };
+    /// object.retain_keys(|k| k != "password");
+    /// assert_eq!(
+    ///     object,
+    ///     Value::Object {
+    ///         class_name: Some("User".to_owned()),
+    ///         sealed_count: 1,
+    ///         entries: vec![pair("name"), pair("extra")],
+    ///     }
+    /// );
+    /// ```
+    pub fn retain_keys<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        match *self {
+            Value::Object {
+                ref mut sealed_count,
+                ref mut entries,
+                ..
+            } => {
+                let mut index = 0;
+                let sealed = *sealed_count;
+                let mut removed_sealed = 0;
+                entries.retain(|p| {
+                    let retained = f(&p.key);
+                    if !retained && index < sealed {
+                        removed_sealed += 1;
+                    }
+                    index += 1;
+                    retained
+                });
+                *sealed_count = sealed.saturating_sub(removed_sealed);
+            }
+            Value::Array {
+                ref mut assoc_entries,
+                ..
+            } => assoc_entries.retain(|p| f(&p.key)),
+            _ => {}
+        }
+    }
+
+    /// Sorts the dynamic members of the objects and the associative entries of the arrays
+    /// in this value by key, recursively.
+    ///
+    /// The sealed members of an object stay in front in their original order,
+    /// because their order is defined by the trait.
+    /// The sort is stable, so entries with the same key keep their relative order.
+    /// The entries of dictionaries are not reordered (but their keys and values are sorted).
+    pub fn sort_keys(&mut self) {
+        match *self {
+            Value::Array {
+                ref mut assoc_entries,
+                ref mut dense_entries,
+            } => {
+                sort_pairs(assoc_entries);
+                dense_entries.iter_mut().for_each(Value::sort_keys);
+            }
+            Value::Object {
+                sealed_count,
+                ref mut entries,
+                ..
+            } => {
+                let sealed_count = cmp::min(sealed_count, entries.len());
+                let (sealed, dynamic) = entries.split_at_mut(sealed_count);
+                sealed.iter_mut().for_each(|p| p.value.sort_keys());
+                sort_pairs(dynamic);
+            }
+            Value::ObjectVector {
+                ref mut entries, ..
+            } => entries.iter_mut().for_each(Value::sort_keys),
+            Value::Dictionary {
+                ref mut entries, ..
+            } => {
+                for p in entries {
+                    p.key.sort_keys();
+                    p.value.sort_keys();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Calls `f` on this value and all the values contained in it, recursively.
+    ///
+    /// The values are visited in pre-order (i.e., a collection is visited before its entries),
+    /// and the entries of a collection are visited in order.
+    /// The associative entries of an `Array` are visited before the dense ones,
+    /// and the key of each `Dictionary` entry is visited before its value.
+    pub fn walk<F>(&self, mut f: F)
+    where
+        F: FnMut(&Value),
+    {
+        self.walk_with(&mut f);
+    }
+
+    fn walk_with<F>(&self, f: &mut F)
+    where
+        F: FnMut(&Value),
+    {
+        f(self);
+        match *self {
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => {
+                assoc_entries.iter().for_each(|p| p.value.walk_with(f));
+                dense_entries.iter().for_each(|v| v.walk_with(f));
+            }
+            Value::Object { ref entries, .. } => entries.iter().for_each(|p| p.value.walk_with(f)),
+            Value::ObjectVector { ref entries, .. } => entries.iter().for_each(|v| v.walk_with(f)),
+            Value::Dictionary { ref entries, .. } => {
+                for p in entries {
+                    p.key.walk_with(f);
+                    p.value.walk_with(f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Calls `f` on this value and all the values contained in it, recursively, with mutable access.
+    ///
+    /// The traversal order is the same as `walk`.
+    /// Since `f` is called on a collection before its entries,
+    /// the entries visited are the ones after `f` is applied to the collection.
+    pub fn walk_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Value),
+    {
+        self.walk_mut_with(&mut f);
+    }
+
+    fn walk_mut_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Value),
+    {
+        f(self);
+        match *self {
+            Value::Array {
+                ref mut assoc_entries,
+                ref mut dense_entries,
+            } => {
+                assoc_entries
+                    .iter_mut()
+                    .for_each(|p| p.value.walk_mut_with(f));
+                dense_entries.iter_mut().for_each(|v| v.walk_mut_with(f));
+            }
+            Value::Object {
+                ref mut entries, ..
+            } => entries.iter_mut().for_each(|p| p.value.walk_mut_with(f)),
+            Value::ObjectVector {
+                ref mut entries, ..
+            } => entries.iter_mut().for_each(|v| v.walk_mut_with(f)),
+            Value::Dictionary {
+                ref mut entries, ..
+            } => {
+                for p in entries {
+                    p.key.walk_mut_with(f);
+                    p.value.walk_mut_with(f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns this value with the keys sorted by `sort_keys`.
+    pub fn sorted(mut self) -> Self {
+        self.sort_keys();
+        self
+    }
+
+    /// Compares two values with a total order.
+    ///
+    /// Values of different variants are ordered by the declaration order of the variants
+    /// (as `PartialOrd` does), and floating point numbers are compared by `f64::total_cmp`.
+    /// So, unlike `PartialOrd`, `NaN` is comparable with any number
+    /// (a positive `NaN` is greater than any other number and a negative one is less)
+    /// and `-0.0` is less than `+0.0`.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Double(a), Value::Double(b)) => a.total_cmp(b),
+            (Value::String(a), Value::String(b))
+            | (Value::XmlDocument(a), Value::XmlDocument(b))
+            | (Value::Xml(a), Value::Xml(b)) => a.cmp(b),
+            (Value::Date { unix_time: a }, Value::Date { unix_time: b }) => a.cmp(b),
+            (
+                Value::Array {
+                    assoc_entries: a0,
+                    dense_entries: a1,
+                },
+                Value::Array {
+                    assoc_entries: b0,
+                    dense_entries: b1,
+                },
+            ) => cmp_pairs(a0, b0).then_with(|| cmp_values(a1, b1)),
+            (
+                Value::Object {
+                    class_name: a0,
+                    sealed_count: a1,
+                    entries: a2,
+                },
+                Value::Object {
+                    class_name: b0,
+                    sealed_count: b1,
+                    entries: b2,
+                },
+            ) => a0.cmp(b0).then(a1.cmp(b1)).then_with(|| cmp_pairs(a2, b2)),
+            (Value::ByteArray(a), Value::ByteArray(b)) => a.cmp(b),
+            (
+                Value::StreamedByteArray {
+                    offset: a0,
+                    len: a1,
+                },
+                Value::StreamedByteArray {
+                    offset: b0,
+                    len: b1,
+                },
+            ) => a0.cmp(b0).then(a1.cmp(b1)),
+            (
+                Value::IntVector {
+                    is_fixed: a0,
+                    entries: a1,
+                },
+                Value::IntVector {
+                    is_fixed: b0,
+                    entries: b1,
+                },
+            ) => a0.cmp(b0).then_with(|| a1.cmp(b1)),
+            (
+                Value::UintVector {
+                    is_fixed: a0,
+                    entries: a1,
+                },
+                Value::UintVector {
+                    is_fixed: b0,
+                    entries: b1,
+                },
+            ) => a0.cmp(b0).then_with(|| a1.cmp(b1)),
+            (
+                Value::DoubleVector {
+                    is_fixed: a0,
+                    entries: a1,
+                },
+                Value::DoubleVector {
+                    is_fixed: b0,
+                    entries: b1,
+                },
+            ) => a0
+                .cmp(b0)
+                .then_with(|| crate::cmp_slices(a1, b1, f64::total_cmp)),
+            (
+                Value::ObjectVector {
+                    class_name: a0,
+                    is_fixed: a1,
+                    entries: a2,
+                },
+                Value::ObjectVector {
+                    class_name: b0,
+                    is_fixed: b1,
+                    entries: b2,
+                },
+            ) => a0.cmp(b0).then(a1.cmp(b1)).then_with(|| cmp_values(a2, b2)),
+            (
+                Value::Dictionary {
+                    is_weak: a0,
+                    entries: a1,
+                },
+                Value::Dictionary {
+                    is_weak: b0,
+                    entries: b1,
+                },
+            ) => a0.cmp(b0).then_with(|| {
+                crate::cmp_slices(a1, b1, |a, b| {
+                    a.key
+                        .cmp_canonical(&b.key)
+                        .then_with(|| a.value.cmp_canonical(&b.value))
+                })
+            }),
+            _ => self.variant_index().cmp(&other.variant_index()),
+        }
+    }
+
+    /// Feeds this value into `state` in a way consistent with `cmp_canonical`.
+    ///
+    /// That is, values which are equal by `cmp_canonical` produce the same hash.
+    /// Floating point numbers are hashed by their bit patterns.
+    pub fn hash_canonical<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.variant_index());
+        match *self {
+            Value::Undefined | Value::Null => {}
+            Value::Boolean(x) => x.hash(state),
+            Value::Integer(x) => x.hash(state),
+            Value::Double(x) => x.to_bits().hash(state),
+            Value::String(ref x) | Value::XmlDocument(ref x) | Value::Xml(ref x) => x.hash(state),
+            Value::Date { unix_time } => unix_time.hash(state),
+            Value::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => {
+                hash_pairs(assoc_entries, state);
+                hash_values(dense_entries, state);
+            }
+            Value::Object {
+                ref class_name,
+                sealed_count,
+                ref entries,
+            } => {
+                class_name.hash(state);
+                sealed_count.hash(state);
+                hash_pairs(entries, state);
+            }
+            Value::ByteArray(ref x) => x.hash(state),
+            Value::IntVector {
+                is_fixed,
+                ref entries,
+            } => {
+                is_fixed.hash(state);
+                entries.hash(state);
+            }
+            Value::UintVector {
+                is_fixed,
+                ref entries,
+            } => {
+                is_fixed.hash(state);
+                entries.hash(state);
+            }
+            Value::DoubleVector {
+                is_fixed,
+                ref entries,
+            } => {
+                is_fixed.hash(state);
+                state.write_usize(entries.len());
+                for x in entries {
+                    x.to_bits().hash(state);
+                }
+            }
+            Value::ObjectVector {
+                ref class_name,
+                is_fixed,
+                ref entries,
+            } => {
+                class_name.hash(state);
+                is_fixed.hash(state);
+                hash_values(entries, state);
+            }
+            Value::Dictionary {
+                is_weak,
+                ref entries,
+            } => {
+                is_weak.hash(state);
+                state.write_usize(entries.len());
+                for p in entries {
+                    p.key.hash_canonical(state);
+                    p.value.hash_canonical(state);
+                }
+            }
+            Value::StreamedByteArray { offset, len } => {
+                offset.hash(state);
+                len.hash(state);
+            }
+        }
+    }
+
+    /// Returns `true` if this value is equal to `other` with a tolerance of `epsilon` for floating point numbers.
+    ///
+    /// Two `Double` values (and the entries of `DoubleVector`s) are treated as equal
+    /// if they are `==` or their difference is within `epsilon`.
+    /// The other values are compared structurally and their non-float leaves are compared by `==`.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        match (self, other) {
+            (Value::Double(a), Value::Double(b)) => crate::approx_eq_f64(*a, *b, epsilon),
+            (
+                Value::Array {
+                    assoc_entries: a0,
+                    dense_entries: a1,
+                },
+                Value::Array {
+                    assoc_entries: b0,
+                    dense_entries: b1,
+                },
+            ) => approx_eq_pairs(a0, b0, epsilon) && approx_eq_values(a1, b1, epsilon),
+            (
+                Value::Object {
+                    class_name: a0,
+                    sealed_count: a1,
+                    entries: a2,
+                },
+                Value::Object {
+                    class_name: b0,
+                    sealed_count: b1,
+                    entries: b2,
+                },
+            ) => a0 == b0 && a1 == b1 && approx_eq_pairs(a2, b2, epsilon),
+            (
+                Value::DoubleVector {
+                    is_fixed: a0,
+                    entries: a1,
+                },
+                Value::DoubleVector {
+                    is_fixed: b0,
+                    entries: b1,
+                },
+            ) => {
+                a0 == b0
+                    && crate::approx_eq_slices(a1, b1, |a, b| crate::approx_eq_f64(*a, *b, epsilon))
+            }
+            (
+                Value::ObjectVector {
+                    class_name: a0,
+                    is_fixed: a1,
+                    entries: a2,
+                },
+                Value::ObjectVector {
+                    class_name: b0,
+                    is_fixed: b1,
+                    entries: b2,
+                },
+            ) => a0 == b0 && a1 == b1 && approx_eq_values(a2, b2, epsilon),
+            (
+                Value::Dictionary {
+                    is_weak: a0,
+                    entries: a1,
+                },
+                Value::Dictionary {
+                    is_weak: b0,
+                    entries: b1,
+                },
+            ) => {
+                a0 == b0
+                    && crate::approx_eq_slices(a1, b1, |a, b| {
+                        a.key.approx_eq(&b.key, epsilon) && a.value.approx_eq(&b.value, epsilon)
+                    })
+            }
+            _ => self == other,
+        }
+    }
+
+    fn variant_index(&self) -> u8 {
+        match *self {
+            Value::Undefined => 0,
+            Value::Null => 1,
+            Value::Boolean(_) => 2,
+            Value::Integer(_) => 3,
+            Value::Double(_) => 4,
+            Value::String(_) => 5,
+            Value::XmlDocument(_) => 6,
+            Value::Date { .. } => 7,
+            Value::Array { .. } => 8,
+            Value::Object { .. } => 9,
+            Value::Xml(_) => 10,
+            Value::ByteArray(_) => 11,
+            Value::IntVector { .. } => 12,
+            Value::UintVector { .. } => 13,
+            Value::DoubleVector { .. } => 14,
+            Value::ObjectVector { .. } => 15,
+            Value::Dictionary { .. } => 16,
+            Value::StreamedByteArray { .. } => 17,
+        }
+    }
+}
+impl Default for Value {
+    /// Returns `Null`.
+    ///
+    /// This allows `core::mem::take` to move a value out of a decoded structure.
+    fn default() -> Self {
+        Value::Null
+    }
+}
+impl IntoIterator for Value {
+    type Item = Value;
+    type IntoIter = Box<dyn Iterator<Item = Value>>;
+
+    /// Converts the value into an iterator of the contained values.
+    ///
+    /// Unlike `try_into_values`, this yields nothing for a non-collection value.
+    fn into_iter(self) -> Self::IntoIter {
+        self.try_into_values()
+            .unwrap_or_else(|_| Box::new(core::iter::empty()))
+    }
+}
+impl FromIterator<(String, Value)> for Value {
+    /// Makes an anonymous `Object` value which has the collected entries.
+    ///
+    /// All of the entries are dynamic members (i.e., `sealed_count` is `0`).
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: iter.into_iter().map(Pair::from).collect(),
+        }
+    }
+}
+impl FromIterator<Value> for Value {
+    /// Makes an `Array` value which has the collected entries as its dense part.
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        Value::Array {
+            assoc_entries: Vec::new(),
+            dense_entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = ConversionError;
+
+    /// Converts an `Integer` or `Double` value into `f64`.
+    fn try_from(f: &Value) -> Result<Self, Self::Error> {
+        f.try_as_f64().ok_or(ConversionError {
+            expected: "Double",
+            found: f.type_name(),
+        })
+    }
+}
+impl TryFrom<&Value> for bool {
+    type Error = ConversionError;
+
+    /// Converts a `Boolean` value into `bool`.
+    fn try_from(f: &Value) -> Result<Self, Self::Error> {
+        match *f {
+            Value::Boolean(x) => Ok(x),
+            _ => Err(ConversionError {
+                expected: "Boolean",
+                found: f.type_name(),
+            }),
+        }
+    }
+}
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    /// Converts a `String`, `XmlDocument` or `Xml` value into `String`.
+    fn try_from(f: Value) -> Result<Self, Self::Error> {
+        match f {
+            Value::String(x) | Value::XmlDocument(x) | Value::Xml(x) => Ok(x),
+            _ => Err(ConversionError {
+                expected: "String",
+                found: f.type_name(),
+            }),
+        }
+    }
+}
+
+macro_rules! impl_from_vector {
+    ($t:ty, $ctor:ident) => {
+        impl From<Vec<$t>> for Value {
+            /// Makes a non-fixed-length vector value.
+            fn from(entries: Vec<$t>) -> Self {
+                Value::$ctor(entries, false)
+            }
+        }
+        impl<'a> From<&'a [$t]> for Value {
+            /// Makes a non-fixed-length vector value.
+            fn from(entries: &'a [$t]) -> Self {
+                Value::$ctor(entries.iter().copied(), false)
+            }
+        }
+    };
+}
+impl_from_vector!(i32, int_vector);
+impl_from_vector!(u32, uint_vector);
+impl_from_vector!(f64, double_vector);
+
+/// Returns `true` if `marker` is a known AMF3 marker.
+pub(crate) fn is_marker(marker: u8) -> bool {
+    marker <= marker::DICTIONARY
+}
+
+fn approx_eq_values(a: &[Value], b: &[Value], epsilon: f64) -> bool {
+    crate::approx_eq_slices(a, b, |a, b| a.approx_eq(b, epsilon))
+}
+
+fn approx_pairs_size(pairs: &[Pair<String, Value>]) -> usize {
+    pairs
+        .iter()
+        .map(|p| APPROX_NODE_SIZE + p.key.len() + p.value.approx_size())
+        .sum()
+}
+
+fn pairs_depth(pairs: &[Pair<String, Value>]) -> usize {
+    pairs.iter().map(|p| p.value.depth()).max().unwrap_or(0)
+}
+
+fn approx_eq_pairs(a: &[Pair<String, Value>], b: &[Pair<String, Value>], epsilon: f64) -> bool {
+    crate::approx_eq_slices(a, b, |a, b| {
+        a.key == b.key && a.value.approx_eq(&b.value, epsilon)
+    })
+}
+
+fn hash_values<H: Hasher>(values: &[Value], state: &mut H) {
+    state.write_usize(values.len());
+    for v in values {
+        v.hash_canonical(state);
+    }
+}
+
+fn hash_pairs<H: Hasher>(pairs: &[Pair<String, Value>], state: &mut H) {
+    state.write_usize(pairs.len());
+    for p in pairs {
+        p.key.hash(state);
+        p.value.hash_canonical(state);
+    }
+}
+
+fn cmp_values(a: &[Value], b: &[Value]) -> Ordering {
+    crate::cmp_slices(a, b, Value::cmp_canonical)
+}
+
+fn cmp_pairs(a: &[Pair<String, Value>], b: &[Pair<String, Value>]) -> Ordering {
+    crate::cmp_slices(a, b, |a, b| {
+        a.key
+            .cmp(&b.key)
+            .then_with(|| a.value.cmp_canonical(&b.value))
+    })
+}
+
+fn sort_pairs(pairs: &mut [Pair<String, Value>]) {
+    pairs.sort_by(|a, b| a.key.cmp(&b.key));
+    for p in pairs {
+        p.value.sort_keys();
+    }
+}
+
+fn merge_pairs(pairs: &mut Vec<Pair<String, Value>>, others: Vec<Pair<String, Value>>) {
+    for other in others {
+        match pairs.iter_mut().find(|p| p.key == other.key) {
+            Some(p) => p.value = other.value,
+            None => pairs.push(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+    use crate::error::{EncodeError, ValidationError};
+    use crate::Pair;
+    use std::f64;
+
+    #[test]
+    fn sorts_mixed_values() {
+        let mut values = [
+            Value::String("b".to_string()),
+            Value::Double(f64::NAN),
+            Value::Integer(2),
+            Value::Null,
+            Value::Double(-0.0),
+            Value::String("a".to_string()),
+            Value::Double(0.0),
+            Value::Double(-f64::NAN),
+            Value::Undefined,
+            Value::Integer(-1),
+            Value::Double(f64::INFINITY),
+        ];
+        values.sort_by(Value::cmp_canonical);
+
+        let expected = [
+            Value::Undefined,
+            Value::Null,
+            Value::Integer(-1),
+            Value::Integer(2),
+            Value::Double(-f64::NAN),
+            Value::Double(-0.0),
+            Value::Double(0.0),
+            Value::Double(f64::INFINITY),
+            Value::Double(f64::NAN),
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+        ];
+        assert_eq!(values.len(), expected.len());
+        for (a, b) in values.iter().zip(&expected) {
+            assert!(a.cmp_canonical(b).is_eq(), "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn splits_sealed_and_dynamic_entries() {
+        let pair = |key: &str| Pair {
+            key: key.to_string(),
+            value: Value::Null,
+        };
+        let object = Value::Object {
+            class_name: Some("org.amf.ASClass".to_string()),
+            sealed_count: 2,
+            entries: vec![pair("foo"), pair("bar"), pair("baz")],
+        };
+        assert_eq!(
+            object.sealed_entries(),
+            Some(&[pair("foo"), pair("bar")][..])
+        );
+        assert_eq!(object.dynamic_entries(), Some(&[pair("baz")][..]));
+        assert_eq!(object.is_dynamic(), Some(true));
+
+        let sealed = Value::Object {
+            class_name: Some("org.amf.ASClass".to_string()),
+            sealed_count: 1,
+            entries: vec![pair("foo")],
+        };
+        assert_eq!(sealed.sealed_entries(), Some(&[pair("foo")][..]));
+        assert_eq!(sealed.dynamic_entries(), Some(&[][..]));
+        assert_eq!(sealed.is_dynamic(), Some(false));
+
+        assert_eq!(Value::Null.sealed_entries(), None);
+        assert_eq!(Value::empty_array().is_dynamic(), None);
+    }
+
+    #[test]
+    fn looks_up_dictionary_entries() {
+        let input = include_bytes!("../testdata/amf3-dictionary.bin");
+        let dict = Value::read_from(&mut &input[..]).unwrap();
+        let typed_obj = Value::Object {
+            class_name: Some("org.amf.ASClass".to_string()),
+            sealed_count: 2,
+            entries: vec![
+                Pair {
+                    key: "foo".to_string(),
+                    value: Value::String("baz".to_string()),
+                },
+                Pair {
+                    key: "baz".to_string(),
+                    value: Value::Null,
+                },
+            ],
+        };
+
+        assert_eq!(
+            dict.dictionary_get(&Value::String("bar".to_string())),
+            Some(&Value::String("asdf1".to_string()))
+        );
+        assert_eq!(
+            dict.dictionary_get(&typed_obj),
+            Some(&Value::String("asdf2".to_string()))
+        );
+        assert_eq!(dict.dictionary_get(&Value::String("baz".to_string())), None);
+        assert_eq!(
+            dict.dictionary_keys().unwrap().collect::<Vec<_>>(),
+            [&Value::String("bar".to_string()), &typed_obj]
+        );
+        assert_eq!(
+            dict.dictionary_values().unwrap().collect::<Vec<_>>(),
+            [
+                &Value::String("asdf1".to_string()),
+                &Value::String("asdf2".to_string())
+            ]
+        );
+
+        assert_eq!(Value::Null.dictionary_get(&Value::Null), None);
+        assert!(Value::Null.dictionary_keys().is_none());
+        assert!(Value::Null.dictionary_values().is_none());
+    }
+
+    #[test]
+    fn converts_string_dictionary() {
+        let pair = |key, value| Pair { key, value };
+        let s = |s: &str| Value::String(s.to_string());
+
+        let dict = Value::Dictionary {
+            is_weak: false,
+            entries: vec![pair(s("a"), Value::Integer(1)), pair(s("b"), Value::Null)],
+        };
+        assert_eq!(
+            dict.try_into_string_dictionary(),
+            Ok(vec![
+                ("a".to_string(), Value::Integer(1)),
+                ("b".to_string(), Value::Null)
+            ])
+        );
+
+        let dict = Value::Dictionary {
+            is_weak: false,
+            entries: vec![
+                pair(s("a"), Value::Integer(1)),
+                pair(Value::Integer(2), Value::Null),
+            ],
+        };
+        assert_eq!(dict.clone().try_into_string_dictionary(), Err(dict));
+
+        assert_eq!(Value::Null.try_into_string_dictionary(), Err(Value::Null));
+    }
+
+    #[test]
+    fn converts_array_into_both_parts() {
+        let input = include_bytes!("../testdata/amf3-associative-array.bin");
+        let array = Value::read_from(&mut &input[..]).unwrap();
+        let s = |s: &str| Value::String(s.to_string());
+        let pair = |k: &str, v: &str| (k.to_string(), s(v));
+
+        assert_eq!(
+            array.clone().try_into_values().unwrap().collect::<Vec<_>>(),
+            [s("bar"), s("bar1"), s("bar2")]
+        );
+        assert_eq!(
+            array.clone().try_into_assoc().unwrap().collect::<Vec<_>>(),
+            [pair("2", "bar3"), pair("foo", "bar"), pair("asdf", "fdsa")]
+        );
+        assert_eq!(
+            array.try_into_all().unwrap().collect::<Vec<_>>(),
+            [
+                pair("0", "bar"),
+                pair("1", "bar1"),
+                pair("2", "bar2"),
+                pair("2", "bar3"),
+                pair("foo", "bar"),
+                pair("asdf", "fdsa")
+            ]
+        );
+
+        let object = Value::empty_object();
+        assert_eq!(object.clone().try_into_assoc().err(), Some(object.clone()));
+        assert_eq!(object.clone().try_into_all().err(), Some(object));
+    }
+
+    #[test]
+    fn converts_dictionary_into_pairs() {
+        let pair = |key, value| Pair { key, value };
+        let s = |s: &str| Value::String(s.to_string());
+
+        let dict = Value::Dictionary {
+            is_weak: false,
+            entries: vec![pair(s("a"), Value::Integer(1)), pair(s("b"), Value::Null)],
+        };
+        assert_eq!(
+            dict.try_into_pairs().unwrap().collect::<Vec<_>>(),
+            [
+                ("a".to_string(), Value::Integer(1)),
+                ("b".to_string(), Value::Null)
+            ]
+        );
+
+        let dict = Value::Dictionary {
+            is_weak: false,
+            entries: vec![
+                pair(s("a"), Value::Integer(1)),
+                pair(Value::Integer(-2), Value::Null),
+            ],
+        };
+        assert_eq!(
+            dict.try_into_pairs().unwrap().collect::<Vec<_>>(),
+            [
+                ("a".to_string(), Value::Integer(1)),
+                ("-2".to_string(), Value::Null)
+            ]
+        );
+
+        let dict = Value::Dictionary {
+            is_weak: false,
+            entries: vec![pair(Value::Double(1.5), Value::Null)],
+        };
+        assert_eq!(dict.clone().try_into_pairs().err(), Some(dict));
+    }
+
+    #[test]
+    fn len_works() {
+        let array = Value::Array {
+            assoc_entries: vec![Pair {
+                key: "a".to_string(),
+                value: Value::Null,
+            }],
+            dense_entries: vec![Value::Null, Value::Null],
+        };
+        assert_eq!(array.len(), Some(2));
+        assert_eq!(array.assoc_len(), Some(1));
+        assert_eq!(array.is_empty(), Some(false));
+        assert_eq!(Value::empty_array().is_empty(), Some(true));
+
+        let object = Value::anonymous_object(vec![("a", Value::Null)]);
+        assert_eq!(object.len(), Some(1));
+        assert_eq!(object.assoc_len(), None);
+        assert_eq!(Value::empty_object().is_empty(), Some(true));
+
+        let vectors = [
+            Value::IntVector {
+                is_fixed: false,
+                entries: vec![1, 2, 3],
+            },
+            Value::UintVector {
+                is_fixed: false,
+                entries: vec![1, 2, 3],
+            },
+            Value::DoubleVector {
+                is_fixed: false,
+                entries: vec![1.0, 2.0, 3.0],
+            },
+            Value::ObjectVector {
+                class_name: None,
+                is_fixed: false,
+                entries: vec![Value::Null, Value::Null, Value::Null],
+            },
+        ];
+        for v in &vectors {
+            assert_eq!(v.len(), Some(3));
+        }
+
+        let dict = Value::Dictionary {
+            is_weak: false,
+            entries: vec![],
+        };
+        assert_eq!(dict.len(), Some(0));
+        assert_eq!(dict.is_empty(), Some(true));
+
+        assert_eq!(Value::Null.len(), None);
+        assert_eq!(Value::String("foo".to_string()).len(), None);
+        assert_eq!(Value::ByteArray(vec![1]).is_empty(), None);
+    }
+
+    #[test]
+    fn iterates_over_decoded_array() {
+        let input = include_bytes!("../testdata/amf3-primitive-array.bin");
+        let array = Value::read_from(&mut &input[..]).unwrap();
+        let mut values = Vec::new();
+        for v in array {
+            values.push(v);
+        }
+        assert_eq!(values, (1..6).map(Value::Integer).collect::<Vec<_>>());
+
+        let vector = Value::UintVector {
+            is_fixed: false,
+            entries: vec![1, 0x0FFF_FFFF, 0x1000_0000, 0x8000_0000, u32::MAX],
+        };
+        let values = vector.into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                Value::Integer(1),
+                Value::Integer(0x0FFF_FFFF),
+                Value::Double(268435456.0),
+                Value::Double(2147483648.0),
+                Value::Double(4294967295.0),
+            ]
+        );
+        assert_eq!(
+            values
+                .iter()
+                .map(|v| v.try_as_f64().unwrap() as u32)
+                .collect::<Vec<_>>(),
+            [1, 0x0FFF_FFFF, 0x1000_0000, 0x8000_0000, u32::MAX]
+        );
+        assert_eq!(Value::Integer(1).into_iter().count(), 0);
+    }
+
+    #[test]
+    fn decodes_byte_array_as_amf3() {
+        let inner = Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: vec![Pair {
+                key: "foo".to_string(),
+                value: Value::String("foo".to_string()),
+            }],
+        };
+        let mut bytes = Vec::new();
+        inner.write_to(&mut bytes).unwrap();
+
+        // The outer value also uses the string "foo", which must not leak into the inner reference table.
+        let outer = Value::Array {
+            assoc_entries: vec![],
+            dense_entries: vec![Value::String("foo".to_string()), Value::ByteArray(bytes)],
+        };
+        let mut buf = Vec::new();
+        outer.write_to(&mut buf).unwrap();
+        let decoded = Value::read_from(&mut &buf[..]).unwrap();
+
+        let byte_array = match decoded {
+            Value::Array {
+                mut dense_entries, ..
+            } => dense_entries.pop().unwrap(),
+            _ => panic!(),
+        };
+        assert_eq!(byte_array.decode_byte_array_as_amf3(), Some(Ok(inner)));
+        assert_eq!(Value::Null.decode_byte_array_as_amf3(), None);
+        assert!(matches!(
+            Value::ByteArray(vec![0xFF]).decode_byte_array_as_amf3(),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn approx_eq_works() {
+        assert_ne!(Value::Double(0.1 + 0.2), Value::Double(0.3));
+        assert!(Value::Double(0.1 + 0.2).approx_eq(&Value::Double(0.3), 1e-9));
+        assert!(!Value::Double(0.1).approx_eq(&Value::Double(0.3), 1e-9));
+        assert!(Value::Double(f64::INFINITY).approx_eq(&Value::Double(f64::INFINITY), 1e-9));
+        assert!(!Value::Double(f64::NAN).approx_eq(&Value::Double(f64::NAN), 1e-9));
+        assert!(!Value::Integer(1).approx_eq(&Value::Double(1.0), 1e-9));
+
+        let array = |n: f64| Value::Array {
+            assoc_entries: vec![Pair {
+                key: "x".to_string(),
+                value: Value::Double(n),
+            }],
+            dense_entries: vec![Value::DoubleVector {
+                is_fixed: false,
+                entries: vec![n, 1.0],
+            }],
+        };
+        assert!(array(0.1 + 0.2).approx_eq(&array(0.3), 1e-9));
+        assert!(!array(0.1).approx_eq(&array(0.3), 1e-9));
+
+        let dict = |n: f64| Value::Dictionary {
+            is_weak: false,
+            entries: vec![Pair {
+                key: Value::Double(n),
+                value: Value::String("foo".to_string()),
+            }],
+        };
+        assert!(dict(0.1 + 0.2).approx_eq(&dict(0.3), 1e-9));
+        assert!(!dict(0.1).approx_eq(&dict(0.3), 1e-9));
+    }
+
+    #[test]
+    fn compares_nested_values() {
+        let obj = |v: f64| Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: vec![Pair {
+                key: "x".to_string(),
+                value: Value::Double(v),
+            }],
+        };
+        assert!(obj(f64::NAN).cmp_canonical(&obj(f64::NAN)).is_eq());
+        assert!(obj(1.0).cmp_canonical(&obj(f64::NAN)).is_lt());
+
+        let dict = |k: i32| Value::Dictionary {
+            is_weak: false,
+            entries: vec![Pair {
+                key: Value::Integer(k),
+                value: Value::Null,
+            }],
+        };
+        assert!(dict(1).cmp_canonical(&dict(2)).is_lt());
+        assert!(dict(2).cmp_canonical(&dict(1)).is_gt());
+    }
+
+    #[test]
+    fn validates_sealed_count() {
+        let valid = Value::Object {
+            class_name: None,
+            sealed_count: 1,
+            entries: vec![Pair {
+                key: "foo".to_string(),
+                value: Value::Null,
+            }],
+        };
+        assert_eq!(valid.validate(), Ok(()));
+        assert!(valid.write_to(&mut Vec::new()).is_ok());
+
+        let invalid = Value::Array {
+            assoc_entries: vec![],
+            dense_entries: vec![Value::Object {
+                class_name: None,
+                sealed_count: 2,
+                entries: vec![],
+            }],
+        };
+        let expected = ValidationError::SealedCountOutOfRange {
+            sealed_count: 2,
+            entries: 0,
+        };
+        assert_eq!(invalid.validate(), Err(expected.clone()));
+        assert_eq!(
+            invalid.write_to(&mut Vec::new()),
+            Err(EncodeError::Invalid(expected))
+        );
+    }
+
+    #[test]
+    fn validates_object_vector_entries() {
+        let object = |class_name: &str| Value::Object {
+            class_name: Some(class_name.to_string()),
+            sealed_count: 0,
+            entries: vec![],
+        };
+        let valid = Value::ObjectVector {
+            class_name: None,
+            is_fixed: false,
+            entries: vec![object("foo"), Value::Null, object("bar")],
+        };
+        assert_eq!(valid.validate(), Ok(()));
+        assert!(valid.write_to(&mut Vec::new()).is_ok());
+
+        let invalid = Value::ObjectVector {
+            class_name: Some("foo".to_string()),
+            is_fixed: false,
+            entries: vec![object("foo"), Value::Integer(1)],
+        };
+        let expected = ValidationError::NonObjectVectorElement {
+            index: 1,
+            type_name: "Integer",
+        };
+        assert_eq!(invalid.validate(), Err(expected.clone()));
+        assert_eq!(
+            invalid.write_to(&mut Vec::new()),
+            Err(EncodeError::Invalid(expected))
+        );
+    }
+
+    #[test]
+    fn validates_dictionary_keys() {
+        let dictionary = |key: f64| Value::Dictionary {
+            is_weak: false,
+            entries: vec![
+                Pair {
+                    key: Value::Integer(1),
+                    value: Value::Null,
+                },
+                Pair {
+                    key: Value::Double(key),
+                    value: Value::Null,
+                },
+            ],
+        };
+        assert_eq!(dictionary(f64::INFINITY).validate(), Ok(()));
+
+        let invalid = dictionary(f64::NAN);
+        let expected = ValidationError::NanDictionaryKey { index: 1 };
+        assert_eq!(invalid.validate(), Err(expected.clone()));
+        assert_eq!(
+            invalid.write_to(&mut Vec::new()),
+            Err(EncodeError::Invalid(expected))
+        );
+    }
+
+    #[test]
+    fn rejects_streamed_placeholders() {
+        let placeholder = Value::StreamedByteArray { offset: 0, len: 3 };
+        let expected = ValidationError::StreamedPlaceholder {
+            type_name: "StreamedByteArray",
+        };
+        assert_eq!(placeholder.validate(), Err(expected.clone()));
+        assert_eq!(
+            placeholder.write_to(&mut Vec::new()),
+            Err(EncodeError::Invalid(expected))
+        );
+    }
+
+    #[test]
+    fn promotes_out_of_range_integers() {
+        assert_eq!(Value::integer_or_double(0), Value::Integer(0));
+        assert_eq!(
+            Value::integer_or_double(0x0FFF_FFFF),
+            Value::Integer(0x0FFF_FFFF)
+        );
+        assert_eq!(
+            Value::integer_or_double(0x1000_0000),
+            Value::Double(268435456.0)
+        );
+        assert_eq!(
+            Value::integer_or_double(-0x1000_0000),
+            Value::Integer(-0x1000_0000)
+        );
+        assert_eq!(
+            Value::integer_or_double(-0x1000_0001),
+            Value::Double(-268435457.0)
+        );
+        assert_eq!(
+            Value::integer_or_double(i64::MAX),
+            Value::Double(i64::MAX as f64)
+        );
+    }
+
+    #[test]
+    fn returns_type_names() {
+        let values = [
+            (Value::Undefined, "Undefined"),
+            (Value::Null, "Null"),
+            (Value::Boolean(true), "Boolean"),
+            (Value::Integer(1), "Integer"),
+            (Value::Double(1.0), "Double"),
+            (Value::String("a".to_string()), "String"),
+            (Value::XmlDocument("<a/>".to_string()), "XmlDocument"),
+            (
+                Value::Date {
+                    unix_time: std::time::Duration::from_secs(0),
+                },
+                "Date",
+            ),
+            (Value::empty_array(), "Array"),
+            (Value::empty_object(), "Object"),
+            (Value::Xml("<a/>".to_string()), "Xml"),
+            (Value::ByteArray(vec![]), "ByteArray"),
+            (
+                Value::IntVector {
+                    is_fixed: false,
+                    entries: vec![],
+                },
+                "IntVector",
+            ),
+            (
+                Value::UintVector {
+                    is_fixed: false,
+                    entries: vec![],
+                },
+                "UintVector",
+            ),
+            (
+                Value::DoubleVector {
+                    is_fixed: false,
+                    entries: vec![],
+                },
+                "DoubleVector",
+            ),
+            (
+                Value::ObjectVector {
+                    class_name: None,
+                    is_fixed: false,
+                    entries: vec![],
+                },
+                "ObjectVector",
+            ),
+            (
+                Value::Dictionary {
+                    is_weak: false,
+                    entries: vec![],
+                },
+                "Dictionary",
+            ),
+        ];
+        for (value, name) in &values {
+            assert_eq!(value.type_name(), *name);
+        }
+    }
+
+    #[test]
+    fn distinguishes_reference_types() {
+        let references = [
+            Value::XmlDocument("<a/>".to_string()),
+            Value::Date {
+                unix_time: std::time::Duration::from_secs(0),
+            },
+            Value::empty_array(),
+            Value::empty_object(),
+            Value::Xml("<a/>".to_string()),
+            Value::ByteArray(vec![]),
+            Value::IntVector {
+                is_fixed: false,
+                entries: vec![],
+            },
+            Value::UintVector {
+                is_fixed: false,
+                entries: vec![],
+            },
+            Value::DoubleVector {
+                is_fixed: false,
+                entries: vec![],
+            },
+            Value::ObjectVector {
+                class_name: None,
+                is_fixed: false,
+                entries: vec![],
+            },
+            Value::Dictionary {
+                is_weak: false,
+                entries: vec![],
+            },
+        ];
+        for value in &references {
+            assert!(value.is_reference_type(), "{:?}", value);
+        }
+
+        let others = [
+            Value::Undefined,
+            Value::Null,
+            Value::Boolean(true),
+            Value::Integer(1),
+            Value::Double(1.0),
+            Value::String("a".to_string()),
+        ];
+        for value in &others {
+            assert!(!value.is_reference_type(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn sorts_only_dynamic_members() {
+        let pair = |key: &str, value| Pair {
+            key: key.to_string(),
+            value,
+        };
+        let value = Value::Object {
+            class_name: Some("Foo".to_string()),
+            sealed_count: 2,
+            entries: vec![
+                pair(
+                    "z",
+                    Value::anonymous_object(vec![("d", Value::Null), ("c", Value::Null)]),
+                ),
+                pair("y", Value::Integer(1)),
+                pair("b", Value::Integer(2)),
+                pair("a", Value::Integer(3)),
+            ],
+        };
+        assert_eq!(
+            value.sorted(),
+            Value::Object {
+                class_name: Some("Foo".to_string()),
+                sealed_count: 2,
+                entries: vec![
+                    pair(
+                        "z",
+                        Value::anonymous_object(vec![("c", Value::Null), ("d", Value::Null)])
+                    ),
+                    pair("y", Value::Integer(1)),
+                    pair("a", Value::Integer(3)),
+                    pair("b", Value::Integer(2)),
+                ],
+            }
+        );
+
+        let mut array = Value::Array {
+            assoc_entries: vec![pair("b", Value::Null), pair("a", Value::Null)],
+            dense_entries: vec![Value::Integer(2), Value::Integer(1)],
+        };
+        array.sort_keys();
+        assert_eq!(
+            array,
+            Value::Array {
+                assoc_entries: vec![pair("a", Value::Null), pair("b", Value::Null)],
+                dense_entries: vec![Value::Integer(2), Value::Integer(1)],
+            }
+        );
+    }
+
+    #[test]
+    fn retains_keys() {
+        let pair = |key: &str| Pair {
+            key: key.to_string(),
+            value: Value::Null,
+        };
+        let object = Value::Object {
+            class_name: Some("Foo".to_string()),
+            sealed_count: 2,
+            entries: vec![pair("a"), pair("b"), pair("c"), pair("d")],
+        };
+
+        let mut sealed_removed = object.clone();
+        sealed_removed.retain_keys(|k| k != "a");
+        assert_eq!(
+            sealed_removed,
+            Value::Object {
+                class_name: Some("Foo".to_string()),
+                sealed_count: 1,
+                entries: vec![pair("b"), pair("c"), pair("d")],
+            }
+        );
+        assert_eq!(sealed_removed.validate(), Ok(()));
+
+        let mut dynamic_removed = object.clone();
+        dynamic_removed.retain_keys(|k| k != "d");
+        assert_eq!(
+            dynamic_removed,
+            Value::Object {
+                class_name: Some("Foo".to_string()),
+                sealed_count: 2,
+                entries: vec![pair("a"), pair("b"), pair("c")],
+            }
+        );
+
+        let mut all_removed = object;
+        all_removed.retain_keys(|_| false);
+        assert_eq!(
+            all_removed,
+            Value::Object {
+                class_name: Some("Foo".to_string()),
+                sealed_count: 0,
+                entries: vec![],
+            }
+        );
+
+        let mut array = Value::Array {
+            assoc_entries: vec![pair("a"), pair("b")],
+            dense_entries: vec![Value::Null],
+        };
+        array.retain_keys(|k| k == "b");
+        assert_eq!(
+            array,
+            Value::Array {
+                assoc_entries: vec![pair("b")],
+                dense_entries: vec![Value::Null],
+            }
+        );
+    }
+
+    #[test]
+    fn merges_objects() {
+        let pair = |key: &str, n| Pair {
+            key: key.to_string(),
+            value: Value::Integer(n),
+        };
+
+        let mut value = Value::Object {
+            class_name: Some("Foo".to_string()),
+            sealed_count: 1,
+            entries: vec![pair("a", 1), pair("b", 2)],
+        };
+        value
+            .merge(Value::anonymous_object(vec![
+                ("c", Value::Integer(3)),
+                ("a", Value::Integer(4)),
+            ]))
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::Object {
+                class_name: Some("Foo".to_string()),
+                sealed_count: 1,
+                entries: vec![pair("a", 4), pair("b", 2), pair("c", 3)],
+            }
+        );
+
+        let mut disjoint = Value::anonymous_object(vec![("x", Value::Null)]);
+        disjoint
+            .merge(Value::anonymous_object(vec![("y", Value::Null)]))
+            .unwrap();
+        assert_eq!(
+            disjoint,
+            Value::anonymous_object(vec![("x", Value::Null), ("y", Value::Null)])
+        );
+
+        assert_eq!(
+            disjoint.merge(Value::empty_array()),
+            Err(Value::empty_array())
+        );
+    }
+
+    #[test]
+    fn round_trips_vectors_made_by_constructors() {
+        let values = [
+            Value::int_vector(vec![0, -1, i32::MAX, i32::MIN], true),
+            Value::uint_vector(vec![0, 1, u32::MAX], false),
+            Value::double_vector(vec![0.5, -1.0, f64::INFINITY], true),
+            Value::object_vector(
+                Some("org.amf.ASClass".to_string()),
+                vec![Value::empty_object(), Value::Null],
+                false,
+            ),
+            Value::object_vector(None, vec![], true),
+            Value::from(vec![1i32, 2, 3]),
+            Value::from(&[4u32, 5][..]),
+            Value::from(vec![1.5f64]),
+        ];
+        for value in &values {
+            let mut buf = Vec::new();
+            value.write_to(&mut buf).unwrap();
+            assert_eq!(&Value::read_from(&mut &buf[..]).unwrap(), value);
+        }
+
+        assert_eq!(
+            Value::from(&[1i32, 2][..]),
+            Value::IntVector {
+                is_fixed: false,
+                entries: vec![1, 2],
+            }
+        );
+        assert_eq!(
+            Value::from(vec![3u32]),
+            Value::UintVector {
+                is_fixed: false,
+                entries: vec![3],
+            }
+        );
+        assert_eq!(
+            Value::from(&[0.25f64][..]),
+            Value::DoubleVector {
+                is_fixed: false,
+                entries: vec![0.25],
+            }
+        );
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn converts_byte_array_from_and_to_base64() {
+        use crate::error::DecodeError;
+
+        let input = include_bytes!("../testdata/amf3-byte-array.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        let text = value.byte_array_base64().unwrap();
+        assert_eq!(text, "AAPjgZPjgox0ZXN0QA==");
+        assert_eq!(Value::byte_array_from_base64(&text), Ok(value));
+
+        assert_eq!(
+            Value::byte_array_from_base64(""),
+            Ok(Value::ByteArray(vec![]))
+        );
+        assert!(matches!(
+            Value::byte_array_from_base64("AA=?"),
+            Err(DecodeError::Base64(_))
+        ));
+        assert_eq!(Value::Null.byte_array_base64(), None);
+    }
+
+    #[test]
+    fn collects_objects_and_arrays() {
+        let object: Value = vec![
+            ("a".to_string(), Value::Integer(1)),
+            ("b".to_string(), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            object,
+            Value::anonymous_object(vec![("a", Value::Integer(1)), ("b", Value::Null)])
+        );
+
+        let array: Value = vec![Value::Integer(1), Value::Null].into_iter().collect();
+        assert_eq!(
+            array,
+            Value::Array {
+                assoc_entries: vec![],
+                dense_entries: vec![Value::Integer(1), Value::Null],
+            }
+        );
+
+        for value in [object, array] {
+            let mut buf = Vec::new();
+            value.write_to(&mut buf).unwrap();
+            assert_eq!(Value::read_from(&mut &buf[..]).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn walks_values_in_pre_order() {
+        let s = |s: &str| Value::String(s.to_string());
+        let mut value = Value::Dictionary {
+            is_weak: false,
+            entries: vec![Pair {
+                key: s("key"),
+                value: Value::Array {
+                    assoc_entries: vec![Pair {
+                        key: "password".to_string(),
+                        value: s("secret"),
+                    }],
+                    dense_entries: vec![Value::object_vector(
+                        None,
+                        vec![Value::anonymous_object(vec![
+                            ("password", s("secret")),
+                            ("id", Value::Integer(1)),
+                        ])],
+                        false,
+                    )],
+                },
+            }],
+        };
+
+        let mut visited = Vec::new();
+        value.walk(|v| visited.push(v.type_name()));
+        assert_eq!(
+            visited,
+            [
+                "Dictionary",
+                "String",
+                "Array",
+                "String",
+                "ObjectVector",
+                "Object",
+                "String",
+                "Integer"
+            ]
+        );
+
+        value.walk_mut(|v| match v {
+            Value::Object { entries, .. }
+            | Value::Array {
+                assoc_entries: entries,
+                ..
+            } => {
+                for p in entries.iter_mut().filter(|p| p.key == "password") {
+                    p.value = Value::Null;
+                }
+            }
+            _ => {}
+        });
+        let mut strings = Vec::new();
+        value.walk(|v| strings.extend(v.try_as_str().map(|s| s.to_string())));
+        assert_eq!(strings, ["key"]);
+    }
+
+    #[test]
+    fn converts_values_into_rust_types() {
+        use crate::error::ConversionError;
+
+        assert_eq!(f64::try_from(&Value::Integer(2)), Ok(2.0));
+        assert_eq!(f64::try_from(&Value::Double(1.5)), Ok(1.5));
+        assert_eq!(bool::try_from(&Value::Boolean(false)), Ok(false));
+        assert_eq!(
+            String::try_from(Value::Xml("<a/>".to_string())),
+            Ok("<a/>".to_string())
+        );
+
+        assert_eq!(
+            f64::try_from(&Value::Undefined),
+            Err(ConversionError {
+                expected: "Double",
+                found: "Undefined"
+            })
+        );
+        assert_eq!(
+            bool::try_from(&Value::Integer(1)),
+            Err(ConversionError {
+                expected: "Boolean",
+                found: "Integer"
+            })
+        );
+        assert_eq!(
+            String::try_from(Value::ByteArray(vec![])),
+            Err(ConversionError {
+                expected: "String",
+                found: "ByteArray"
+            })
+        );
+    }
+
+    #[test]
+    fn converts_dates_from_and_to_millis() {
+        use crate::error::DecodeError;
+
+        let epoch = Value::date_from_millis(0).unwrap();
+        assert_eq!(
+            epoch,
+            Value::Date {
+                unix_time: std::time::Duration::from_secs(0)
+            }
+        );
+        assert_eq!(epoch.date_millis(), Some(0));
+
+        let date = Value::date_from_millis(1_590_796_800_000).unwrap();
+        let mut buf = Vec::new();
+        date.write_to(&mut buf).unwrap();
+        let decoded = Value::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(decoded.date_millis(), Some(1_590_796_800_000));
+
+        assert_eq!(
+            Value::date_from_millis(-1),
+            Err(DecodeError::InvalidDate { millis: -1.0 })
+        );
+        assert_eq!(Value::Null.date_millis(), None);
+    }
+
+    #[test]
+    fn makes_dates_from_unix_timestamps() {
+        let input = include_bytes!("../testdata/amf3-date.bin");
+        let epoch = Value::read_from(&mut &input[..]).unwrap();
+        assert_eq!(Value::date_from_unix_secs(0), epoch);
+        assert_eq!(Value::date_from_unix_millis(0), epoch);
+
+        assert_eq!(
+            Value::date_from_unix_secs(1_590_796_800),
+            Value::date_from_unix_millis(1_590_796_800_000)
+        );
+        assert_eq!(
+            Value::date_from_unix_millis(1_590_796_800_123).date_millis(),
+            Some(1_590_796_800_123)
+        );
+    }
+
+    #[test]
+    fn makes_xml_values() {
+        let xml = Value::xml("<a/>");
+        assert_eq!(xml, Value::Xml("<a/>".to_string()));
+        let mut buf = Vec::new();
+        xml.write_to(&mut buf).unwrap();
+        assert_eq!(buf[0], 0x0B);
+
+        let doc = Value::xml_document(String::from("<b/>"));
+        assert_eq!(doc, Value::XmlDocument("<b/>".to_string()));
+        let mut buf = Vec::new();
+        doc.write_to(&mut buf).unwrap();
+        assert_eq!(buf[0], 0x07);
+
+        assert!(xml.is_xml());
+        assert!(doc.is_xml());
+        assert_eq!(xml.as_xml_str(), Some("<a/>"));
+        assert_eq!(doc.as_xml_str(), Some("<b/>"));
+
+        let string = Value::String("<c/>".to_string());
+        assert!(!string.is_xml());
+        assert_eq!(string.as_xml_str(), None);
+        assert_eq!(string.try_as_str(), Some("<c/>"));
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn views_objects_as_maps() {
+        let input = include_bytes!("../testdata/amf3-dynamic-object.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        let map = value.object_map().unwrap();
+        let entries = match value {
+            Value::Object { ref entries, .. } => entries,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            entries
+                .iter()
+                .map(|p| (p.key.as_str(), &p.value))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Value::Null.object_map(), None);
+    }
+
+    #[test]
+    fn measures_depth() {
+        assert_eq!(Value::Integer(1).depth(), 1);
+        assert_eq!(Value::int_vector(Vec::new(), false).depth(), 1);
+        assert_eq!(Value::double_vector([1.0], false).depth(), 2);
+
+        let array = Value::Array {
+            assoc_entries: Vec::new(),
+            dense_entries: vec![Value::Integer(1)],
+        };
+        let nested = Value::Dictionary {
+            is_weak: false,
+            entries: vec![Pair {
+                key: Value::String("a".to_string()),
+                value: Value::object_vector(None, [array.clone()], false),
+            }],
+        };
+        assert_eq!(nested.depth(), 4);
+
+        let keyed = Value::Dictionary {
+            is_weak: false,
+            entries: vec![Pair {
+                key: array,
+                value: Value::Null,
+            }],
+        };
+        assert_eq!(keyed.depth(), 3);
+    }
+
+    #[test]
+    fn counts_nodes() {
+        assert_eq!(Value::Null.count_nodes(), 1);
+
+        // A dictionary which has a string key and a typed object key (with two members).
+        let input = include_bytes!("../testdata/amf3-dictionary.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        assert_eq!(value.count_nodes(), 7);
+
+        let input = include_bytes!("../testdata/amf3-vector-int.bin");
+        let value = Value::read_from(&mut &input[..]).unwrap();
+        assert_eq!(value.count_nodes(), 4);
+    }
+
+    #[test]
+    fn converts_to_amf0() {
+        use crate::amf0;
+
+        let array = Value::Array {
+            assoc_entries: vec![Pair {
+                key: "a".to_string(),
+                value: Value::Integer(1),
+            }],
+            dense_entries: vec![Value::Xml("<a/>".to_string())],
+        };
+        assert_eq!(
+            array.to_amf0(),
+            amf0::Value::EcmaArray {
+                entries: vec![
+                    Pair {
+                        key: "0".to_string(),
+                        value: amf0::Value::XmlDocument("<a/>".to_string()),
+                    },
+                    Pair {
+                        key: "a".to_string(),
+                        value: amf0::number(1),
+                    },
+                ],
+                declared_count: None,
+            }
+        );
+
+        let vector = Value::int_vector([1], false);
+        assert_eq!(
+            Value::object_vector(None, [vector.clone()], false).to_amf0(),
+            amf0::Value::AvmPlus(Value::object_vector(None, [vector], false))
+        );
+    }
 }
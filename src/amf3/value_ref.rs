@@ -0,0 +1,683 @@
+use super::marker;
+use super::Value;
+use crate::error::{DecodeError, StringContext};
+use crate::io;
+use crate::{DecodeResult, Pair, MAX_DECODE_DEPTH, MAX_PREALLOCATED_ENTRIES};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::cmp;
+use core::str;
+use core::time;
+
+/// AMF3 value which borrows its strings and byte arrays from the decoded buffer.
+///
+/// This is the borrowed counterpart of [`Value`](super::Value).
+///
+/// # Examples
+/// ```
+/// use amf::amf3::{Value, ValueRef};
+///
+/// let mut buf = Vec::new();
+/// Value::String("foo".to_owned()).write_to(&mut buf).unwrap();
+///
+/// let decoded = ValueRef::read_from_slice(&buf).unwrap();
+/// assert_eq!(decoded, ValueRef::String("foo"));
+/// assert_eq!(decoded.to_owned(), Value::String("foo".to_owned()));
+/// ```
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum ValueRef<'a> {
+    /// See [`Value::Undefined`](super::Value::Undefined).
+    Undefined,
+
+    /// See [`Value::Null`](super::Value::Null).
+    Null,
+
+    /// See [`Value::Boolean`](super::Value::Boolean).
+    Boolean(bool),
+
+    /// See [`Value::Integer`](super::Value::Integer).
+    Integer(i32),
+
+    /// See [`Value::Double`](super::Value::Double).
+    Double(f64),
+
+    /// See [`Value::String`](super::Value::String).
+    String(&'a str),
+
+    /// See [`Value::XmlDocument`](super::Value::XmlDocument).
+    XmlDocument(&'a str),
+
+    /// See [`Value::Date`](super::Value::Date).
+    Date {
+        /// Unix timestamp with milliseconds precision.
+        unix_time: time::Duration,
+    },
+
+    /// See [`Value::Array`](super::Value::Array).
+    Array {
+        /// Entries of the associative part of the array.
+        assoc_entries: Vec<Pair<&'a str, ValueRef<'a>>>,
+
+        /// Entries of the dense part of the array.
+        dense_entries: Vec<ValueRef<'a>>,
+    },
+
+    /// See [`Value::Object`](super::Value::Object).
+    Object {
+        /// The class name of the object.
+        /// `None` means it is an anonymous object.
+        class_name: Option<&'a str>,
+
+        /// Sealed member count of the object.
+        ///
+        /// Sealed members are located in front of the `entries`.
+        sealed_count: usize,
+
+        /// Members of the object.
+        entries: Vec<Pair<&'a str, ValueRef<'a>>>,
+    },
+
+    /// See [`Value::Xml`](super::Value::Xml).
+    Xml(&'a str),
+
+    /// See [`Value::ByteArray`](super::Value::ByteArray).
+    ByteArray(&'a [u8]),
+
+    /// See [`Value::IntVector`](super::Value::IntVector).
+    IntVector {
+        /// If `true`, this is a fixed-length vector.
+        is_fixed: bool,
+
+        /// The entries of the vector.
+        entries: Vec<i32>,
+    },
+
+    /// See [`Value::UintVector`](super::Value::UintVector).
+    UintVector {
+        /// If `true`, this is a fixed-length vector.
+        is_fixed: bool,
+
+        /// The entries of the vector.
+        entries: Vec<u32>,
+    },
+
+    /// See [`Value::DoubleVector`](super::Value::DoubleVector).
+    DoubleVector {
+        /// If `true`, this is a fixed-length vector.
+        is_fixed: bool,
+
+        /// The entries of the vector.
+        entries: Vec<f64>,
+    },
+
+    /// See [`Value::ObjectVector`](super::Value::ObjectVector).
+    ObjectVector {
+        /// The base type name of entries in the vector.
+        /// `None` means it is the ANY type.
+        class_name: Option<&'a str>,
+
+        /// If `true`, this is a fixed-length vector.
+        is_fixed: bool,
+
+        /// The entries of the vector.
+        entries: Vec<ValueRef<'a>>,
+    },
+
+    /// See [`Value::Dictionary`](super::Value::Dictionary).
+    Dictionary {
+        /// If `true`, the keys of `entries` are weakly referenced.
+        is_weak: bool,
+
+        /// The entries of the dictionary.
+        entries: Vec<Pair<ValueRef<'a>, ValueRef<'a>>>,
+    },
+}
+impl<'a> ValueRef<'a> {
+    /// Reads an AMF3 encoded `ValueRef` from `input` without copying strings and byte arrays.
+    ///
+    /// Reference objects are copied in the same way as [`Value::read_from`](super::Value::read_from).
+    pub fn read_from_slice(input: &'a [u8]) -> DecodeResult<Self> {
+        SliceDecoder::new_at(input, 0, 0).decode()
+    }
+
+    /// Tries to convert the value as a `str` reference.
+    pub fn try_as_str(&self) -> Option<&'a str> {
+        match *self {
+            ValueRef::String(x) => Some(x),
+            ValueRef::XmlDocument(x) => Some(x),
+            ValueRef::Xml(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Converts this value into the equivalent owned [`Value`](super::Value).
+    pub fn to_owned(&self) -> Value {
+        match *self {
+            ValueRef::Undefined => Value::Undefined,
+            ValueRef::Null => Value::Null,
+            ValueRef::Boolean(x) => Value::Boolean(x),
+            ValueRef::Integer(x) => Value::Integer(x),
+            ValueRef::Double(x) => Value::Double(x),
+            ValueRef::String(x) => Value::String(x.to_owned()),
+            ValueRef::XmlDocument(x) => Value::XmlDocument(x.to_owned()),
+            ValueRef::Date { unix_time } => Value::Date { unix_time },
+            ValueRef::Array {
+                ref assoc_entries,
+                ref dense_entries,
+            } => Value::Array {
+                assoc_entries: pairs_to_owned(assoc_entries),
+                dense_entries: dense_entries.iter().map(ValueRef::to_owned).collect(),
+            },
+            ValueRef::Object {
+                class_name,
+                sealed_count,
+                ref entries,
+            } => Value::Object {
+                class_name: class_name.map(ToOwned::to_owned),
+                sealed_count,
+                entries: pairs_to_owned(entries),
+            },
+            ValueRef::Xml(x) => Value::Xml(x.to_owned()),
+            ValueRef::ByteArray(x) => Value::ByteArray(x.to_owned()),
+            ValueRef::IntVector {
+                is_fixed,
+                ref entries,
+            } => Value::IntVector {
+                is_fixed,
+                entries: entries.clone(),
+            },
+            ValueRef::UintVector {
+                is_fixed,
+                ref entries,
+            } => Value::UintVector {
+                is_fixed,
+                entries: entries.clone(),
+            },
+            ValueRef::DoubleVector {
+                is_fixed,
+                ref entries,
+            } => Value::DoubleVector {
+                is_fixed,
+                entries: entries.clone(),
+            },
+            ValueRef::ObjectVector {
+                class_name,
+                is_fixed,
+                ref entries,
+            } => Value::ObjectVector {
+                class_name: class_name.map(ToOwned::to_owned),
+                is_fixed,
+                entries: entries.iter().map(ValueRef::to_owned).collect(),
+            },
+            ValueRef::Dictionary {
+                is_weak,
+                ref entries,
+            } => Value::Dictionary {
+                is_weak,
+                entries: entries
+                    .iter()
+                    .map(|p| Pair {
+                        key: p.key.to_owned(),
+                        value: p.value.to_owned(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+fn pairs_to_owned(pairs: &[Pair<&str, ValueRef>]) -> Vec<Pair<String, Value>> {
+    pairs
+        .iter()
+        .map(|p| Pair {
+            key: p.key.to_owned(),
+            value: p.value.to_owned(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct Trait<'a> {
+    class_name: Option<&'a str>,
+    is_dynamic: bool,
+    fields: Vec<&'a str>,
+}
+
+#[derive(Debug)]
+enum SizeOrIndex {
+    Size(usize),
+    Index(usize),
+}
+
+/// AMF3 decoder which reads values directly from a byte slice.
+#[derive(Debug)]
+pub(crate) struct SliceDecoder<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize,
+    traits: Vec<Trait<'a>>,
+    strings: Vec<&'a str>,
+    complexes: Vec<ValueRef<'a>>,
+}
+impl<'a> SliceDecoder<'a> {
+    /// Makes a new instance which starts reading `input` from `position`
+    /// and counting the nesting level from `depth`.
+    pub(crate) fn new_at(input: &'a [u8], position: usize, depth: usize) -> Self {
+        SliceDecoder {
+            input,
+            position,
+            depth,
+            traits: Vec::new(),
+            strings: Vec::new(),
+            complexes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(crate) fn decode(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_value()
+    }
+
+    fn decode_value(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let marker = self.read_u8()?;
+        let offset = self.position - 1;
+        match marker {
+            marker::UNDEFINED => Ok(ValueRef::Undefined),
+            marker::NULL => Ok(ValueRef::Null),
+            marker::FALSE => Ok(ValueRef::Boolean(false)),
+            marker::TRUE => Ok(ValueRef::Boolean(true)),
+            marker::INTEGER => self.decode_integer(),
+            marker::DOUBLE => self.decode_double(),
+            marker::STRING => self.decode_string(),
+            marker::XML_DOC => self.decode_xml_doc(),
+            marker::DATE => self.decode_date(),
+            marker::ARRAY => self.decode_array(),
+            marker::OBJECT => self.decode_object(),
+            marker::XML => self.decode_xml(),
+            marker::BYTE_ARRAY => self.decode_byte_array(),
+            marker::VECTOR_INT => self.decode_vector_int(),
+            marker::VECTOR_UINT => self.decode_vector_uint(),
+            marker::VECTOR_DOUBLE => self.decode_vector_double(),
+            marker::VECTOR_OBJECT => self.decode_vector_object(),
+            marker::DICTIONARY => self.decode_dictionary(),
+            _ => Err(DecodeError::Unknown { marker, offset }),
+        }
+    }
+
+    fn decode_integer(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let n = self.decode_u29()? as i32;
+        let n = if n >= (1 << 28) { n - (1 << 29) } else { n };
+        Ok(ValueRef::Integer(n))
+    }
+    fn decode_double(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let n = self.read_f64()?;
+        Ok(ValueRef::Double(n))
+    }
+    fn decode_string(&mut self) -> DecodeResult<ValueRef<'a>> {
+        let s = self.decode_utf8_in(StringContext::Value)?;
+        Ok(ValueRef::String(s))
+    }
+    fn decode_xml_doc(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, len| {
+            this.read_utf8(len, StringContext::Value)
+                .map(ValueRef::XmlDocument)
+        })
+    }
+    fn decode_date(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, size| {
+            if size != 0 {
+                return Err(DecodeError::InvalidDateHeader { size });
+            }
+            let millis = this.read_f64()?;
+            if !(millis.is_finite() && millis.is_sign_positive()) {
+                Err(DecodeError::InvalidDate { millis })
+            } else {
+                Ok(ValueRef::Date {
+                    unix_time: time::Duration::from_millis(millis as u64),
+                })
+            }
+        })
+    }
+    fn decode_array(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, count| {
+            let assoc = this.decode_pairs()?;
+            let mut dense = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                dense.push(this.decode_value()?);
+            }
+            Ok(ValueRef::Array {
+                assoc_entries: assoc,
+                dense_entries: dense,
+            })
+        })
+    }
+    fn decode_object(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, u28| {
+            let amf_trait = this.decode_trait(u28)?;
+            let mut entries = Vec::with_capacity(amf_trait.fields.len());
+            for &key in &amf_trait.fields {
+                let value = this.decode_value()?;
+                entries.push(Pair { key, value });
+            }
+            if amf_trait.is_dynamic {
+                entries.extend(this.decode_pairs()?);
+            }
+            Ok(ValueRef::Object {
+                class_name: amf_trait.class_name,
+                sealed_count: amf_trait.fields.len(),
+                entries,
+            })
+        })
+    }
+    fn decode_xml(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, len| {
+            this.read_utf8(len, StringContext::Value).map(ValueRef::Xml)
+        })
+    }
+    fn decode_byte_array(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, len| Ok(ValueRef::ByteArray(this.read_slice(len)?)))
+    }
+    fn decode_vector_int(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, count| {
+            let is_fixed = this.read_u8()? != 0;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.read_i32()?);
+            }
+            Ok(ValueRef::IntVector { is_fixed, entries })
+        })
+    }
+    fn decode_vector_uint(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, count| {
+            let is_fixed = this.read_u8()? != 0;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.read_u32()?);
+            }
+            Ok(ValueRef::UintVector { is_fixed, entries })
+        })
+    }
+    fn decode_vector_double(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, count| {
+            let is_fixed = this.read_u8()? != 0;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.read_f64()?);
+            }
+            Ok(ValueRef::DoubleVector { is_fixed, entries })
+        })
+    }
+    fn decode_vector_object(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, count| {
+            let is_fixed = this.read_u8()? != 0;
+            let class_name = this.decode_utf8_in(StringContext::ClassName)?;
+            let mut entries = Vec::with_capacity(cmp::min(count, MAX_PREALLOCATED_ENTRIES));
+            for _ in 0..count {
+                entries.push(this.decode_value()?);
+            }
+            Ok(ValueRef::ObjectVector {
+                class_name: if class_name == "*" {
+                    None
+                } else {
+                    Some(class_name)
+                },
+                is_fixed,
+                entries,
+            })
+        })
+    }
+    fn decode_dictionary(&mut self) -> DecodeResult<ValueRef<'a>> {
+        self.decode_complex_type(|this, count| {
+            let is_weak = this.read_u8()? == 1;
+            let entries = (0..count)
+                .map(|_| {
+                    Ok(Pair {
+                        key: this.decode_value()?,
+                        value: this.decode_value()?,
+                    })
+                })
+                .collect::<DecodeResult<_>>()?;
+            Ok(ValueRef::Dictionary { is_weak, entries })
+        })
+    }
+
+    fn decode_utf8_in(&mut self, context: StringContext) -> DecodeResult<&'a str> {
+        match self.decode_size_or_index()? {
+            SizeOrIndex::Size(len) => {
+                let s = self.read_utf8(len, context)?;
+                if !s.is_empty() {
+                    self.strings.push(s);
+                }
+                Ok(s)
+            }
+            SizeOrIndex::Index(index) => self
+                .strings
+                .get(index)
+                .copied()
+                .ok_or(DecodeError::OutOfRangeReference { index }),
+        }
+    }
+    fn decode_u29(&mut self) -> DecodeResult<u32> {
+        let mut n = 0;
+        for _ in 0..3 {
+            let b = self.read_u8()? as u32;
+            n = (n << 7) | (b & 0b0111_1111);
+            if (b & 0b1000_0000) == 0 {
+                return Ok(n);
+            }
+        }
+        let b = self.read_u8()? as u32;
+        n = (n << 8) | b;
+        Ok(n)
+    }
+    fn decode_size_or_index(&mut self) -> DecodeResult<SizeOrIndex> {
+        let u29 = self.decode_u29()? as usize;
+        let is_reference = (u29 & 0b01) == 0;
+        let value = u29 >> 1;
+        if is_reference {
+            Ok(SizeOrIndex::Index(value))
+        } else {
+            Ok(SizeOrIndex::Size(value))
+        }
+    }
+    fn decode_complex_type<F>(&mut self, f: F) -> DecodeResult<ValueRef<'a>>
+    where
+        F: FnOnce(&mut Self, usize) -> DecodeResult<ValueRef<'a>>,
+    {
+        match self.decode_size_or_index()? {
+            SizeOrIndex::Index(index) => self
+                .complexes
+                .get(index)
+                .ok_or(DecodeError::OutOfRangeReference { index })
+                .and_then(|v| {
+                    if *v == ValueRef::Null {
+                        Err(DecodeError::CircularReference { index })
+                    } else {
+                        Ok(v.clone())
+                    }
+                }),
+            SizeOrIndex::Size(u28) => {
+                if self.depth == MAX_DECODE_DEPTH {
+                    return Err(DecodeError::DepthLimitExceeded {
+                        offset: self.position,
+                    });
+                }
+                let index = self.complexes.len();
+                self.complexes.push(ValueRef::Null);
+                self.depth += 1;
+                let value = f(self, u28);
+                self.depth -= 1;
+                let value = value?;
+                self.complexes[index] = value.clone();
+                Ok(value)
+            }
+        }
+    }
+    fn decode_pairs(&mut self) -> DecodeResult<Vec<Pair<&'a str, ValueRef<'a>>>> {
+        let mut pairs = Vec::new();
+        loop {
+            let key = self.decode_utf8_in(StringContext::Key)?;
+            if key.is_empty() {
+                return Ok(pairs);
+            }
+            let value = self.decode_value()?;
+            pairs.push(Pair { key, value });
+        }
+    }
+    fn decode_trait(&mut self, u28: usize) -> DecodeResult<Trait<'a>> {
+        if (u28 & 0b1) == 0 {
+            let i = u28 >> 1;
+            let t = self
+                .traits
+                .get(i)
+                .ok_or(DecodeError::OutOfRangeReference { index: i })?;
+            Ok(t.clone())
+        } else if (u28 & 0b10) != 0 {
+            let class_name = self.decode_utf8_in(StringContext::ClassName)?;
+            Err(DecodeError::ExternalizableType {
+                name: class_name.to_owned(),
+            })
+        } else {
+            let is_dynamic = (u28 & 0b100) != 0;
+            let field_num = u28 >> 3;
+            let class_name = self.decode_utf8_in(StringContext::ClassName)?;
+            let fields = (0..field_num)
+                .map(|_| self.decode_utf8_in(StringContext::Key))
+                .collect::<DecodeResult<_>>()?;
+
+            let t = Trait {
+                class_name: if class_name.is_empty() {
+                    None
+                } else {
+                    Some(class_name)
+                },
+                is_dynamic,
+                fields,
+            };
+            self.traits.push(t.clone());
+            Ok(t)
+        }
+    }
+    fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_slice(1)?[0])
+    }
+    fn read_i32(&mut self) -> io::Result<i32> {
+        Ok(BigEndian::read_i32(self.read_slice(4)?))
+    }
+    fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(BigEndian::read_u32(self.read_slice(4)?))
+    }
+    fn read_f64(&mut self) -> io::Result<f64> {
+        Ok(BigEndian::read_f64(self.read_slice(8)?))
+    }
+    fn read_slice(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let rest = &self.input[self.position..];
+        if rest.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+    fn read_utf8(&mut self, len: usize, context: StringContext) -> DecodeResult<&'a str> {
+        let offset = self.position;
+        let bytes = self.read_slice(len)?;
+        match str::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(DecodeError::Utf8 {
+                error: String::from_utf8(bytes.to_owned()).unwrap_err(),
+                offset,
+                context,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Value;
+    use super::ValueRef;
+    use crate::error::DecodeError;
+    use crate::io;
+
+    macro_rules! decode_same {
+        ($file:expr) => {{
+            let input = include_bytes!(concat!("../testdata/", $file));
+            let expected = Value::read_from(&mut &input[..]);
+            let value = ValueRef::read_from_slice(&input[..]).map(|v| v.to_owned());
+            assert_eq!(value, expected);
+        }};
+    }
+
+    #[test]
+    fn decodes_same_as_owned_value() {
+        decode_same!("amf3-0.bin");
+        decode_same!("amf3-array-collection.bin");
+        decode_same!("amf3-array-ref.bin");
+        decode_same!("amf3-associative-array.bin");
+        decode_same!("amf3-bignum.bin");
+        decode_same!("amf3-byte-array-ref.bin");
+        decode_same!("amf3-byte-array.bin");
+        decode_same!("amf3-complex-encoded-string-array.bin");
+        decode_same!("amf3-date-ref.bin");
+        decode_same!("amf3-date.bin");
+        decode_same!("amf3-dictionary.bin");
+        decode_same!("amf3-dynamic-object.bin");
+        decode_same!("amf3-empty-array-ref.bin");
+        decode_same!("amf3-empty-dictionary.bin");
+        decode_same!("amf3-empty-string-ref.bin");
+        decode_same!("amf3-encoded-string-ref.bin");
+        decode_same!("amf3-graph-member.bin");
+        decode_same!("amf3-hash.bin");
+        decode_same!("amf3-mixed-array.bin");
+        decode_same!("amf3-object-ref.bin");
+        decode_same!("amf3-primitive-array.bin");
+        decode_same!("amf3-string-ref.bin");
+        decode_same!("amf3-trait-ref.bin");
+        decode_same!("amf3-typed-object.bin");
+        decode_same!("amf3-vector-double.bin");
+        decode_same!("amf3-vector-int.bin");
+        decode_same!("amf3-vector-object-non-object.bin");
+        decode_same!("amf3-vector-object.bin");
+        decode_same!("amf3-vector-uint.bin");
+        decode_same!("amf3-xml-doc.bin");
+        decode_same!("amf3-xml-ref.bin");
+    }
+    #[test]
+    fn reports_same_errors_as_owned_value() {
+        decode_same!("amf3-array-unknown-marker.bin");
+        decode_same!("amf3-bad-object-ref.bin");
+        decode_same!("amf3-bad-string-ref.bin");
+        decode_same!("amf3-bad-utf8-key.bin");
+        decode_same!("amf3-bad-trait-ref.bin");
+        decode_same!("amf3-date-bad-header.bin");
+        decode_same!("amf3-date-invalid-millis.bin");
+        decode_same!("amf3-date-minus-millis.bin");
+        decode_same!("amf3-externalizable.bin");
+        decode_same!("amf3-unknown-marker.bin");
+    }
+    #[test]
+    fn borrows_from_input() {
+        let input = include_bytes!("../testdata/amf3-string.bin");
+        let value = ValueRef::read_from_slice(&input[..]).unwrap();
+        let s = match value {
+            ValueRef::String(s) => s,
+            _ => panic!(),
+        };
+        assert_eq!(s, "String . String");
+        assert!(input.as_ptr_range().contains(&s.as_ptr()));
+    }
+    #[test]
+    fn unexpected_eof() {
+        let input = include_bytes!("../testdata/amf3-string-partial.bin");
+        match ValueRef::read_from_slice(&input[..]) {
+            Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! `arbitrary::Arbitrary` implementations which generate well-formed values.
+//!
+//! The generated values survive an encode/decode round trip unchanged:
+//! numbers are finite, dates have millisecond precision, AMF3 integers are in the 29-bit range,
+//! `sealed_count`s are in bounds, and keys and class names are non-empty.
+//! The nesting depth and the number of the entries of a collection are bounded.
+use crate::{amf0, amf3, Pair, Value};
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use std::time;
+
+/// The maximum nesting depth of the generated values.
+const MAX_DEPTH: usize = 4;
+
+/// The maximum number of the entries of a generated collection.
+const MAX_ENTRIES: usize = 4;
+
+/// The maximum number of the characters of a generated string.
+const MAX_CHARS: usize = 16;
+
+impl<'a> Arbitrary<'a> for amf0::Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        amf0_value(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for amf3::Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        amf3_value(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.arbitrary()? {
+            amf0_value(u, 0).map(Value::Amf0)
+        } else {
+            amf3_value(u, 0).map(Value::Amf3)
+        }
+    }
+}
+
+fn amf0_value(u: &mut Unstructured, depth: usize) -> Result<amf0::Value> {
+    use crate::amf0::Value;
+
+    let kinds = if depth < MAX_DEPTH { 10 } else { 6 };
+    Ok(match u.int_in_range(0..=kinds)? {
+        0 => Value::Number(number(u)?),
+        1 => Value::Boolean(u.arbitrary()?),
+        2 => Value::String(string(u)?),
+        3 => Value::Null,
+        4 => Value::Undefined,
+        5 => Value::Date {
+            unix_time: unix_time(u)?,
+            time_zone: u.arbitrary()?,
+        },
+        6 => Value::XmlDocument(string(u)?),
+        7 => Value::Object {
+            class_name: optional_key(u)?,
+            entries: pairs(u, |u| amf0_value(u, depth + 1))?,
+        },
+        8 => Value::EcmaArray {
+            entries: pairs(u, |u| amf0_value(u, depth + 1))?,
+            declared_count: None,
+        },
+        9 => Value::Array {
+            entries: entries(u, |u| amf0_value(u, depth + 1))?,
+        },
+        _ => Value::AvmPlus(amf3_value(u, depth + 1)?),
+    })
+}
+
+fn amf3_value(u: &mut Unstructured, depth: usize) -> Result<amf3::Value> {
+    use crate::amf3::Value;
+
+    let kinds = if depth < MAX_DEPTH { 16 } else { 12 };
+    Ok(match u.int_in_range(0..=kinds)? {
+        0 => Value::Undefined,
+        1 => Value::Null,
+        2 => Value::Boolean(u.arbitrary()?),
+        3 => Value::Integer(u.int_in_range(-0x1000_0000..=0x0FFF_FFFF)?),
+        4 => Value::Double(number(u)?),
+        5 => Value::String(string(u)?),
+        6 => Value::XmlDocument(string(u)?),
+        7 => Value::Date {
+            unix_time: unix_time(u)?,
+        },
+        8 => Value::Xml(string(u)?),
+        9 => Value::ByteArray(entries(u, |u| u.arbitrary())?),
+        10 => Value::IntVector {
+            is_fixed: u.arbitrary()?,
+            entries: entries(u, |u| u.arbitrary())?,
+        },
+        11 => Value::UintVector {
+            is_fixed: u.arbitrary()?,
+            entries: entries(u, |u| u.arbitrary())?,
+        },
+        12 => Value::DoubleVector {
+            is_fixed: u.arbitrary()?,
+            entries: entries(u, number)?,
+        },
+        13 => Value::Array {
+            assoc_entries: pairs(u, |u| amf3_value(u, depth + 1))?,
+            dense_entries: entries(u, |u| amf3_value(u, depth + 1))?,
+        },
+        14 => amf3_object(u, depth)?,
+        15 => Value::ObjectVector {
+            class_name: optional_key(u)?,
+            is_fixed: u.arbitrary()?,
+            entries: entries(u, |u| {
+                if u.arbitrary()? {
+                    Ok(Value::Null)
+                } else {
+                    amf3_object(u, depth + 1)
+                }
+            })?,
+        },
+        _ => Value::Dictionary {
+            is_weak: u.arbitrary()?,
+            entries: entries(u, |u| {
+                Ok(Pair {
+                    key: amf3_value(u, depth + 1)?,
+                    value: amf3_value(u, depth + 1)?,
+                })
+            })?,
+        },
+    })
+}
+
+fn amf3_object(u: &mut Unstructured, depth: usize) -> Result<amf3::Value> {
+    let entries = pairs(u, |u| amf3_value(u, depth + 1))?;
+    Ok(amf3::Value::Object {
+        class_name: optional_key(u)?,
+        sealed_count: u.int_in_range(0..=entries.len())?,
+        entries,
+    })
+}
+
+fn number(u: &mut Unstructured) -> Result<f64> {
+    let x: f64 = u.arbitrary()?;
+    Ok(if x.is_finite() { x } else { 0.0 })
+}
+
+fn string(u: &mut Unstructured) -> Result<String> {
+    (0..u.int_in_range(0..=MAX_CHARS)?)
+        .map(|_| u.arbitrary::<char>())
+        .collect()
+}
+
+/// Makes a non-empty string which is neither the end of an object nor the ANY type name (`"*"`).
+fn key(u: &mut Unstructured) -> Result<String> {
+    let mut key = string(u)?;
+    key.push('k');
+    Ok(key)
+}
+
+fn optional_key(u: &mut Unstructured) -> Result<Option<String>> {
+    if u.arbitrary()? {
+        key(u).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn unix_time(u: &mut Unstructured) -> Result<time::Duration> {
+    u.int_in_range(0..=1 << 42).map(time::Duration::from_millis)
+}
+
+fn entries<'a, F, T>(u: &mut Unstructured<'a>, mut f: F) -> Result<Vec<T>>
+where
+    F: FnMut(&mut Unstructured<'a>) -> Result<T>,
+{
+    (0..u.int_in_range(0..=MAX_ENTRIES)?)
+        .map(|_| f(u))
+        .collect()
+}
+
+fn pairs<'a, F, V>(u: &mut Unstructured<'a>, mut f: F) -> Result<Vec<Pair<String, V>>>
+where
+    F: FnMut(&mut Unstructured<'a>) -> Result<V>,
+{
+    entries(u, |u| {
+        Ok(Pair {
+            key: key(u)?,
+            value: f(u)?,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::EncodeOptions;
+    use crate::Value;
+    use ::arbitrary::{Arbitrary, Unstructured};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn assert_round_trip(value: Value) {
+        let version = value.version();
+        crate::testing::assert_roundtrip(&value, version);
+
+        let options = EncodeOptions {
+            use_string_references: true,
+            use_object_references: true,
+            use_trait_references: true,
+            force_long_strings: true,
+            amf0_date_time_zone: None,
+            wrap_avmplus: false,
+            preserve_ecma_array_counts: true,
+        };
+        let mut buf = Vec::new();
+        value.write_to_with_options(&mut buf, &options).unwrap();
+        assert_eq!(Value::read_from(&mut &buf[..], version).unwrap(), value);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn amf0_values_round_trip(bytes in vec(any::<u8>(), 0..1024)) {
+            let value = crate::amf0::Value::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            assert_round_trip(Value::Amf0(value));
+        }
+
+        #[test]
+        fn amf3_values_round_trip(bytes in vec(any::<u8>(), 0..1024)) {
+            let value = crate::amf3::Value::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            assert_round_trip(Value::Amf3(value));
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use crate::error::DecodeError;
+use crate::options::DecodeOptions;
+use crate::{amf0, DecodeResult, Decoder, EncodeResult, Value, Version};
+use crate::{MAX_DECODE_DEPTH, MAX_PREALLOCATED_BYTES};
+use byteorder::{BigEndian, ByteOrder};
+use std::cmp;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl Value {
+    /// Reads an AMF encoded `Value` from the asynchronous `reader`.
+    ///
+    /// Only the bytes which make up the value are consumed from `reader`,
+    /// so consecutive values can be read from the same reader.
+    /// If `reader` reaches EOF in the middle of a value,
+    /// `DecodeError::Io` with the kind `io::ErrorKind::UnexpectedEof` is returned.
+    pub async fn read_from_async<R>(reader: &mut R, version: Version) -> DecodeResult<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        Self::read_from_async_with_options(reader, version, DecodeOptions::default()).await
+    }
+
+    /// Reads an AMF encoded `Value` from the asynchronous `reader` with the given options.
+    ///
+    /// The bytes of the value are read by following its markers and lengths (awaiting each read),
+    /// and then decoded at once by the same decoder as `read_from`.
+    /// The buffered bytes are also limited by `DecodeOptions::max_total_bytes`, so a forged length
+    /// can not make this buffer the input until the peer closes the connection.
+    /// Likewise, the nesting depth and `DecodeOptions::max_object_entries` are checked while reading,
+    /// and reading stops where the decoder reports the error.
+    pub async fn read_from_async_with_options<R>(
+        reader: &mut R,
+        version: Version,
+        options: DecodeOptions,
+    ) -> DecodeResult<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut scanner = Scanner {
+            reader,
+            buf: Vec::new(),
+            options: &options,
+            depth: 0,
+            traits: Vec::new(),
+        };
+        scanner.scan(version).await?;
+        let buf = scanner.buf;
+        Decoder::with_options(&buf[..], version, options).decode()
+    }
+
+    /// Writes the AMF encoded bytes of this value to the asynchronous `writer`.
+    pub async fn write_to_async<W>(&self, writer: &mut W) -> EncodeResult<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut buf = Vec::new();
+        self.write_to(&mut buf)?;
+        writer.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+/// A part of a value which remains to be read.
+#[derive(Debug, Clone, Copy)]
+enum Task {
+    Amf0Value,
+    Amf0Values(usize),
+    /// The pairs of an AMF0 object, and the number of the pairs read so far.
+    Amf0Pairs(usize),
+    Amf3Value,
+    Amf3Values(usize),
+    /// The pairs of an AMF3 object or array, and the number of the pairs read so far.
+    Amf3Pairs(usize),
+    /// The end of a complex value.
+    Leave,
+}
+
+/// Reads the bytes of a value from an asynchronous reader without decoding them.
+///
+/// Scanning stops at the first malformed part (e.g., an unknown marker) or at the first part
+/// which exceeds a limit without consuming any more bytes,
+/// and the decoder reports the error from the bytes read so far.
+struct Scanner<'a, R> {
+    reader: &'a mut R,
+    buf: Vec<u8>,
+    options: &'a DecodeOptions,
+
+    /// The nesting level of the complex values (as counted by the decoders).
+    depth: usize,
+
+    /// The number of the sealed members and `is_dynamic` of the AMF3 traits read so far.
+    traits: Vec<(usize, bool)>,
+}
+impl<R> Scanner<'_, R>
+where
+    R: AsyncRead + Unpin,
+{
+    async fn scan(&mut self, version: Version) -> DecodeResult<()> {
+        let mut tasks = vec![match version {
+            Version::Amf0 => Task::Amf0Value,
+            Version::Amf3 => Task::Amf3Value,
+        }];
+        while let Some(task) = tasks.pop() {
+            let is_valid = match task {
+                Task::Amf0Value => {
+                    let marker = self.read_u8().await?;
+                    self.scan_amf0_value(marker, &mut tasks).await?
+                }
+                Task::Amf0Values(n) => {
+                    if n > 1 {
+                        tasks.push(Task::Amf0Values(n - 1));
+                    }
+                    if n > 0 {
+                        tasks.push(Task::Amf0Value);
+                    }
+                    true
+                }
+                // The decoder checks the number of the pairs after decoding the value of each pair.
+                Task::Amf0Pairs(n) if self.exceeds_max_entries(n) => false,
+                Task::Amf0Pairs(n) => {
+                    let len = self.read_u16().await? as usize;
+                    self.read_bytes(len).await?;
+                    let marker = self.read_u8().await?;
+                    if len == 0 && marker == amf0::marker::OBJECT_END_MARKER {
+                        true
+                    } else {
+                        tasks.push(Task::Amf0Pairs(n + 1));
+                        self.scan_amf0_value(marker, &mut tasks).await?
+                    }
+                }
+                Task::Amf3Value => self.scan_amf3_value(&mut tasks).await?,
+                Task::Amf3Values(n) => {
+                    if n > 1 {
+                        tasks.push(Task::Amf3Values(n - 1));
+                    }
+                    if n > 0 {
+                        tasks.push(Task::Amf3Value);
+                    }
+                    true
+                }
+                // The decoder checks the number of the pairs after decoding the key of each pair.
+                Task::Amf3Pairs(n) => {
+                    if self.read_amf3_string().await? {
+                        true
+                    } else if self.exceeds_max_entries(n + 1) {
+                        false
+                    } else {
+                        tasks.push(Task::Amf3Pairs(n + 1));
+                        tasks.push(Task::Amf3Value);
+                        true
+                    }
+                }
+                Task::Leave => {
+                    self.depth -= 1;
+                    true
+                }
+            };
+            if !is_valid {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the rest of an AMF0 value, returning `false` if the value is malformed.
+    async fn scan_amf0_value(&mut self, marker: u8, tasks: &mut Vec<Task>) -> DecodeResult<bool> {
+        use crate::amf0::marker;
+
+        let is_complex = match marker {
+            marker::OBJECT | marker::ECMA_ARRAY | marker::STRICT_ARRAY | marker::TYPED_OBJECT => {
+                true
+            }
+            marker::RECORDSET => self.options.decode_recordsets,
+            _ => false,
+        };
+        if is_complex && !self.enter(tasks) {
+            return Ok(false);
+        }
+        match marker {
+            marker::NUMBER => self.read_bytes(8).await?,
+            marker::BOOLEAN => self.read_bytes(1).await?,
+            marker::STRING => {
+                let len = self.read_u16().await? as usize;
+                self.read_bytes(len).await?;
+            }
+            marker::OBJECT => tasks.push(Task::Amf0Pairs(0)),
+            marker::NULL | marker::UNDEFINED | marker::MOVIECLIP | marker::UNSUPPORTED => {}
+            marker::REFERENCE => self.read_bytes(2).await?,
+            marker::ECMA_ARRAY => {
+                self.read_bytes(4).await?;
+                tasks.push(Task::Amf0Pairs(0));
+            }
+            marker::STRICT_ARRAY => {
+                let count = self.read_u32().await? as usize;
+                tasks.push(Task::Amf0Values(count));
+            }
+            marker::DATE => self.read_bytes(10).await?,
+            marker::LONG_STRING | marker::XML_DOCUMENT => {
+                let len = self.read_u32().await? as usize;
+                self.read_bytes(len).await?;
+            }
+            marker::RECORDSET if self.options.decode_recordsets => tasks.push(Task::Amf0Pairs(0)),
+            marker::RECORDSET => {}
+            marker::TYPED_OBJECT => {
+                let len = self.read_u16().await? as usize;
+                self.read_bytes(len).await?;
+                tasks.push(Task::Amf0Pairs(0));
+            }
+            marker::AVMPLUS_OBJECT => {
+                if !self.options.share_avmplus_reference_tables {
+                    self.traits.clear();
+                }
+                tasks.push(Task::Amf3Value);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Reads an AMF3 value, returning `false` if the value is malformed.
+    async fn scan_amf3_value(&mut self, tasks: &mut Vec<Task>) -> DecodeResult<bool> {
+        use crate::amf3::marker;
+
+        let marker = self.read_u8().await?;
+        match marker {
+            marker::UNDEFINED | marker::NULL | marker::FALSE | marker::TRUE => return Ok(true),
+            marker::INTEGER => {
+                self.read_u29().await?;
+                return Ok(true);
+            }
+            marker::DOUBLE => {
+                self.read_bytes(8).await?;
+                return Ok(true);
+            }
+            marker::STRING => {
+                self.read_amf3_string().await?;
+                return Ok(true);
+            }
+            marker::XML_DOC
+            | marker::DATE
+            | marker::ARRAY
+            | marker::OBJECT
+            | marker::XML
+            | marker::BYTE_ARRAY
+            | marker::VECTOR_INT
+            | marker::VECTOR_UINT
+            | marker::VECTOR_DOUBLE
+            | marker::VECTOR_OBJECT
+            | marker::DICTIONARY => {}
+            _ => return Ok(false),
+        }
+
+        // The rest are complex values, which may be references.
+        let size = match self.read_size().await? {
+            Some(size) => size,
+            None => return Ok(true),
+        };
+        if !self.enter(tasks) {
+            return Ok(false);
+        }
+        match marker {
+            marker::XML_DOC | marker::XML | marker::BYTE_ARRAY => self.read_bytes(size).await?,
+            marker::DATE if size != 0 && !self.options.lenient_amf3_dates => return Ok(false),
+            marker::DATE => self.read_bytes(8).await?,
+            marker::ARRAY => {
+                tasks.push(Task::Amf3Values(size));
+                tasks.push(Task::Amf3Pairs(0));
+            }
+            marker::OBJECT => return self.scan_amf3_object(size, tasks).await,
+            marker::VECTOR_INT | marker::VECTOR_UINT => {
+                self.read_bytes(size.saturating_mul(4).saturating_add(1))
+                    .await?;
+            }
+            marker::VECTOR_DOUBLE => {
+                self.read_bytes(size.saturating_mul(8).saturating_add(1))
+                    .await?;
+            }
+            marker::VECTOR_OBJECT => {
+                self.read_bytes(1).await?;
+                self.read_amf3_string().await?;
+                tasks.push(Task::Amf3Values(size));
+            }
+            _ => {
+                // `marker::DICTIONARY`
+                self.read_bytes(1).await?;
+                tasks.push(Task::Amf3Values(size.saturating_mul(2)));
+            }
+        }
+        Ok(true)
+    }
+    async fn scan_amf3_object(&mut self, u28: usize, tasks: &mut Vec<Task>) -> DecodeResult<bool> {
+        let (field_num, is_dynamic) = if (u28 & 0b1) == 0 {
+            match self.traits.get(u28 >> 1) {
+                Some(&t) => t,
+                None => return Ok(false),
+            }
+        } else if (u28 & 0b10) != 0 {
+            // The decoder rejects externalizable types after reading the class name.
+            self.read_amf3_string().await?;
+            return Ok(false);
+        } else {
+            let is_dynamic = (u28 & 0b100) != 0;
+            let field_num = u28 >> 3;
+            self.read_amf3_string().await?;
+            for _ in 0..field_num {
+                self.read_amf3_string().await?;
+            }
+            self.traits.push((field_num, is_dynamic));
+            (field_num, is_dynamic)
+        };
+        if is_dynamic {
+            tasks.push(Task::Amf3Pairs(0));
+        }
+        tasks.push(Task::Amf3Values(field_num));
+        Ok(true)
+    }
+
+    /// Enters a complex value, returning `false` if it exceeds the depth limit.
+    fn enter(&mut self, tasks: &mut Vec<Task>) -> bool {
+        if self.depth == MAX_DECODE_DEPTH {
+            return false;
+        }
+        self.depth += 1;
+        tasks.push(Task::Leave);
+        true
+    }
+    fn exceeds_max_entries(&self, count: usize) -> bool {
+        matches!(self.options.max_object_entries, Some(max) if count > max)
+    }
+
+    /// Reads an AMF3 string, returning whether it is the empty string.
+    async fn read_amf3_string(&mut self) -> DecodeResult<bool> {
+        match self.read_size().await? {
+            Some(len) => {
+                self.read_bytes(len).await?;
+                Ok(len == 0)
+            }
+            None => Ok(false),
+        }
+    }
+    /// Reads a size, or returns `None` if it is a reference.
+    async fn read_size(&mut self) -> DecodeResult<Option<usize>> {
+        let u29 = self.read_u29().await? as usize;
+        if (u29 & 0b01) == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(u29 >> 1))
+        }
+    }
+    async fn read_u29(&mut self) -> DecodeResult<u32> {
+        let mut n = 0;
+        for _ in 0..3 {
+            let b = self.read_u8().await? as u32;
+            n = (n << 7) | (b & 0b0111_1111);
+            if (b & 0b1000_0000) == 0 {
+                return Ok(n);
+            }
+        }
+        let b = self.read_u8().await? as u32;
+        Ok((n << 8) | b)
+    }
+    async fn read_u8(&mut self) -> DecodeResult<u8> {
+        self.read_bytes(1).await?;
+        Ok(self.buf[self.buf.len() - 1])
+    }
+    async fn read_u16(&mut self) -> DecodeResult<u16> {
+        self.read_bytes(2).await?;
+        Ok(BigEndian::read_u16(&self.buf[self.buf.len() - 2..]))
+    }
+    async fn read_u32(&mut self) -> DecodeResult<u32> {
+        self.read_bytes(4).await?;
+        Ok(BigEndian::read_u32(&self.buf[self.buf.len() - 4..]))
+    }
+    /// Appends `len` bytes from the reader to the buffer in chunks,
+    /// so that the buffer grows only as fast as the bytes arrive.
+    async fn read_bytes(&mut self, mut len: usize) -> DecodeResult<()> {
+        while len > 0 {
+            let start = self.buf.len();
+            let n = cmp::min(len, MAX_PREALLOCATED_BYTES);
+            if let Some(max) = self.options.max_total_bytes {
+                if start + n > max {
+                    return Err(DecodeError::AllocationBudgetExceeded { max, offset: start });
+                }
+            }
+            self.buf.resize(start + n, 0);
+            self.reader.read_exact(&mut self.buf[start..]).await?;
+            len -= n;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::DecodeError;
+    use crate::options::DecodeOptions;
+    use crate::{amf0, amf3, DecodeResult, Decoder, Pair, Value, Version, MAX_DECODE_DEPTH};
+    use std::io;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Reads a value from `input` which arrives in small chunks, returning the bytes left after it.
+    async fn read_in_chunks(
+        input: &[u8],
+        version: Version,
+        options: DecodeOptions,
+    ) -> (DecodeResult<Value>, Vec<u8>) {
+        let (mut tx, mut rx) = tokio::io::duplex(3);
+        let input = input.to_vec();
+        let writer = tokio::spawn(async move { tx.write_all(&input).await });
+        let result = Value::read_from_async_with_options(&mut rx, version, options).await;
+        let mut rest = Vec::new();
+        rx.read_to_end(&mut rest).await.unwrap();
+        writer.await.unwrap().unwrap();
+        (result, rest)
+    }
+
+    #[tokio::test]
+    async fn reads_values_from_async_reader() {
+        let (mut tx, rx) = tokio::io::duplex(4);
+        let writer = tokio::spawn(async move {
+            let input = include_bytes!("testdata/amf0-complex-encoded-string.bin");
+            tx.write_all(&input[..]).await.unwrap();
+            tx.write_all(&[0x00, 0x40, 0x0C, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+
+        let mut reader = rx;
+        let value = Value::read_from_async(&mut reader, Version::Amf0)
+            .await
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::Amf0(amf0::object(
+                vec![
+                    ("utf", amf0::string("UTF テスト")),
+                    ("zed", amf0::number(5.0)),
+                    ("shift", amf0::string("Shift テスト")),
+                ]
+                .into_iter()
+            ))
+        );
+
+        let value = Value::read_from_async(&mut reader, Version::Amf0)
+            .await
+            .unwrap();
+        assert_eq!(value, Value::Amf0(amf0::number(3.5)));
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_unexpected_eof() {
+        let (mut tx, rx) = tokio::io::duplex(64);
+        tx.write_all(&[0x00, 0x40, 0x0C]).await.unwrap();
+        drop(tx);
+
+        let mut reader = rx;
+        match Value::read_from_async(&mut reader, Version::Amf0).await {
+            Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_same_values_as_sync_decoder() {
+        let limited = DecodeOptions {
+            max_object_entries: Some(1),
+            ..Default::default()
+        };
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata");
+        let mut paths = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert!(paths.len() > 100);
+        for path in paths {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let version = if name.contains("amf0-") {
+                Version::Amf0
+            } else {
+                Version::Amf3
+            };
+            // Both decoders read the trailer if the input ends in the middle of a value.
+            let trailer = [0xFF, 0xFE];
+            let input = [std::fs::read(&path).unwrap(), trailer.to_vec()].concat();
+            for options in [DecodeOptions::default(), limited.clone()] {
+                let mut unread = &input[..];
+                let expected =
+                    Decoder::with_options(&mut unread, version, options.clone()).decode();
+                let (result, rest) = read_in_chunks(&input, version, options).await;
+                match (&result, &expected) {
+                    // The messages of the I/O errors differ.
+                    (Err(DecodeError::Io(a)), Err(DecodeError::Io(b))) => {
+                        assert_eq!(a.kind(), b.kind(), "{}", name)
+                    }
+                    // `NaN`s are not equal to themselves.
+                    (Ok(a), Ok(b)) => assert!(a.cmp_canonical(b).is_eq(), "{}", name),
+                    _ => assert_eq!(result, expected, "{}", name),
+                }
+                if expected.is_ok() {
+                    assert_eq!(rest, unread, "{}", name);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn limits_object_entries() {
+        let options = DecodeOptions {
+            max_object_entries: Some(2),
+            ..Default::default()
+        };
+        let amf0 = amf0::object((0..4).map(|i| (format!("k{}", i), amf0::number(i))));
+        let amf3 = amf3::Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: (0..4)
+                .map(|i| Pair {
+                    key: format!("k{}", i),
+                    value: amf3::Value::Integer(i),
+                })
+                .collect(),
+        };
+        for value in [Value::Amf0(amf0), Value::Amf3(amf3)] {
+            let mut input = Vec::new();
+            value.write_to(&mut input).unwrap();
+            let expected =
+                Decoder::with_options(&input[..], value.version(), options.clone()).decode();
+            assert!(
+                matches!(
+                    expected,
+                    Err(DecodeError::CollectionTooLarge { max: 2, .. })
+                ),
+                "{:?}",
+                expected
+            );
+
+            // The entries after the limit are left unread.
+            let (result, rest) = read_in_chunks(&input, value.version(), options.clone()).await;
+            assert_eq!(result, expected);
+            assert!(!rest.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn limits_nesting_depth() {
+        let amf0 = (0..MAX_DECODE_DEPTH + 1).fold(amf0::Value::Null, |v, _| amf0::Value::Array {
+            entries: vec![v],
+        });
+        let amf3 = (0..MAX_DECODE_DEPTH + 1).fold(amf3::Value::Null, |v, _| amf3::Value::Array {
+            assoc_entries: vec![],
+            dense_entries: vec![v],
+        });
+        for value in [Value::Amf0(amf0), Value::Amf3(amf3)] {
+            let mut input = Vec::new();
+            value.write_to(&mut input).unwrap();
+            let expected = Value::read_from(&input[..], value.version());
+            assert!(
+                matches!(expected, Err(DecodeError::DepthLimitExceeded { .. })),
+                "{:?}",
+                expected
+            );
+
+            // The bytes after the too deep value are left unread.
+            let (result, rest) =
+                read_in_chunks(&input, value.version(), DecodeOptions::default()).await;
+            assert_eq!(result, expected);
+            assert!(!rest.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn limits_buffered_bytes() {
+        // A string which claims to have 2^28 - 1 bytes, followed by only a few bytes.
+        let input = [0x06, 0xFF, 0xFF, 0xFF, 0xFF, b'a', b'b', b'c'];
+        let options = DecodeOptions {
+            max_total_bytes: Some(1024),
+            ..Default::default()
+        };
+        let (result, _) = read_in_chunks(&input, Version::Amf3, options).await;
+        assert_eq!(
+            result,
+            Err(DecodeError::AllocationBudgetExceeded {
+                max: 1024,
+                offset: 5
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_values_to_async_writer() {
+        let (mut tx, mut rx) = tokio::io::duplex(64);
+        let value = Value::Amf3(amf3::Value::Array {
+            assoc_entries: vec![],
+            dense_entries: (1..6).map(amf3::Value::Integer).collect(),
+        });
+        value.write_to_async(&mut tx).await.unwrap();
+        drop(tx);
+
+        let mut buf = Vec::new();
+        rx.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(
+            buf,
+            &include_bytes!("testdata/amf3-primitive-array.bin")[..]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::io;
+use crate::{DecodeResult, EncodeResult, Value, Version};
+use bytes::{Buf, BufMut};
+use core::cmp;
+
+impl Value {
+    /// Reads an AMF encoded `Value` from `buf`.
+    ///
+    /// The cursor of `buf` is advanced by the number of bytes consumed by the decoding,
+    /// so the remaining bytes of `buf` are those following the value.
+    /// Note that if an error occurs, the cursor may be advanced to an arbitrary position.
+    pub fn read_from_buf<B>(buf: &mut B, version: Version) -> DecodeResult<Self>
+    where
+        B: Buf,
+    {
+        Value::read_from(BufReader(buf), version)
+    }
+
+    /// Writes the AMF encoded bytes of this value to `buf`.
+    ///
+    /// If `buf` has insufficient capacity, an `EncodeError::Io` error of the kind
+    /// `io::ErrorKind::WriteZero` is returned.
+    pub fn write_to_buf<B>(&self, buf: &mut B) -> EncodeResult<()>
+    where
+        B: BufMut,
+    {
+        self.write_to(BufWriter(buf))
+    }
+}
+
+struct BufReader<'a, B>(&'a mut B);
+impl<B: Buf> io::Read for BufReader<'_, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = cmp::min(buf.len(), self.0.remaining());
+        self.0.copy_to_slice(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+struct BufWriter<'a, B>(&'a mut B);
+impl<B: BufMut> io::Write for BufWriter<'_, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = cmp::min(buf.len(), self.0.remaining_mut());
+        self.0.put_slice(&buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{DecodeError, EncodeError};
+    use crate::io;
+    use crate::{amf0, amf3, Value, Version};
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+    #[test]
+    fn reads_value_from_buf() {
+        let mut buf = BytesMut::new();
+        buf.put_slice(include_bytes!("testdata/amf3-primitive-array.bin"));
+        buf.put_u8(0x01);
+        let mut buf = buf.freeze();
+
+        let value = Value::read_from_buf(&mut buf, Version::Amf3).unwrap();
+        assert_eq!(
+            value,
+            Value::Amf3(amf3::Value::Array {
+                assoc_entries: vec![],
+                dense_entries: (1..6).map(amf3::Value::Integer).collect(),
+            })
+        );
+        assert_eq!(buf.remaining(), 1);
+
+        let value = Value::read_from_buf(&mut buf, Version::Amf3).unwrap();
+        assert_eq!(value, Value::Amf3(amf3::Value::Null));
+        assert_eq!(buf.remaining(), 0);
+    }
+
+    #[test]
+    fn reads_value_from_chained_bufs() {
+        let input = include_bytes!("testdata/amf0-number.bin");
+        let mut buf = Bytes::from_static(&input[..3]).chain(Bytes::from_static(&input[3..]));
+        let value = Value::read_from_buf(&mut buf, Version::Amf0).unwrap();
+        assert_eq!(value, Value::Amf0(amf0::number(3.5)));
+        assert_eq!(buf.remaining(), 0);
+    }
+
+    #[test]
+    fn reports_unexpected_eof() {
+        let mut buf = Bytes::from_static(include_bytes!("testdata/amf0-number-partial.bin"));
+        match Value::read_from_buf(&mut buf, Version::Amf0) {
+            Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn writes_value_to_buf() {
+        let mut buf = BytesMut::new();
+        let value = Value::Amf0(amf0::number(3.5));
+        value.write_to_buf(&mut buf).unwrap();
+        assert_eq!(&buf[..], &include_bytes!("testdata/amf0-number.bin")[..]);
+
+        let mut small = [0; 4];
+        match value.write_to_buf(&mut &mut small[..]).unwrap_err() {
+            EncodeError::Io(e) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
+            e => panic!("{:?}", e),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::io;
+
+/// A writer which counts the number of bytes written through it.
+///
+/// By default, the written bytes are discarded.
+/// This is useful to measure the encoded size of a value
+/// through the same code path as `write_to`.
+///
+/// # Examples
+/// ```
+/// use amf::{Amf0Value, CountingWriter};
+///
+/// let mut writer = CountingWriter::new();
+/// Amf0Value::Number(1.0).write_to(&mut writer).unwrap();
+/// assert_eq!(writer.count(), 9);
+/// ```
+#[derive(Debug)]
+pub struct CountingWriter<W = io::Sink> {
+    inner: W,
+    count: usize,
+}
+impl CountingWriter<io::Sink> {
+    /// Makes a new instance which discards the written bytes.
+    pub fn new() -> Self {
+        Self::with_inner(io::sink())
+    }
+}
+impl Default for CountingWriter<io::Sink> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<W> CountingWriter<W> {
+    /// Makes a new instance which passes the written bytes through to `inner`.
+    pub fn with_inner(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Unwraps this `CountingWriter`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Returns the reference to the underlying writer.
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+}
+impl<W: io::Write> io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CountingWriter;
+    use crate::{Value, Version};
+
+    macro_rules! assert_count_eq_len {
+        ($file:expr, $version:expr) => {{
+            let input = include_bytes!(concat!("testdata/", $file));
+            let value = Value::read_from(&mut &input[..], $version).unwrap();
+
+            let mut buf = Vec::new();
+            value.write_to(&mut buf).unwrap();
+
+            let mut writer = CountingWriter::new();
+            value.write_to(&mut writer).unwrap();
+            assert_eq!(writer.count(), buf.len());
+
+            let mut buf2 = Vec::new();
+            assert_eq!(value.write_to_counting(&mut buf2).unwrap(), buf.len());
+            assert_eq!(buf2, buf);
+        }};
+    }
+
+    #[test]
+    fn counts_written_bytes() {
+        assert_count_eq_len!("amf0-number.bin", Version::Amf0);
+        assert_count_eq_len!("amf0-long-string.bin", Version::Amf0);
+        assert_count_eq_len!("amf0-ref-test.bin", Version::Amf0);
+        assert_count_eq_len!("amf0-typed-object.bin", Version::Amf0);
+        assert_count_eq_len!("amf0-avmplus-object.bin", Version::Amf0);
+        assert_count_eq_len!("amf3-dictionary.bin", Version::Amf3);
+        assert_count_eq_len!("amf3-typed-object.bin", Version::Amf3);
+        assert_count_eq_len!("amf3-vector-object.bin", Version::Amf3);
+        assert_count_eq_len!("amf3-byte-array.bin", Version::Amf3);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::Warning;
+use crate::io;
+use crate::options::{DecodeLimits, DecodeOptions};
+use crate::{amf0, amf3, DecodeResult, Value, Version};
+use alloc::vec::Vec;
+
+/// A decoder of either format which is chosen at run time.
+///
+/// Unlike `Value::read_from`, this keeps the reference tables across `decode` calls
+/// (until `clear_reference_table` is called), as the per-format decoders do.
+///
+/// # Examples
+/// ```
+/// use amf::{Amf0Value, Decoder, Version};
+///
+/// let mut buf = Vec::new();
+/// Amf0Value::Number(1.0).write_to(&mut buf).unwrap();
+///
+/// let mut decoder = Decoder::new(&buf[..], Version::Amf0);
+/// assert_eq!(decoder.decode().unwrap(), Amf0Value::Number(1.0));
+/// ```
+#[derive(Debug)]
+pub enum Decoder<R> {
+    /// AMF0 decoder.
+    Amf0(amf0::Decoder<R>),
+
+    /// AMF3 decoder.
+    Amf3(amf3::Decoder<R>),
+}
+impl<R> Decoder<R> {
+    /// Returns the format version of this decoder.
+    pub fn version(&self) -> Version {
+        match *self {
+            Decoder::Amf0(_) => Version::Amf0,
+            Decoder::Amf3(_) => Version::Amf3,
+        }
+    }
+
+    /// Unwraps this `Decoder`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        match self {
+            Decoder::Amf0(x) => x.into_inner(),
+            Decoder::Amf3(x) => x.into_inner(),
+        }
+    }
+
+    /// Returns the number of bytes consumed by this decoder so far.
+    pub fn position(&self) -> usize {
+        match *self {
+            Decoder::Amf0(ref x) => x.position(),
+            Decoder::Amf3(ref x) => x.position(),
+        }
+    }
+
+    /// Takes the warnings collected so far (see `DecodeOptions::collect_warnings`).
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        match *self {
+            Decoder::Amf0(ref mut x) => x.take_warnings(),
+            Decoder::Amf3(ref mut x) => x.take_warnings(),
+        }
+    }
+
+    /// Replaces the limits of this decoder with `limits` (see `amf0::Decoder::set_limits`).
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        match *self {
+            Decoder::Amf0(ref mut x) => x.set_limits(limits),
+            Decoder::Amf3(ref mut x) => x.set_limits(limits),
+        }
+    }
+}
+impl<R> Decoder<R>
+where
+    R: io::Read,
+{
+    /// Makes a new instance which decodes the values of `version`.
+    pub fn new(inner: R, version: Version) -> Self {
+        Self::with_options(inner, version, DecodeOptions::default())
+    }
+
+    /// Makes a new instance which decodes the values of `version` with the given options.
+    pub fn with_options(inner: R, version: Version, options: DecodeOptions) -> Self {
+        match version {
+            Version::Amf0 => Decoder::Amf0(amf0::Decoder::with_options(inner, options)),
+            Version::Amf3 => Decoder::Amf3(amf3::Decoder::with_options(inner, options)),
+        }
+    }
+
+    /// Decodes a value.
+    pub fn decode(&mut self) -> DecodeResult<Value> {
+        match *self {
+            Decoder::Amf0(ref mut x) => x.decode().map(Value::Amf0),
+            Decoder::Amf3(ref mut x) => x.decode().map(Value::Amf3),
+        }
+    }
+
+    /// Clear the reference tables of this decoder.
+    ///
+    /// This should be called between messages
+    /// (see `amf0::Decoder::clear_reference_table` and `amf3::Decoder::clear_reference_table`).
+    pub fn clear_reference_table(&mut self) {
+        match *self {
+            Decoder::Amf0(ref mut x) => x.clear_reference_table(),
+            Decoder::Amf3(ref mut x) => x.clear_reference_table(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+    use crate::error::DecodeError;
+    use crate::options::{DecodeLimits, EncodeOptions};
+    use crate::{Amf0Value, Amf3Value, Value, Version};
+
+    #[test]
+    fn decodes_messages_with_reset() {
+        let options = EncodeOptions {
+            use_object_references: true,
+            ..Default::default()
+        };
+        let message = |s: &str| {
+            let object =
+                crate::amf0::object(vec![("s", Amf0Value::String(s.to_string()))].into_iter());
+            let array = Amf0Value::Array {
+                entries: vec![object.clone(), object],
+            };
+            let mut buf = Vec::new();
+            array.write_to_with_options(&mut buf, &options).unwrap();
+            (array, buf)
+        };
+        let (first, mut buf) = message("foo");
+        let (second, second_buf) = message("bar");
+        buf.extend_from_slice(&second_buf);
+
+        let mut decoder = Decoder::new(&buf[..], Version::Amf0);
+        assert_eq!(decoder.version(), Version::Amf0);
+        assert_eq!(decoder.decode().unwrap(), first);
+        decoder.clear_reference_table();
+        assert_eq!(decoder.decode().unwrap(), second);
+        assert_eq!(decoder.position(), buf.len());
+
+        let mut buf = Vec::new();
+        Amf3Value::Integer(1).write_to(&mut buf).unwrap();
+        Amf3Value::Integer(2).write_to(&mut buf).unwrap();
+        let mut decoder = Decoder::new(&buf[..], Version::Amf3);
+        assert_eq!(decoder.decode().unwrap(), Amf3Value::Integer(1));
+        decoder.clear_reference_table();
+        assert_eq!(decoder.decode().unwrap(), Amf3Value::Integer(2));
+    }
+
+    #[test]
+    fn tightens_limits_between_messages() {
+        let object = |n: usize| {
+            Amf3Value::anonymous_object((0..n).map(|i| (i.to_string(), Amf3Value::Null)))
+        };
+        for version in [Version::Amf0, Version::Amf3] {
+            let value = match version {
+                Version::Amf0 => Value::Amf0(object(3).to_amf0()),
+                Version::Amf3 => Value::Amf3(object(3)),
+            };
+            let mut buf = Vec::new();
+            value.write_to(&mut buf).unwrap();
+            value.write_to(&mut buf).unwrap();
+
+            let mut decoder = Decoder::new(&buf[..], version);
+            assert_eq!(decoder.decode(), Ok(value.clone()));
+
+            decoder.set_limits(DecodeLimits {
+                max_object_entries: Some(2),
+                ..Default::default()
+            });
+            assert!(
+                matches!(
+                    decoder.decode(),
+                    Err(DecodeError::CollectionTooLarge { max: 2, .. })
+                ),
+                "{:?}",
+                version
+            );
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::error::EncodeError;
+use crate::io;
+use crate::options::EncodeOptions;
+use crate::{amf0, amf3, Amf0Value, EncodeResult, Value, Version};
+
+/// An encoder of either format which is chosen at run time.
+///
+/// Unlike `Value::write_as`, this keeps the reference tables across `encode` calls
+/// (until `clear_reference_table` is called), as the per-format encoders do.
+/// A value of the other format is rejected with `EncodeError::IncompatibleVersion`
+/// unless `EncodeOptions::wrap_avmplus` is enabled.
+///
+/// # Examples
+/// ```
+/// use amf::{Amf0Value, Encoder, Value, Version};
+///
+/// let mut encoder = Encoder::new(Vec::new(), Version::Amf0);
+/// encoder.encode(&Value::Amf0(Amf0Value::Number(1.0))).unwrap();
+/// let buf = encoder.into_inner();
+/// assert_eq!(Value::read_from(&buf[..], Version::Amf0).unwrap(), Amf0Value::Number(1.0));
+/// ```
+#[derive(Debug)]
+pub struct Encoder<W> {
+    inner: Inner<W>,
+    wrap_avmplus: bool,
+}
+
+#[derive(Debug)]
+enum Inner<W> {
+    Amf0(amf0::Encoder<W>),
+    Amf3(amf3::Encoder<W>),
+}
+
+impl<W> Encoder<W> {
+    /// Returns the format version of this encoder.
+    pub fn version(&self) -> Version {
+        match self.inner {
+            Inner::Amf0(_) => Version::Amf0,
+            Inner::Amf3(_) => Version::Amf3,
+        }
+    }
+
+    /// Unwraps this `Encoder`, returning the underlying writer.
+    ///
+    /// Note that the writer is not flushed (use `finish` to flush it).
+    pub fn into_inner(self) -> W {
+        match self.inner {
+            Inner::Amf0(x) => x.into_inner(),
+            Inner::Amf3(x) => x.into_inner(),
+        }
+    }
+}
+impl<W> Encoder<W>
+where
+    W: io::Write,
+{
+    /// Makes a new instance which encodes values in the format of `version`.
+    pub fn new(inner: W, version: Version) -> Self {
+        Self::with_options(inner, version, EncodeOptions::default())
+    }
+
+    /// Makes a new instance which encodes values in the format of `version` with the given options.
+    pub fn with_options(inner: W, version: Version, options: EncodeOptions) -> Self {
+        let wrap_avmplus = options.wrap_avmplus;
+        let inner = match version {
+            Version::Amf0 => Inner::Amf0(amf0::Encoder::with_options(inner, options)),
+            Version::Amf3 => Inner::Amf3(amf3::Encoder::with_options(inner, options)),
+        };
+        Encoder {
+            inner,
+            wrap_avmplus,
+        }
+    }
+
+    /// Encodes a value.
+    pub fn encode(&mut self, value: &Value) -> EncodeResult<()> {
+        let version = self.version();
+        match (&mut self.inner, value) {
+            (Inner::Amf0(x), Value::Amf0(v)) => x.encode(v),
+            (Inner::Amf3(x), Value::Amf3(v)) => x.encode(v),
+            (Inner::Amf0(x), Value::Amf3(v)) if self.wrap_avmplus => x.encode_avmplus(v),
+            (Inner::Amf3(x), Value::Amf0(Amf0Value::AvmPlus(v))) if self.wrap_avmplus => {
+                x.encode(v)
+            }
+            (_, value) => Err(EncodeError::IncompatibleVersion {
+                type_name: value.type_name(),
+                version,
+            }),
+        }
+    }
+
+    /// Clear the reference tables of this encoder.
+    ///
+    /// The tables are only used if references are enabled by `EncodeOptions`.
+    pub fn clear_reference_table(&mut self) {
+        match self.inner {
+            Inner::Amf0(ref mut x) => x.clear_reference_table(),
+            Inner::Amf3(ref mut x) => x.clear_reference_table(),
+        }
+    }
+
+    /// Flushes the underlying writer and unwraps this `Encoder`, returning the writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.inner {
+            Inner::Amf0(x) => x.finish(),
+            Inner::Amf3(x) => x.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoder;
+    use crate::error::EncodeError;
+    use crate::options::EncodeOptions;
+    use crate::{Amf0Value, Amf3Value, Value, Version};
+
+    #[test]
+    fn encodes_both_formats() {
+        let values = [
+            Value::Amf0(Amf0Value::String("foo".to_string())),
+            Value::Amf0(Amf0Value::Number(1.0)),
+        ];
+        let mut encoder = Encoder::new(Vec::new(), Version::Amf0);
+        for v in &values {
+            encoder.encode(v).unwrap();
+        }
+        let mut expected = Vec::new();
+        Value::write_all(&values, &mut expected, Version::Amf0).unwrap();
+        assert_eq!(encoder.finish().unwrap(), expected);
+
+        let value = Value::Amf3(Amf3Value::Integer(1));
+        let mut encoder = Encoder::new(Vec::new(), Version::Amf3);
+        assert_eq!(encoder.version(), Version::Amf3);
+        encoder.encode(&value).unwrap();
+        assert_eq!(
+            Value::read_from(&encoder.into_inner()[..], Version::Amf3).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn rejects_values_of_other_format() {
+        let amf3 = Value::Amf3(Amf3Value::Integer(1));
+        let avmplus = Value::Amf0(Amf0Value::AvmPlus(Amf3Value::Integer(1)));
+        assert_eq!(
+            Encoder::new(Vec::new(), Version::Amf0).encode(&amf3),
+            Err(EncodeError::IncompatibleVersion {
+                type_name: "Integer",
+                version: Version::Amf0,
+            })
+        );
+        assert_eq!(
+            Encoder::new(Vec::new(), Version::Amf3).encode(&avmplus),
+            Err(EncodeError::IncompatibleVersion {
+                type_name: "AvmPlus",
+                version: Version::Amf3,
+            })
+        );
+
+        let options = EncodeOptions {
+            wrap_avmplus: true,
+            ..Default::default()
+        };
+        let mut encoder = Encoder::with_options(Vec::new(), Version::Amf0, options.clone());
+        encoder.encode(&amf3).unwrap();
+        let buf = encoder.into_inner();
+        assert_eq!(Value::read_from(&buf[..], Version::Amf0).unwrap(), avmplus);
+
+        let mut encoder = Encoder::with_options(Vec::new(), Version::Amf3, options);
+        encoder.encode(&avmplus).unwrap();
+        let buf = encoder.into_inner();
+        assert_eq!(Value::read_from(&buf[..], Version::Amf3).unwrap(), amf3);
+    }
+}
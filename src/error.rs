@@ -1,28 +1,67 @@
 //! AMF error.
-use std::error;
-use std::fmt;
-use std::io;
-use std::string;
+use crate::io;
+use crate::Version;
+use alloc::string::{self, String};
+use core::error;
+use core::fmt;
 
 /// AMF Decoding Error.
+///
+/// Note that cloning an `Io` error is lossy:
+/// the clone only preserves the `io::ErrorKind` and the message of the original error
+/// (i.e., the inner source error is dropped).
+///
+/// New variants may be added in future versions,
+/// so matches outside of this crate need a wildcard arm:
+///
+/// ```compile_fail,E0004
+/// use amf::error::DecodeError;
+///
+/// fn is_io(e: &DecodeError) -> bool {
+///     match e {
+///         DecodeError::Io(_) => true,
+///     }
+/// }
+/// ```
+///
+/// ```
+/// use amf::error::DecodeError;
+///
+/// fn is_io(e: &DecodeError) -> bool {
+///     match e {
+///         DecodeError::Io(_) => true,
+///         _ => false,
+///     }
+/// }
+/// ```
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecodeError {
     /// I/O error.
     Io(io::Error),
 
     /// Invalid UTF-8 error.
+    ///
+    /// The decoders of this crate return `Utf8` instead,
+    /// which also has the offset and the context of the string.
     String(string::FromUtf8Error),
 
     /// Unknown marker.
     Unknown {
         /// Unknown marker.
         marker: u8,
+
+        /// The byte offset of the marker in the input stream.
+        offset: usize,
     },
 
     /// Unsupported type.
     Unsupported {
         /// The marker of the unsupported type.
         marker: u8,
+
+        /// The byte offset of the marker in the input stream.
+        offset: usize,
     },
 
     /// Unexpected object end marker (only AMF0).
@@ -38,6 +77,10 @@ pub enum DecodeError {
     },
 
     /// Out-of-range reference index.
+    ///
+    /// Note that the index of an AMF0 reference is a `u16`, so it can only refer to
+    /// the first 65536 complex values (objects and arrays) of a message.
+    /// This is a limit of the format.
     OutOfRangeReference {
         /// Out-of-range index.
         index: usize,
@@ -55,11 +98,95 @@ pub enum DecodeError {
         millis: f64,
     },
 
+    /// An inline AMF3 date whose header declares a non-zero size (only AMF3).
+    ///
+    /// The header of an inline date is always `0x01` (see `DecodeOptions::lenient_amf3_dates`).
+    InvalidDateHeader {
+        /// The declared size.
+        size: usize,
+    },
+
     /// Unsupported externalizable type.
     ExternalizableType {
         /// The name of the externalizable type.
         name: String,
     },
+
+    /// Too deeply nested complex values.
+    ///
+    /// The decoders accept up to 256 levels of nesting.
+    DepthLimitExceeded {
+        /// The byte offset at which the limit was exceeded.
+        offset: usize,
+    },
+
+    /// Too many entries in an object (see `DecodeOptions::max_object_entries`).
+    CollectionTooLarge {
+        /// The maximum number of the entries.
+        max: usize,
+
+        /// The byte offset at which the limit was exceeded.
+        offset: usize,
+    },
+
+    /// A value of an unexpected type (e.g., a non-string event name of an FLV script tag).
+    UnexpectedType {
+        /// The expected type name.
+        expected: &'static str,
+
+        /// The type name of the decoded value.
+        actual: &'static str,
+    },
+
+    /// A `NaN` or infinite number (see `DecodeOptions::reject_non_finite_numbers`).
+    NonFiniteNumber {
+        /// The byte offset of the number in the input stream.
+        offset: usize,
+    },
+
+    /// An object which has more than one member with the same key
+    /// (see `DecodeOptions::reject_duplicate_keys`).
+    DuplicateKey {
+        /// The duplicated key.
+        key: String,
+    },
+
+    /// A value which cannot be represented in the target format of a conversion
+    /// (e.g., `amf0::Value::to_amf3` or `Value::transcode`).
+    UnrepresentableValue {
+        /// The name of the variant of the value (see `type_name` of the values).
+        type_name: &'static str,
+
+        /// The target format.
+        version: Version,
+    },
+
+    /// The total size of the allocations for a value exceeds `DecodeOptions::max_total_bytes`.
+    AllocationBudgetExceeded {
+        /// The maximum number of bytes.
+        max: usize,
+
+        /// The byte offset at which the limit was exceeded.
+        offset: usize,
+    },
+
+    /// Invalid UTF-8 in a string.
+    ///
+    /// This is returned by all the decoders of this crate (including the borrowing `ValueRef` ones).
+    Utf8 {
+        /// The error which has the offending bytes (see `FromUtf8Error::as_bytes`).
+        error: string::FromUtf8Error,
+
+        /// The byte offset of the bytes of the string (i.e., just after its length).
+        offset: usize,
+
+        /// What the string is.
+        context: StringContext,
+    },
+
+    /// Invalid base64 text (see `amf3::Value::byte_array_from_base64`).
+    #[cfg(feature = "base64")]
+    Base64(base64::DecodeError),
 }
 impl error::Error for DecodeError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
@@ -67,6 +194,7 @@ impl error::Error for DecodeError {
         match *self {
             Io(ref x) => x.source(),
             String(ref x) => x.source(),
+            Utf8 { ref error, .. } => Some(error),
             _ => None,
         }
     }
@@ -77,8 +205,12 @@ impl fmt::Display for DecodeError {
         match *self {
             Io(ref x) => write!(f, "I/O Error: {}", x),
             String(ref x) => write!(f, "Invalid String: {}", x),
-            Unknown { marker } => write!(f, "Unknown marker: {}", marker),
-            Unsupported { marker } => write!(f, "Unsupported type: maker={}", marker),
+            Unknown { marker, offset } => {
+                write!(f, "Unknown marker: {} (offset={})", marker, offset)
+            }
+            Unsupported { marker, offset } => {
+                write!(f, "Unsupported type: maker={}, offset={}", marker, offset)
+            }
             UnexpectedObjectEnd => write!(f, "Unexpected occurrence of object-end-marker"),
             CircularReference { index } => {
                 write!(f, "Circular references are unsupported: index={}", index)
@@ -88,9 +220,85 @@ impl fmt::Display for DecodeError {
                 write!(f, "Non zero time zone offset {} is unsupported", offset)
             }
             InvalidDate { millis } => write!(f, "Invalid date value {}", millis),
+            InvalidDateHeader { size } => {
+                write!(f, "Invalid date header: non-zero size {}", size)
+            }
             ExternalizableType { ref name } => {
                 write!(f, "Externalizable type {:?} is unsupported", name)
             }
+            DepthLimitExceeded { offset } => {
+                write!(f, "Too deeply nested value (offset={})", offset)
+            }
+            CollectionTooLarge { max, offset } => {
+                write!(f, "More than {} entries (offset={})", max, offset)
+            }
+            UnexpectedType { expected, actual } => {
+                write!(f, "Expected {} but got {}", expected, actual)
+            }
+            NonFiniteNumber { offset } => write!(f, "Non-finite number (offset={})", offset),
+            DuplicateKey { ref key } => write!(f, "Duplicate key {:?}", key),
+            UnrepresentableValue { type_name, version } => {
+                write!(
+                    f,
+                    "{} value cannot be represented in {:?}",
+                    type_name, version
+                )
+            }
+            AllocationBudgetExceeded { max, offset } => {
+                write!(f, "More than {} bytes allocated (offset={})", max, offset)
+            }
+            Utf8 {
+                ref error,
+                offset,
+                context,
+            } => write!(
+                f,
+                "Invalid UTF-8 in {} at offset {}: {}",
+                context, offset, error
+            ),
+            #[cfg(feature = "base64")]
+            Base64(ref x) => write!(f, "Invalid base64: {}", x),
+        }
+    }
+}
+impl Clone for DecodeError {
+    fn clone(&self) -> Self {
+        use self::DecodeError::*;
+        match *self {
+            #[cfg(feature = "std")]
+            Io(ref x) => Io(io::Error::new(x.kind(), x.to_string())),
+            #[cfg(not(feature = "std"))]
+            Io(ref x) => Io(x.clone()),
+            String(ref x) => String(x.clone()),
+            Unknown { marker, offset } => Unknown { marker, offset },
+            Unsupported { marker, offset } => Unsupported { marker, offset },
+            UnexpectedObjectEnd => UnexpectedObjectEnd,
+            CircularReference { index } => CircularReference { index },
+            OutOfRangeReference { index } => OutOfRangeReference { index },
+            NonZeroTimeZone { offset } => NonZeroTimeZone { offset },
+            InvalidDate { millis } => InvalidDate { millis },
+            InvalidDateHeader { size } => InvalidDateHeader { size },
+            ExternalizableType { ref name } => ExternalizableType { name: name.clone() },
+            DepthLimitExceeded { offset } => DepthLimitExceeded { offset },
+            CollectionTooLarge { max, offset } => CollectionTooLarge { max, offset },
+            UnexpectedType { expected, actual } => UnexpectedType { expected, actual },
+            NonFiniteNumber { offset } => NonFiniteNumber { offset },
+            DuplicateKey { ref key } => DuplicateKey { key: key.clone() },
+            UnrepresentableValue { type_name, version } => {
+                UnrepresentableValue { type_name, version }
+            }
+            AllocationBudgetExceeded { max, offset } => AllocationBudgetExceeded { max, offset },
+            Utf8 {
+                ref error,
+                offset,
+                context,
+            } => Utf8 {
+                error: error.clone(),
+                offset,
+                context,
+            },
+            #[cfg(feature = "base64")]
+            Base64(ref x) => Base64(x.clone()),
         }
     }
 }
@@ -98,14 +306,78 @@ impl PartialEq for DecodeError {
     fn eq(&self, other: &Self) -> bool {
         use self::DecodeError::*;
         match (self, other) {
-            (&Unknown { marker: x }, &Unknown { marker: y }) => x == y,
-            (&Unsupported { marker: x }, &Unsupported { marker: y }) => x == y,
+            (
+                &Unknown {
+                    marker: x,
+                    offset: o,
+                },
+                &Unknown {
+                    marker: y,
+                    offset: p,
+                },
+            ) => x == y && o == p,
+            (
+                &Unsupported {
+                    marker: x,
+                    offset: o,
+                },
+                &Unsupported {
+                    marker: y,
+                    offset: p,
+                },
+            ) => x == y && o == p,
             (&UnexpectedObjectEnd, &UnexpectedObjectEnd) => true,
             (&CircularReference { index: x }, &CircularReference { index: y }) => x == y,
             (&OutOfRangeReference { index: x }, &OutOfRangeReference { index: y }) => x == y,
             (&NonZeroTimeZone { offset: x }, &NonZeroTimeZone { offset: y }) => x == y,
             (&InvalidDate { millis: x }, &InvalidDate { millis: y }) => x == y,
-            (&ExternalizableType { name: ref x }, &ExternalizableType { name: ref y }) => x == y,
+            (&InvalidDateHeader { size: x }, &InvalidDateHeader { size: y }) => x == y,
+            (ExternalizableType { name: x }, ExternalizableType { name: y }) => x == y,
+            (&DepthLimitExceeded { offset: x }, &DepthLimitExceeded { offset: y }) => x == y,
+            (
+                &CollectionTooLarge { max: x, offset: o },
+                &CollectionTooLarge { max: y, offset: p },
+            ) => x == y && o == p,
+            (
+                &UnexpectedType {
+                    expected: x,
+                    actual: a,
+                },
+                &UnexpectedType {
+                    expected: y,
+                    actual: b,
+                },
+            ) => x == y && a == b,
+            (&NonFiniteNumber { offset: x }, &NonFiniteNumber { offset: y }) => x == y,
+            (DuplicateKey { key: x }, DuplicateKey { key: y }) => x == y,
+            (
+                &UnrepresentableValue {
+                    type_name: x,
+                    version: v,
+                },
+                &UnrepresentableValue {
+                    type_name: y,
+                    version: w,
+                },
+            ) => x == y && v == w,
+            (
+                &AllocationBudgetExceeded { max: x, offset: o },
+                &AllocationBudgetExceeded { max: y, offset: p },
+            ) => x == y && o == p,
+            (
+                Utf8 {
+                    error: x,
+                    offset: o,
+                    context: c,
+                },
+                Utf8 {
+                    error: y,
+                    offset: p,
+                    context: d,
+                },
+            ) => x == y && o == p && c == d,
+            #[cfg(feature = "base64")]
+            (Base64(x), Base64(y)) => x == y,
             _ => false,
         }
     }
@@ -120,3 +392,399 @@ impl From<string::FromUtf8Error> for DecodeError {
         DecodeError::String(f)
     }
 }
+#[cfg(feature = "base64")]
+impl From<base64::DecodeError> for DecodeError {
+    fn from(f: base64::DecodeError) -> Self {
+        DecodeError::Base64(f)
+    }
+}
+
+/// What an invalid string is (see `DecodeError::Utf8`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StringContext {
+    /// A string value (including XML values).
+    Value,
+
+    /// A key of an object or an associative array (including an AMF3 sealed member name).
+    Key,
+
+    /// A class name of a typed object or an object vector.
+    ClassName,
+}
+impl fmt::Display for StringContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StringContext::Value => write!(f, "string value"),
+            StringContext::Key => write!(f, "object key"),
+            StringContext::ClassName => write!(f, "class name"),
+        }
+    }
+}
+
+/// AMF Encoding Error.
+///
+/// Note that cloning an `Io` error is lossy (see `DecodeError`).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// I/O error.
+    Io(io::Error),
+
+    /// The value violates a structural invariant of the format.
+    Invalid(ValidationError),
+
+    /// The value cannot be written in the requested format version.
+    IncompatibleVersion {
+        /// The type name of the value.
+        type_name: &'static str,
+
+        /// The requested format version.
+        version: Version,
+    },
+
+    /// The Rust type is not registered in the `ClassRegistry` (see `registry::ClassRegistry`).
+    UnregisteredType {
+        /// The name of the Rust type (as returned by `core::any::type_name`).
+        type_name: &'static str,
+    },
+}
+impl error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            EncodeError::Io(ref x) => x.source(),
+            EncodeError::Invalid(_)
+            | EncodeError::IncompatibleVersion { .. }
+            | EncodeError::UnregisteredType { .. } => None,
+        }
+    }
+}
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::Io(ref x) => write!(f, "I/O Error: {}", x),
+            EncodeError::Invalid(ref x) => write!(f, "Invalid Value: {}", x),
+            EncodeError::IncompatibleVersion { type_name, version } => {
+                write!(f, "{} value cannot be written as {:?}", type_name, version)
+            }
+            EncodeError::UnregisteredType { type_name } => {
+                write!(f, "Unregistered type: {}", type_name)
+            }
+        }
+    }
+}
+impl Clone for EncodeError {
+    fn clone(&self) -> Self {
+        match *self {
+            #[cfg(feature = "std")]
+            EncodeError::Io(ref x) => EncodeError::Io(io::Error::new(x.kind(), x.to_string())),
+            #[cfg(not(feature = "std"))]
+            EncodeError::Io(ref x) => EncodeError::Io(x.clone()),
+            EncodeError::Invalid(ref x) => EncodeError::Invalid(x.clone()),
+            EncodeError::IncompatibleVersion { type_name, version } => {
+                EncodeError::IncompatibleVersion { type_name, version }
+            }
+            EncodeError::UnregisteredType { type_name } => {
+                EncodeError::UnregisteredType { type_name }
+            }
+        }
+    }
+}
+impl PartialEq for EncodeError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (EncodeError::Invalid(x), EncodeError::Invalid(y)) => x == y,
+            (
+                &EncodeError::IncompatibleVersion {
+                    type_name: x,
+                    version: v,
+                },
+                &EncodeError::IncompatibleVersion {
+                    type_name: y,
+                    version: w,
+                },
+            ) => x == y && v == w,
+            (
+                &EncodeError::UnregisteredType { type_name: x },
+                &EncodeError::UnregisteredType { type_name: y },
+            ) => x == y,
+            _ => false,
+        }
+    }
+}
+impl From<io::Error> for EncodeError {
+    fn from(f: io::Error) -> Self {
+        EncodeError::Io(f)
+    }
+}
+impl From<ValidationError> for EncodeError {
+    fn from(f: ValidationError) -> Self {
+        EncodeError::Invalid(f)
+    }
+}
+
+/// A non-fatal anomaly found while decoding (see `DecodeOptions::collect_warnings`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The declared count of an AMF0 ECMA array differs from the number of its entries.
+    ///
+    /// The count is ignored by the decoder
+    /// (unless `DecodeOptions::preserve_ecma_array_counts` is enabled).
+    EcmaArrayCountMismatch {
+        /// The declared count.
+        declared: u32,
+
+        /// The actual number of the entries.
+        actual: usize,
+
+        /// The byte offset of the count in the input stream.
+        offset: usize,
+    },
+
+    /// An AMF0 date has a non-zero time zone.
+    ///
+    /// The specification reserves the field and requires it to be zero.
+    NonZeroTimeZone {
+        /// The time zone offset.
+        time_zone: i16,
+
+        /// The byte offset of the time zone in the input stream.
+        offset: usize,
+    },
+
+    /// An object has more than one member with the same key.
+    DuplicateKey {
+        /// The duplicated key.
+        key: String,
+
+        /// The byte offset of the second (or later) occurrence of the key in the input stream.
+        offset: usize,
+    },
+
+    /// Bytes are left after the value (see `Value::read_from_with_warnings`).
+    TrailingBytes {
+        /// The number of the trailing bytes.
+        len: usize,
+
+        /// The byte offset of the first trailing byte in the input stream.
+        offset: usize,
+    },
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::EcmaArrayCountMismatch {
+                declared,
+                actual,
+                offset,
+            } => write!(
+                f,
+                "ECMA array declares {} entries but has {} (offset={})",
+                declared, actual, offset
+            ),
+            Warning::NonZeroTimeZone { time_zone, offset } => {
+                write!(f, "Non zero time zone {} (offset={})", time_zone, offset)
+            }
+            Warning::DuplicateKey { ref key, offset } => {
+                write!(f, "Duplicate key {:?} (offset={})", key, offset)
+            }
+            Warning::TrailingBytes { len, offset } => {
+                write!(f, "{} trailing bytes (offset={})", len, offset)
+            }
+        }
+    }
+}
+
+/// Violation of a structural invariant which makes a value unencodable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The `sealed_count` of an AMF3 object exceeds the number of its entries.
+    SealedCountOutOfRange {
+        /// The sealed member count.
+        sealed_count: usize,
+
+        /// The number of the entries.
+        entries: usize,
+    },
+
+    /// The length of a string or a collection exceeds the limit of the format.
+    TooLarge {
+        /// The length of the string or the collection.
+        len: usize,
+
+        /// The maximum length which can be encoded.
+        max: usize,
+    },
+
+    /// An element of an AMF3 `ObjectVector` is neither an `Object` nor `Null`.
+    NonObjectVectorElement {
+        /// The index of the element.
+        index: usize,
+
+        /// The name of the variant of the element (see `amf3::Value::type_name`).
+        type_name: &'static str,
+    },
+
+    /// A key of an AMF3 `Dictionary` is `Double(NaN)`, which can never be looked up.
+    NanDictionaryKey {
+        /// The index of the entry.
+        index: usize,
+    },
+
+    /// A placeholder for a payload streamed into a sink (e.g., `amf3::Value::StreamedByteArray`),
+    /// whose bytes are not in the value.
+    StreamedPlaceholder {
+        /// The name of the variant of the placeholder.
+        type_name: &'static str,
+    },
+}
+impl error::Error for ValidationError {}
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::SealedCountOutOfRange {
+                sealed_count,
+                entries,
+            } => write!(
+                f,
+                "Sealed member count {} exceeds the number of the entries {}",
+                sealed_count, entries
+            ),
+            ValidationError::TooLarge { len, max } => {
+                write!(f, "Length {} exceeds the maximum {}", len, max)
+            }
+            ValidationError::NonObjectVectorElement { index, type_name } => write!(
+                f,
+                "Element {} of an object vector is not an object (type={})",
+                index, type_name
+            ),
+            ValidationError::NanDictionaryKey { index } => {
+                write!(f, "Key of dictionary entry {} is NaN", index)
+            }
+            ValidationError::StreamedPlaceholder { type_name } => write!(
+                f,
+                "Placeholder of a streamed payload cannot be encoded (type={})",
+                type_name
+            ),
+        }
+    }
+}
+
+/// Failure of a conversion from a value into a Rust type (e.g., `f64::try_from(&value)`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+    /// The name of the variant which the conversion expected (e.g., `"Number"`).
+    pub expected: &'static str,
+
+    /// The name of the variant of the actual value (see `type_name` of the values).
+    pub found: &'static str,
+}
+impl error::Error for ConversionError {}
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expected {} but found {}", self.expected, self.found)
+    }
+}
+
+pub(crate) fn check_len(len: usize, max: usize) -> Result<(), ValidationError> {
+    if len > max {
+        Err(ValidationError::TooLarge { len, max })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConversionError, DecodeError, EncodeError, ValidationError};
+    use crate::io;
+
+    #[test]
+    fn clones_io_error() {
+        let e = DecodeError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "eof"));
+        match e.clone() {
+            DecodeError::Io(x) => {
+                assert_eq!(x.kind(), io::ErrorKind::UnexpectedEof);
+                assert_eq!(x.to_string(), "eof");
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn clones_string_error() {
+        let e = DecodeError::from(String::from_utf8(vec![0xFF]).unwrap_err());
+        match e.clone() {
+            DecodeError::String(x) => assert_eq!(x.as_bytes(), &[0xFF]),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn clones_other_errors() {
+        let errors = vec![
+            DecodeError::Unknown {
+                marker: 97,
+                offset: 3,
+            },
+            DecodeError::Unsupported {
+                marker: 4,
+                offset: 0,
+            },
+            DecodeError::UnexpectedObjectEnd,
+            DecodeError::CircularReference { index: 1 },
+            DecodeError::OutOfRangeReference { index: 2 },
+            DecodeError::NonZeroTimeZone { offset: 9 },
+            DecodeError::InvalidDate { millis: -1.0 },
+            DecodeError::InvalidDateHeader { size: 3 },
+            DecodeError::ExternalizableType {
+                name: "foo".to_string(),
+            },
+            DecodeError::DepthLimitExceeded { offset: 5 },
+            DecodeError::CollectionTooLarge { max: 2, offset: 7 },
+            DecodeError::UnexpectedType {
+                expected: "String",
+                actual: "Null",
+            },
+            DecodeError::NonFiniteNumber { offset: 1 },
+            DecodeError::DuplicateKey {
+                key: "foo".to_string(),
+            },
+            DecodeError::UnrepresentableValue {
+                type_name: "Unsupported",
+                version: crate::Version::Amf3,
+            },
+            DecodeError::AllocationBudgetExceeded { max: 8, offset: 3 },
+            DecodeError::Utf8 {
+                error: String::from_utf8(vec![0xFF]).unwrap_err(),
+                offset: 3,
+                context: super::StringContext::Key,
+            },
+        ];
+        for e in errors {
+            assert_eq!(e.clone(), e);
+        }
+    }
+    #[test]
+    fn clones_encode_error() {
+        let e = EncodeError::Io(io::Error::new(io::ErrorKind::WriteZero, "zero"));
+        match e.clone() {
+            EncodeError::Io(x) => assert_eq!(x.kind(), io::ErrorKind::WriteZero),
+            _ => panic!(),
+        }
+
+        let e = EncodeError::from(ValidationError::SealedCountOutOfRange {
+            sealed_count: 2,
+            entries: 1,
+        });
+        assert_eq!(e.clone(), e);
+    }
+    #[test]
+    fn displays_conversion_error() {
+        let e = ConversionError {
+            expected: "Number",
+            found: "String",
+        };
+        assert_eq!(e.to_string(), "Expected Number but found String");
+    }
+}
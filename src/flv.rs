@@ -0,0 +1,112 @@
+//! Helpers for the script data of FLV (Flash Video) files.
+//!
+//! This is not an FLV parser: the caller is expected to have already framed a tag
+//! and to pass its body (i.e., the bytes after the tag header) to this module.
+use crate::error::DecodeError;
+use crate::io;
+use crate::{amf0, amf3, DecodeResult, Decoder, Value, Version};
+use alloc::string::String;
+
+/// Decodes the body of an FLV script data tag, returning the event name (e.g., `"onMetaData"`)
+/// and the data.
+///
+/// A script tag body consists of two values: the event name string and the data.
+/// Both of them are encoded in the format of `version`
+/// (AMF0 in classic FLV files, and AMF0 or AMF3 in enhanced RTMP streams).
+///
+/// If the first value is not a string, `DecodeError::UnexpectedType` is returned.
+///
+/// # Examples
+/// ```
+/// use amf::{amf0, Value, Version};
+///
+/// let metadata = amf0::object([("duration", amf0::number(10))].into_iter());
+/// let mut body = Vec::new();
+/// amf0::string("onMetaData").write_to(&mut body).unwrap();
+/// metadata.write_to(&mut body).unwrap();
+///
+/// let (event, data) = amf::flv::decode_script_tag(&body[..], Version::Amf0).unwrap();
+/// assert_eq!(event, "onMetaData");
+/// assert_eq!(data, metadata);
+/// ```
+pub fn decode_script_tag<R>(reader: R, version: Version) -> DecodeResult<(String, Value)>
+where
+    R: io::Read,
+{
+    let mut decoder = Decoder::new(reader, version);
+    let name = match decoder.decode()? {
+        Value::Amf0(amf0::Value::String(s)) | Value::Amf3(amf3::Value::String(s)) => s,
+        v => {
+            return Err(DecodeError::UnexpectedType {
+                expected: "String",
+                actual: v.type_name(),
+            })
+        }
+    };
+    let data = decoder.decode()?;
+    Ok((name, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_script_tag;
+    use crate::error::DecodeError;
+    use crate::options::EncodeOptions;
+    use crate::{amf0, amf3, Value, Version};
+
+    #[test]
+    fn decodes_amf0_script_tag() {
+        let input = include_bytes!("testdata/amf0-ecma-ordinal-array.bin");
+        let mut body = Vec::new();
+        amf0::string("onMetaData").write_to(&mut body).unwrap();
+        body.extend_from_slice(input);
+
+        let (event, data) = decode_script_tag(&body[..], Version::Amf0).unwrap();
+        assert_eq!(event, "onMetaData");
+        assert_eq!(
+            data,
+            Value::read_from(&mut &input[..], Version::Amf0).unwrap()
+        );
+    }
+
+    #[test]
+    fn decodes_amf3_script_tag() {
+        let data = amf3::Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: vec![crate::Pair {
+                key: "onCuePoint".to_string(),
+                value: amf3::Value::Double(1.5),
+            }],
+        };
+        // The event name and the member name share the string reference table.
+        let mut body = Vec::new();
+        let options = EncodeOptions {
+            use_string_references: true,
+            ..Default::default()
+        };
+        let mut encoder = amf3::Encoder::with_options(&mut body, options);
+        encoder
+            .encode(&amf3::Value::String("onCuePoint".to_string()))
+            .unwrap();
+        encoder.encode(&data).unwrap();
+
+        let (event, decoded) = decode_script_tag(&body[..], Version::Amf3).unwrap();
+        assert_eq!(event, "onCuePoint");
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn rejects_non_string_event_name() {
+        let mut body = Vec::new();
+        amf0::number(1).write_to(&mut body).unwrap();
+        amf0::Value::Null.write_to(&mut body).unwrap();
+        assert_eq!(
+            decode_script_tag(&body[..], Version::Amf0),
+            Err(DecodeError::UnexpectedType {
+                expected: "String",
+                actual: "Number",
+            })
+        );
+    }
+}
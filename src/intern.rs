@@ -0,0 +1,43 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use core::borrow::Borrow;
+
+/// The maximum number of the strings which an `Interner` keeps.
+const MAX_STRINGS: usize = 1024;
+
+/// The maximum length in bytes of a string which an `Interner` keeps.
+pub(crate) const MAX_LEN: usize = 256;
+
+/// A string which can be looked up by its bytes.
+///
+/// The order of `String` is the byte-wise order, so it is consistent with that of `[u8]`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Interned(String);
+impl Borrow<[u8]> for Interned {
+    fn borrow(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+/// The strings decoded so far (see `DecodeOptions::intern_strings`).
+///
+/// The strings are looked up by their raw bytes, so a repeated string is not validated as UTF-8 again.
+/// At most `MAX_STRINGS` strings of up to `MAX_LEN` bytes are kept
+/// to bound the memory spent on untrusted input.
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    strings: BTreeSet<Interned>,
+}
+impl Interner {
+    /// Returns a copy of the interned string whose bytes are `bytes`, if any.
+    pub fn get(&self, bytes: &[u8]) -> Option<String> {
+        self.strings.get(bytes).map(|s| s.0.clone())
+    }
+
+    /// Interns `s` if it is short enough and this interner is not full.
+    pub fn insert(&mut self, s: &str) {
+        if s.len() <= MAX_LEN && self.strings.len() < MAX_STRINGS {
+            self.strings.insert(Interned(s.into()));
+        }
+    }
+}
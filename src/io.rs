@@ -0,0 +1,142 @@
+//! A minimal subset of `std::io` used in `no_std` environments.
+//!
+//! When the `std` feature is enabled, `std::io` is used instead of this module.
+use alloc::vec::Vec;
+use core::error;
+use core::fmt;
+
+/// A specialized `Result` type for I/O operations.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// A list specifying general categories of I/O error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// An operation could not be completed because an "end of file" was reached prematurely.
+    UnexpectedEof,
+
+    /// An operation could not be completed because a call to `write` returned `Ok(0)`.
+    WriteZero,
+
+    /// Any I/O error not part of this list.
+    Other,
+}
+
+/// I/O error.
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    message: &'static str,
+}
+impl Error {
+    /// Makes a new instance.
+    pub fn new(kind: ErrorKind, message: &'static str) -> Self {
+        Error { kind, message }
+    }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+impl error::Error for Error {}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        let message = match kind {
+            ErrorKind::UnexpectedEof => "failed to fill whole buffer",
+            ErrorKind::WriteZero => "failed to write whole buffer",
+            ErrorKind::Other => "other error",
+        };
+        Error { kind, message }
+    }
+}
+
+/// The `Read` trait allows for reading bytes from a source.
+pub trait Read {
+    /// Pulls some bytes from this source into the specified buffer,
+    /// returning how many bytes were read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Reads the exact number of bytes required to fill `buf`.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(Error::from(ErrorKind::UnexpectedEof)),
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+}
+impl Read for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = core::cmp::min(buf.len(), self.len());
+        let (a, b) = self.split_at(n);
+        buf[..n].copy_from_slice(a);
+        *self = b;
+        Ok(n)
+    }
+}
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+/// A trait for objects which are byte-oriented sinks.
+pub trait Write {
+    /// Writes a buffer into this writer, returning how many bytes were written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Flushes this output stream.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Attempts to write an entire buffer into this writer.
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(Error::from(ErrorKind::WriteZero)),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+}
+impl Write for Vec<u8> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// A writer which discards all written bytes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sink;
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Makes a writer which discards all written bytes.
+pub fn sink() -> Sink {
+    Sink
+}
@@ -14,24 +14,114 @@
 //! assert_eq!(number, decoded);
 //! ```
 //!
+//! # Untrusted Input
+//! The decoders never panic on malformed input; a `DecodeError` is returned instead.
+//! The nesting of complex values is limited to 256 levels
+//! (`DecodeError::DepthLimitExceeded`), and the buffers for strings and byte arrays
+//! are grown as the bytes arrive rather than reserved from the (untrusted) length.
+//!
+//! Note that referenced values are copied when decoding,
+//! so a small input can still be decoded into a large value.
+//! The `fuzz/` directory contains a `cargo fuzz` target for the decoders.
+//!
 //! # References
 //! - [AMF0 Specification](http://download.macromedia.com/pub/labs/amf/amf0_spec_121207.pdf)
 //! - [AMF3 Specification](https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf)
+//!
+//! # Features
+//! - `std` (enabled by default): Uses `std::io::Read` and `std::io::Write` for I/O.
+//!   If this feature is disabled, the crate is built as `no_std` (with `alloc`),
+//!   and the minimal I/O traits defined in the `io` module are used instead.
+//! - `tokio`: Adds `Value::read_from_async` and `Value::write_to_async`
+//!   which work with `tokio`'s asynchronous I/O traits.
+//! - `bytes`: Adds `Value::read_from_buf` and `Value::write_to_buf`
+//!   which work directly with `bytes::Buf` and `bytes::BufMut`.
+//! - `base64`: Adds `amf3::Value::byte_array_base64` and `amf3::Value::byte_array_from_base64`
+//!   which convert the raw bytes of a `ByteArray` from/to base64 text.
+//! - `indexmap`: Adds `amf0::Value::object_map` and `amf3::Value::object_map`
+//!   which view the entries of an object as an `IndexMap` (for lookup-heavy workloads).
+//! - `arbitrary`: Implements `arbitrary::Arbitrary` for `Value`, `Amf0Value` and `Amf3Value`,
+//!   which generates well-formed values (i.e., those which survive an encode/decode round trip)
+//!   for fuzzing and property tests.
+//! - `testing`: Adds the `testing` module which has helpers for the tests of downstream crates
+//!   (e.g., `testing::assert_roundtrip`).
 #![warn(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
 extern crate byteorder;
 
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Write as _;
+use core::hash::{Hash, Hasher};
+use error::Warning;
+use options::{DecodeOptions, EncodeOptions};
+#[cfg(feature = "std")]
 use std::io;
 
 pub use amf0::Value as Amf0Value;
+pub use amf0::ValueRef as Amf0ValueRef;
 pub use amf3::Value as Amf3Value;
+pub use amf3::ValueRef as Amf3ValueRef;
+pub use counting_writer::CountingWriter;
+pub use decoder::Decoder;
+pub use encoder::Encoder;
+pub use shared_value::SharedValue;
+pub use value_stream::ValueStream;
 
 pub mod amf0;
 pub mod amf3;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "bytes")]
+mod buf;
+mod counting_writer;
+mod decoder;
+mod encoder;
 pub mod error;
+pub mod flv;
+mod intern;
+#[cfg(not(feature = "std"))]
+pub mod io;
+pub mod options;
+pub mod registry;
+mod shared_value;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod value_stream;
+
+/// The upper bound of the capacity reserved in advance for a decoded collection.
+///
+/// The element count of a collection is read from the (untrusted) input,
+/// so it is not used as the initial capacity as is.
+const MAX_PREALLOCATED_ENTRIES: usize = 4096;
+
+/// The upper bound of the buffer size reserved in advance for a decoded string or byte array.
+///
+/// Like `MAX_PREALLOCATED_ENTRIES`, the length is read from the input,
+/// so the buffer is grown as the bytes actually arrive.
+const MAX_PREALLOCATED_BYTES: usize = 64 * 1024;
+
+/// The maximum nesting level of complex values accepted by the decoders.
+///
+/// This prevents a malicious input from overflowing the stack.
+const MAX_DECODE_DEPTH: usize = 256;
+
+/// The size which `approx_size` counts for each value and each key.
+///
+/// This is not smaller than the fixed part of the encoded size of any value or key.
+const APPROX_NODE_SIZE: usize = 16;
 
 /// AMF decoding result.
 pub type DecodeResult<T> = Result<T, error::DecodeError>;
 
+/// AMF encoding result.
+pub type EncodeResult<T> = Result<T, error::EncodeError>;
+
 /// Format version.
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum Version {
@@ -43,6 +133,9 @@ pub enum Version {
 }
 
 /// AMF value.
+///
+/// `Clone` is a deep copy of the whole tree.
+/// Use `SharedValue` to share a (large) value cheaply instead.
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Value {
     /// AMF0 value.
@@ -52,6 +145,107 @@ pub enum Value {
     Amf3(Amf3Value),
 }
 impl Value {
+    /// Makes an AMF0 `Number` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{Amf0Value, Amf3Value, Value};
+    ///
+    /// assert_eq!(Value::amf0_number(1.5), Value::Amf0(Amf0Value::Number(1.5)));
+    /// assert_eq!(Value::amf0_string("foo"), Value::Amf0(Amf0Value::String("foo".to_owned())));
+    /// assert_eq!(Value::amf3_integer(-1), Value::Amf3(Amf3Value::Integer(-1)));
+    /// ```
+    pub fn amf0_number(n: f64) -> Self {
+        Value::from(Amf0Value::Number(n))
+    }
+
+    /// Makes an AMF0 `Boolean` value.
+    pub fn amf0_boolean(b: bool) -> Self {
+        Value::from(Amf0Value::Boolean(b))
+    }
+
+    /// Makes an AMF0 `String` value.
+    pub fn amf0_string<T>(t: T) -> Self
+    where
+        String: From<T>,
+    {
+        Value::from(Amf0Value::String(From::from(t)))
+    }
+
+    /// Makes an AMF0 `Null` value.
+    pub fn amf0_null() -> Self {
+        Value::from(Amf0Value::Null)
+    }
+
+    /// Makes an AMF0 `Date` value whose time zone is zero.
+    pub fn amf0_date(unix_time: core::time::Duration) -> Self {
+        Value::from(Amf0Value::Date {
+            unix_time,
+            time_zone: 0,
+        })
+    }
+
+    /// Makes an AMF0 `XmlDocument` value.
+    pub fn amf0_xml_document<T>(t: T) -> Self
+    where
+        String: From<T>,
+    {
+        Value::from(Amf0Value::XmlDocument(From::from(t)))
+    }
+
+    /// Makes an AMF3 `Integer` value.
+    ///
+    /// Note that only 29-bit integers can be encoded as AMF3 integers
+    /// (see `Amf3Value::integer_or_double`).
+    pub fn amf3_integer(n: i32) -> Self {
+        Value::from(Amf3Value::Integer(n))
+    }
+
+    /// Makes an AMF3 `Double` value.
+    pub fn amf3_double(n: f64) -> Self {
+        Value::from(Amf3Value::Double(n))
+    }
+
+    /// Makes an AMF3 `Boolean` value.
+    pub fn amf3_boolean(b: bool) -> Self {
+        Value::from(Amf3Value::Boolean(b))
+    }
+
+    /// Makes an AMF3 `String` value.
+    pub fn amf3_string<T>(t: T) -> Self
+    where
+        String: From<T>,
+    {
+        Value::from(Amf3Value::String(From::from(t)))
+    }
+
+    /// Makes an AMF3 `Null` value.
+    pub fn amf3_null() -> Self {
+        Value::from(Amf3Value::Null)
+    }
+
+    /// Makes an AMF3 `Date` value.
+    pub fn amf3_date(unix_time: core::time::Duration) -> Self {
+        Value::from(Amf3Value::Date { unix_time })
+    }
+
+    /// Makes an AMF3 `Xml` value.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{Amf3Value, Value};
+    ///
+    /// let value = Value::amf3_xml("<a/>");
+    /// assert_eq!(value, Amf3Value::xml("<a/>"));
+    /// assert!(value.into_amf3().unwrap().is_xml());
+    /// ```
+    pub fn amf3_xml<T>(text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Value::from(Amf3Value::xml(text))
+    }
+
     /// Reads an AMF encoded `Value` from `reader`.
     ///
     /// Note that reference objects are copied in the decoding phase
@@ -67,8 +261,92 @@ impl Value {
         }
     }
 
+    /// Reads an AMF encoded `Value` from `reader`, collecting non-fatal anomalies as `Warning`s.
+    ///
+    /// This is intended for diagnostic tools.
+    /// Unlike `read_from`, this reads `reader` to its end,
+    /// and reports the bytes left after the value as `Warning::TrailingBytes`.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::error::Warning;
+    /// use amf::{Value, Version};
+    ///
+    /// // An AMF0 number followed by an extra byte.
+    /// let input = [0x00, 0x3F, 0xF0, 0, 0, 0, 0, 0, 0, 0xFF];
+    /// let (value, warnings) = Value::read_from_with_warnings(&input[..], Version::Amf0).unwrap();
+    /// assert_eq!(value.try_as_f64(), Some(1.0));
+    /// assert_eq!(warnings, [Warning::TrailingBytes { len: 1, offset: 9 }]);
+    /// ```
+    pub fn read_from_with_warnings<R>(
+        reader: R,
+        version: Version,
+    ) -> DecodeResult<(Self, Vec<Warning>)>
+    where
+        R: io::Read,
+    {
+        let options = DecodeOptions {
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let mut decoder = Decoder::with_options(reader, version, options);
+        let value = decoder.decode()?;
+        let mut warnings = decoder.take_warnings();
+        let offset = decoder.position();
+
+        let mut reader = decoder.into_inner();
+        let mut buf = [0; 256];
+        let mut len = 0;
+        loop {
+            match reader.read(&mut buf)? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        if len > 0 {
+            warnings.push(Warning::TrailingBytes { len, offset });
+        }
+        Ok((value, warnings))
+    }
+
+    /// Returns the first byte of `input` if it is a known AMF0 or AMF3 marker.
+    ///
+    /// This does not check whether the rest of `input` is a valid value.
+    pub fn peek_marker(input: &[u8]) -> Option<u8> {
+        input
+            .first()
+            .copied()
+            .filter(|&m| amf0::is_leading_marker(m) || amf3::is_marker(m))
+    }
+
+    /// Guesses the format version of the value at the head of `input` from its first byte.
+    ///
+    /// The AMF0 and AMF3 markers share the same range of bytes, so this is only a best guess:
+    /// `Version::Amf3` is returned if the byte cannot be the first one of an AMF0 value
+    /// (e.g., the `MOVIECLIP` or `OBJECT_END` marker of AMF0), and `Version::Amf0` otherwise.
+    /// Note that AMF3 values are usually wrapped in an AMF0 `AVMPLUS_OBJECT` marker.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{Value, Version};
+    ///
+    /// assert_eq!(Value::peek_version(&[0x02, 0x00, 0x00]), Some(Version::Amf0));
+    /// assert_eq!(Value::peek_version(&[0x0D, 0x01, 0x00]), Some(Version::Amf3));
+    /// assert_eq!(Value::peek_version(&[0xFF]), None);
+    /// assert_eq!(Value::peek_version(&[]), None);
+    /// ```
+    pub fn peek_version(input: &[u8]) -> Option<Version> {
+        Self::peek_marker(input).map(|m| {
+            if amf0::is_leading_marker(m) {
+                Version::Amf0
+            } else {
+                Version::Amf3
+            }
+        })
+    }
+
     /// Writes the AMF encoded bytes of this value to `writer`.
-    pub fn write_to<W>(&self, writer: W) -> io::Result<()>
+    pub fn write_to<W>(&self, writer: W) -> EncodeResult<()>
     where
         W: io::Write,
     {
@@ -78,6 +356,194 @@ impl Value {
         }
     }
 
+    /// Writes the AMF encoded bytes of this value to `writer` in the format of `version`.
+    ///
+    /// An AMF3 value written as AMF0 is wrapped in the `AVMPLUS_OBJECT` marker,
+    /// and an AMF0 `AvmPlus` value written as AMF3 is unwrapped.
+    /// The other AMF0 values cannot be written as AMF3
+    /// (`EncodeError::IncompatibleVersion` is returned).
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{Amf0Value, Amf3Value, Value, Version};
+    ///
+    /// let value = Value::from(Amf3Value::Integer(1));
+    /// let mut buf = Vec::new();
+    /// value.write_as(&mut buf, Version::Amf0).unwrap();
+    ///
+    /// let decoded = Value::read_from(&mut &buf[..], Version::Amf0).unwrap();
+    /// assert_eq!(decoded, Value::from(Amf0Value::AvmPlus(Amf3Value::Integer(1))));
+    /// ```
+    pub fn write_as<W>(&self, writer: W, version: Version) -> EncodeResult<()>
+    where
+        W: io::Write,
+    {
+        match (self, version) {
+            (Value::Amf0(x), Version::Amf0) => x.write_to(writer),
+            (Value::Amf3(x), Version::Amf3) => x.write_to(writer),
+            (Value::Amf3(x), Version::Amf0) => amf0::Encoder::new(writer).encode_avmplus(x),
+            (Value::Amf0(Amf0Value::AvmPlus(x)), Version::Amf3) => x.write_to(writer),
+            (Value::Amf0(x), Version::Amf3) => Err(error::EncodeError::IncompatibleVersion {
+                type_name: x.type_name(),
+                version,
+            }),
+        }
+    }
+
+    /// Decodes one value in the format of `from` from `reader`,
+    /// and re-encodes it in the format of `to` to `writer`.
+    ///
+    /// The value is converted by `Amf0Value::to_amf3` or `Amf3Value::to_amf0`
+    /// (so `DecodeError::UnrepresentableValue` is returned for an AMF0 `Unsupported` value).
+    /// Errors in encoding are also returned as `DecodeError`s:
+    /// an I/O error as `DecodeError::Io` and the others as `DecodeError::UnrepresentableValue`.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{amf0, Amf3Value, Value, Version};
+    ///
+    /// let mut input = Vec::new();
+    /// amf0::number(1.5).write_to(&mut input).unwrap();
+    ///
+    /// let mut output = Vec::new();
+    /// Value::transcode(&input[..], Version::Amf0, Version::Amf3, &mut output).unwrap();
+    /// assert_eq!(Value::read_from(&output[..], Version::Amf3).unwrap(), Amf3Value::Double(1.5));
+    /// ```
+    pub fn transcode<R, W>(reader: R, from: Version, to: Version, writer: W) -> DecodeResult<()>
+    where
+        R: io::Read,
+        W: io::Write,
+    {
+        let value = match (Value::read_from(reader, from)?, to) {
+            (Value::Amf0(x), Version::Amf3) => Value::Amf3(x.to_amf3()?),
+            (Value::Amf3(x), Version::Amf0) => Value::Amf0(x.to_amf0()),
+            (value, _) => value,
+        };
+        value.write_to(writer).map_err(|e| match e {
+            error::EncodeError::Io(e) => error::DecodeError::Io(e),
+            _ => error::DecodeError::UnrepresentableValue {
+                type_name: value.type_name(),
+                version: to,
+            },
+        })
+    }
+
+    /// Writes the AMF encoded bytes of `values` back-to-back to `writer` in the format of `version`.
+    ///
+    /// This is how a sequence of values such as an RTMP command payload is encoded.
+    /// Each value is written as `write_as` does,
+    /// so the values do not share reference tables with each other.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{Amf0Value, Value, Version};
+    ///
+    /// let values = [
+    ///     Value::from(Amf0Value::String("connect".to_string())),
+    ///     Value::from(Amf0Value::Number(1.0)),
+    /// ];
+    /// let mut buf = Vec::new();
+    /// Value::write_all(&values, &mut buf, Version::Amf0).unwrap();
+    ///
+    /// let mut reader = &buf[..];
+    /// assert_eq!(Value::read_from(&mut reader, Version::Amf0).unwrap(), values[0]);
+    /// assert_eq!(Value::read_from(&mut reader, Version::Amf0).unwrap(), values[1]);
+    /// ```
+    pub fn write_all<W>(values: &[Value], mut writer: W, version: Version) -> EncodeResult<()>
+    where
+        W: io::Write,
+    {
+        for value in values {
+            value.write_as(&mut writer, version)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the AMF encoded bytes of this value to `writer` with the given options.
+    pub fn write_to_with_options<W>(&self, writer: W, options: &EncodeOptions) -> EncodeResult<()>
+    where
+        W: io::Write,
+    {
+        match *self {
+            Value::Amf0(ref x) => x.write_to_with_options(writer, options),
+            Value::Amf3(ref x) => x.write_to_with_options(writer, options),
+        }
+    }
+
+    /// Checks the structural invariants which `write_to` requires.
+    pub fn validate(&self) -> Result<(), error::ValidationError> {
+        match *self {
+            Value::Amf0(ref x) => x.validate(),
+            Value::Amf3(ref x) => x.validate(),
+        }
+    }
+
+    /// Writes the AMF encoded bytes of this value to `writer`,
+    /// returning the number of bytes written.
+    pub fn write_to_counting<W>(&self, writer: W) -> EncodeResult<usize>
+    where
+        W: io::Write,
+    {
+        let mut writer = CountingWriter::with_inner(writer);
+        self.write_to(&mut writer)?;
+        Ok(writer.count())
+    }
+
+    /// Returns a hex dump of the bytes which this value encodes to in the format of `version`.
+    ///
+    /// Each line of the dump consists of the offset, up to 16 bytes in hex,
+    /// and the printable ASCII characters of those bytes (like `hexdump -C`).
+    /// Unlike `Debug`, this shows the wire bytes rather than the value itself.
+    ///
+    /// # Examples
+    /// ```
+    /// use amf::{Amf0Value, Value, Version};
+    ///
+    /// let dump = Value::from(Amf0Value::Boolean(true)).to_hex_dump(Version::Amf0).unwrap();
+    /// assert_eq!(dump, "00000000  01 01                                             |..|\n");
+    /// ```
+    pub fn to_hex_dump(&self, version: Version) -> EncodeResult<String> {
+        let mut buf = Vec::new();
+        self.write_as(&mut buf, version)?;
+        Ok(hex_dump(&buf))
+    }
+
+    /// Returns the name of the variant of the inner value (e.g., `"Number"` or `"ByteArray"`).
+    ///
+    /// See `amf0::Value::type_name` and `amf3::Value::type_name`.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Amf0(ref x) => x.type_name(),
+            Value::Amf3(ref x) => x.type_name(),
+        }
+    }
+
+    /// Returns the format version of the inner value.
+    ///
+    /// Note that an AMF3 value wrapped in `Amf0Value::AvmPlus` is an AMF0 value.
+    pub fn version(&self) -> Version {
+        match *self {
+            Value::Amf0(_) => Version::Amf0,
+            Value::Amf3(_) => Version::Amf3,
+        }
+    }
+
+    /// Unwraps the inner value if this is an AMF0 value.
+    pub fn into_amf0(self) -> Option<Amf0Value> {
+        match self {
+            Value::Amf0(x) => Some(x),
+            Value::Amf3(_) => None,
+        }
+    }
+
+    /// Unwraps the inner value if this is an AMF3 value.
+    pub fn into_amf3(self) -> Option<Amf3Value> {
+        match self {
+            Value::Amf0(_) => None,
+            Value::Amf3(x) => Some(x),
+        }
+    }
+
     /// Tries to convert the value as a `str` reference.
     pub fn try_as_str(&self) -> Option<&str> {
         match *self {
@@ -94,6 +560,31 @@ impl Value {
         }
     }
 
+    /// Returns `true` if this is a finite number.
+    ///
+    /// See `Amf0Value::is_finite_number` and `Amf3Value::is_finite_number`.
+    pub fn is_finite_number(&self) -> bool {
+        match *self {
+            Value::Amf0(ref x) => x.is_finite_number(),
+            Value::Amf3(ref x) => x.is_finite_number(),
+        }
+    }
+
+    /// Returns the number of the entries if this is a collection value.
+    ///
+    /// See `Amf0Value::len` and `Amf3Value::len` for details.
+    pub fn len(&self) -> Option<usize> {
+        match *self {
+            Value::Amf0(ref x) => x.len(),
+            Value::Amf3(ref x) => x.len(),
+        }
+    }
+
+    /// Returns `Some(true)` if this is a collection value which has no entries.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|n| n == 0)
+    }
+
     /// Tries to convert the value as an iterator of the contained values.
     pub fn try_into_values(self) -> Result<Box<dyn Iterator<Item = Value>>, Self> {
         match self {
@@ -106,6 +597,89 @@ impl Value {
         }
     }
 
+    /// Returns a quick estimate of the number of bytes which `write_to` writes for this value.
+    ///
+    /// See `Amf0Value::approx_size` and `Amf3Value::approx_size` for details.
+    pub fn approx_size(&self) -> usize {
+        match *self {
+            Value::Amf0(ref x) => x.approx_size(),
+            Value::Amf3(ref x) => x.approx_size(),
+        }
+    }
+
+    /// Returns the maximum nesting level of the value.
+    ///
+    /// See `Amf0Value::depth` and `Amf3Value::depth` for details.
+    pub fn depth(&self) -> usize {
+        match *self {
+            Value::Amf0(ref x) => x.depth(),
+            Value::Amf3(ref x) => x.depth(),
+        }
+    }
+
+    /// Returns the number of the values in the value (including itself).
+    ///
+    /// See `Amf0Value::count_nodes` and `Amf3Value::count_nodes` for details.
+    pub fn count_nodes(&self) -> usize {
+        match *self {
+            Value::Amf0(ref x) => x.count_nodes(),
+            Value::Amf3(ref x) => x.count_nodes(),
+        }
+    }
+
+    /// Compares two values with a total order.
+    ///
+    /// `Amf0` values are less than `Amf3` values.
+    /// See `Amf0Value::cmp_canonical` and `Amf3Value::cmp_canonical` for the rest.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Amf0(a), Value::Amf0(b)) => a.cmp_canonical(b),
+            (Value::Amf3(a), Value::Amf3(b)) => a.cmp_canonical(b),
+            (Value::Amf0(_), Value::Amf3(_)) => Ordering::Less,
+            (Value::Amf3(_), Value::Amf0(_)) => Ordering::Greater,
+        }
+    }
+
+    /// Returns `true` if this value is equal to `other` with a tolerance of `epsilon` for floating point numbers.
+    ///
+    /// See `Amf0Value::approx_eq` and `Amf3Value::approx_eq` for details.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        match (self, other) {
+            (Value::Amf0(a), Value::Amf0(b)) => a.approx_eq(b, epsilon),
+            (Value::Amf3(a), Value::Amf3(b)) => a.approx_eq(b, epsilon),
+            _ => false,
+        }
+    }
+
+    /// Feeds this value into `state` in a way consistent with `cmp_canonical`.
+    ///
+    /// See `Amf0Value::hash_canonical` and `Amf3Value::hash_canonical` for details.
+    pub fn hash_canonical<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            Value::Amf0(ref x) => {
+                state.write_u8(0);
+                x.hash_canonical(state);
+            }
+            Value::Amf3(ref x) => {
+                state.write_u8(1);
+                x.hash_canonical(state);
+            }
+        }
+    }
+
+    /// Removes the entries of an object-like value whose keys are rejected by `f`.
+    ///
+    /// See `Amf0Value::retain_keys` and `Amf3Value::retain_keys` for details.
+    pub fn retain_keys<F>(&mut self, f: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        match *self {
+            Value::Amf0(ref mut x) => x.retain_keys(f),
+            Value::Amf3(ref mut x) => x.retain_keys(f),
+        }
+    }
+
     /// Tries to convert the value as an iterator of the contained pairs.
     pub fn try_into_pairs(self) -> Result<Box<dyn Iterator<Item = (String, Value)>>, Self> {
         match self {
@@ -118,6 +692,26 @@ impl Value {
         }
     }
 }
+impl IntoIterator for Value {
+    type Item = Value;
+    type IntoIter = Box<dyn Iterator<Item = Value>>;
+
+    /// Converts the value into an iterator of the contained values.
+    ///
+    /// Unlike `try_into_values`, this yields nothing for a non-collection value.
+    fn into_iter(self) -> Self::IntoIter {
+        self.try_into_values()
+            .unwrap_or_else(|_| Box::new(core::iter::empty()))
+    }
+}
+impl Default for Value {
+    /// Returns an AMF0 `Null` (i.e., `Value::Amf0(Amf0Value::Null)`).
+    ///
+    /// AMF0 is chosen because it is the default format of RTMP commands.
+    fn default() -> Self {
+        Value::Amf0(Amf0Value::default())
+    }
+}
 impl From<Amf0Value> for Value {
     fn from(f: Amf0Value) -> Value {
         Value::Amf0(f)
@@ -128,6 +722,120 @@ impl From<Amf3Value> for Value {
         Value::Amf3(f)
     }
 }
+impl PartialEq<Amf0Value> for Value {
+    fn eq(&self, other: &Amf0Value) -> bool {
+        matches!(*self, Value::Amf0(ref x) if x == other)
+    }
+}
+impl PartialEq<Amf3Value> for Value {
+    fn eq(&self, other: &Amf3Value) -> bool {
+        matches!(*self, Value::Amf3(ref x) if x == other)
+    }
+}
+impl PartialEq<Value> for Amf0Value {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+impl PartialEq<Value> for Amf3Value {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+
+/// Wrapper of `Value` which implements `Eq` and `Hash`.
+///
+/// Two wrapped values are equal if `Value::cmp_canonical` returns `Ordering::Equal`,
+/// and they are hashed by `Value::hash_canonical`.
+/// Thus, unlike `Value`'s `PartialEq`, `NaN` is equal to itself (if the bit patterns are the same)
+/// and `0.0` is not equal to `-0.0`.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashSet;
+/// use amf::{Amf3Value, HashableValue, Value};
+///
+/// let mut set = HashSet::new();
+/// set.insert(HashableValue(Value::from(Amf3Value::Double(f64::NAN))));
+/// assert!(set.contains(&HashableValue(Value::from(Amf3Value::Double(f64::NAN)))));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HashableValue(pub Value);
+impl PartialEq for HashableValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.cmp_canonical(&other.0) == Ordering::Equal
+    }
+}
+impl Eq for HashableValue {}
+impl Hash for HashableValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_canonical(state);
+    }
+}
+impl From<Value> for HashableValue {
+    fn from(f: Value) -> Self {
+        HashableValue(f)
+    }
+}
+
+/// AMF value which borrows its strings and byte arrays from the decoded buffer.
+///
+/// # Examples
+/// ```
+/// use amf::{Value, ValueRef, Amf0Value, Version};
+///
+/// let mut buf = Vec::new();
+/// Value::from(Amf0Value::String("foo".to_owned())).write_to(&mut buf).unwrap();
+///
+/// let decoded = ValueRef::read_from_slice(&buf, Version::Amf0).unwrap();
+/// assert_eq!(decoded.try_as_str(), Some("foo"));
+/// assert_eq!(decoded.to_owned(), Value::read_from(&mut &buf[..], Version::Amf0).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum ValueRef<'a> {
+    /// AMF0 value.
+    Amf0(Amf0ValueRef<'a>),
+
+    /// AMF3 value.
+    Amf3(Amf3ValueRef<'a>),
+}
+impl<'a> ValueRef<'a> {
+    /// Reads an AMF encoded `ValueRef` from `input` without copying strings and byte arrays.
+    ///
+    /// Reference objects are copied in the same way as `Value::read_from`.
+    pub fn read_from_slice(input: &'a [u8], version: Version) -> DecodeResult<Self> {
+        match version {
+            Version::Amf0 => Amf0ValueRef::read_from_slice(input).map(ValueRef::Amf0),
+            Version::Amf3 => Amf3ValueRef::read_from_slice(input).map(ValueRef::Amf3),
+        }
+    }
+
+    /// Converts this value into the equivalent owned `Value`.
+    pub fn to_owned(&self) -> Value {
+        match *self {
+            ValueRef::Amf0(ref x) => Value::Amf0(x.to_owned()),
+            ValueRef::Amf3(ref x) => Value::Amf3(x.to_owned()),
+        }
+    }
+
+    /// Tries to convert the value as a `str` reference.
+    pub fn try_as_str(&self) -> Option<&'a str> {
+        match *self {
+            ValueRef::Amf0(ref x) => x.try_as_str(),
+            ValueRef::Amf3(ref x) => x.try_as_str(),
+        }
+    }
+}
+impl<'a> From<Amf0ValueRef<'a>> for ValueRef<'a> {
+    fn from(f: Amf0ValueRef<'a>) -> ValueRef<'a> {
+        ValueRef::Amf0(f)
+    }
+}
+impl<'a> From<Amf3ValueRef<'a>> for ValueRef<'a> {
+    fn from(f: Amf3ValueRef<'a>) -> ValueRef<'a> {
+        ValueRef::Amf3(f)
+    }
+}
 
 /// Key-value pair.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -138,6 +846,25 @@ pub struct Pair<K, V> {
     /// The value of the pair.
     pub value: V,
 }
+impl<K, V> Pair<K, V> {
+    /// Converts this pair into a `(key, value)` tuple.
+    pub fn into_tuple(self) -> (K, V) {
+        (self.key, self.value)
+    }
+}
+impl<K, V> From<(K, V)> for Pair<K, V> {
+    /// Makes a pair from a `(key, value)` tuple.
+    ///
+    /// A vector of pairs can be built by `tuples.into_iter().map(Pair::from).collect()`.
+    fn from((key, value): (K, V)) -> Self {
+        Pair { key, value }
+    }
+}
+impl<K, V> From<Pair<K, V>> for (K, V) {
+    fn from(f: Pair<K, V>) -> Self {
+        f.into_tuple()
+    }
+}
 
 fn iter_boxed<I, T>(iter: I) -> Box<dyn Iterator<Item = T>>
 where
@@ -145,3 +872,480 @@ where
 {
     Box::new(iter)
 }
+
+fn cmp_slices<T, F>(a: &[T], b: &[T], mut f: F) -> Ordering
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    for (x, y) in a.iter().zip(b) {
+        match f(x, y) {
+            Ordering::Equal => {}
+            o => return o,
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn approx_eq_f64(a: f64, b: f64, epsilon: f64) -> bool {
+    a == b || (a - b).abs() <= epsilon
+}
+
+fn approx_eq_slices<T, F>(a: &[T], b: &[T], mut f: F) -> bool
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| f(x, y))
+}
+
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x} ", i * 16);
+        for (j, b) in line.iter().enumerate() {
+            if j % 8 == 0 {
+                dump.push(' ');
+            }
+            let _ = write!(dump, "{:02x} ", b);
+        }
+        for j in line.len()..16 {
+            dump.push_str(if j % 8 == 0 { "    " } else { "   " });
+        }
+        dump.push_str(" |");
+        dump.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                char::from(b)
+            } else {
+                '.'
+            }
+        }));
+        dump.push_str("|\n");
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Amf0Value, Amf3Value, HashableValue, Pair, Value, ValueRef, Version};
+    use crate::error::{DecodeError, EncodeError, Warning};
+    use crate::io;
+    use std::collections::HashSet;
+    use std::f64;
+
+    fn h(v: Amf3Value) -> HashableValue {
+        HashableValue(Value::Amf3(v))
+    }
+
+    #[test]
+    fn hash_set_dedups_equal_values() {
+        let object = |n: f64| Amf3Value::Object {
+            class_name: None,
+            sealed_count: 0,
+            entries: vec![Pair {
+                key: "x".to_string(),
+                value: Amf3Value::Double(n),
+            }],
+        };
+
+        let mut set = HashSet::new();
+        assert!(set.insert(h(Amf3Value::Double(f64::NAN))));
+        assert!(!set.insert(h(Amf3Value::Double(f64::NAN))));
+        assert!(set.insert(h(object(1.0))));
+        assert!(!set.insert(h(object(1.0))));
+        assert!(set.insert(h(Amf3Value::String("foo".to_string()))));
+        assert!(!set.insert(h(Amf3Value::String("foo".to_string()))));
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn hash_set_keeps_distinct_values() {
+        let values = [
+            h(Amf3Value::Double(0.0)),
+            h(Amf3Value::Double(-0.0)),
+            h(Amf3Value::Integer(0)),
+            h(Amf3Value::Null),
+            h(Amf3Value::Undefined),
+            h(Amf3Value::String("".to_string())),
+            h(Amf3Value::Xml("".to_string())),
+            HashableValue(Value::Amf0(Amf0Value::Number(0.0))),
+            HashableValue(Value::Amf0(Amf0Value::Null)),
+        ];
+        let set = values.iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(set.len(), values.len());
+    }
+
+    #[test]
+    fn returns_type_names() {
+        assert_eq!(Value::from(Amf0Value::Number(1.0)).type_name(), "Number");
+        assert_eq!(
+            Value::from(Amf3Value::ByteArray(vec![])).type_name(),
+            "ByteArray"
+        );
+    }
+
+    #[test]
+    fn writes_amf3_value_as_amf0() {
+        let input = include_bytes!("testdata/amf0-avmplus-object.bin");
+        let array = Amf3Value::Array {
+            assoc_entries: vec![],
+            dense_entries: (1..4).map(Amf3Value::Integer).collect(),
+        };
+
+        let mut buf = Vec::new();
+        Value::from(array.clone())
+            .write_as(&mut buf, Version::Amf0)
+            .unwrap();
+        assert_eq!(buf, &input[..]);
+        assert_eq!(
+            Value::read_from(&mut &buf[..], Version::Amf0).unwrap(),
+            Value::from(array.clone().into_amf0_avmplus())
+        );
+
+        let mut buf = Vec::new();
+        Value::from(array.clone().into_amf0_avmplus())
+            .write_as(&mut buf, Version::Amf3)
+            .unwrap();
+        assert_eq!(Amf3Value::read_from(&mut &buf[..]).unwrap(), array);
+
+        assert_eq!(
+            Value::from(Amf0Value::Null).write_as(&mut Vec::new(), Version::Amf3),
+            Err(EncodeError::IncompatibleVersion {
+                type_name: "Null",
+                version: Version::Amf3
+            })
+        );
+    }
+
+    macro_rules! decode_truncated {
+        ($file:expr, $version:expr) => {{
+            let input = include_bytes!(concat!("testdata/", $file));
+            match Value::read_from(&mut &input[..], $version) {
+                Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                other => panic!("{:?}", other),
+            }
+            match ValueRef::read_from_slice(&input[..], $version) {
+                Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                other => panic!("{:?}", other),
+            }
+        }};
+    }
+
+    #[test]
+    fn writes_values_back_to_back() {
+        let values = [
+            Value::from(Amf0Value::String("_result".to_string())),
+            Value::from(Amf0Value::Number(1.0)),
+            Value::from(Amf0Value::Object {
+                class_name: None,
+                entries: vec![Pair {
+                    key: "code".to_string(),
+                    value: Amf0Value::String("NetConnection.Connect.Success".to_string()),
+                }],
+            }),
+            Value::from(Amf3Value::Integer(2)),
+        ];
+        let mut buf = Vec::new();
+        Value::write_all(&values, &mut buf, Version::Amf0).unwrap();
+
+        let mut reader = &buf[..];
+        for value in &values[..3] {
+            assert_eq!(
+                &Value::read_from(&mut reader, Version::Amf0).unwrap(),
+                value
+            );
+        }
+        assert_eq!(
+            Value::read_from(&mut reader, Version::Amf0).unwrap(),
+            Value::from(Amf3Value::Integer(2).into_amf0_avmplus())
+        );
+        assert!(reader.is_empty());
+
+        assert!(Value::write_all(&values, &mut Vec::new(), Version::Amf3).is_err());
+    }
+
+    #[test]
+    fn peeks_leading_markers() {
+        let peek = |input: &[u8]| (Value::peek_marker(input), Value::peek_version(input));
+        assert_eq!(
+            peek(include_bytes!("testdata/amf0-number.bin")),
+            (Some(0x00), Some(Version::Amf0))
+        );
+        assert_eq!(
+            peek(include_bytes!("testdata/amf0-avmplus-object.bin")),
+            (Some(0x11), Some(Version::Amf0))
+        );
+        assert_eq!(
+            peek(include_bytes!("testdata/amf3-vector-int.bin")),
+            (Some(0x0D), Some(Version::Amf3))
+        );
+        assert_eq!(
+            peek(include_bytes!("testdata/amf3-array-ref.bin")),
+            (Some(0x09), Some(Version::Amf3))
+        );
+        assert_eq!(
+            peek(include_bytes!("testdata/amf0-movieclip.bin")),
+            (Some(0x04), Some(Version::Amf3))
+        );
+        assert_eq!(
+            peek(include_bytes!("testdata/amf0-unknown-marker.bin")),
+            (None, None)
+        );
+        assert_eq!(peek(&[]), (None, None));
+
+        // Ambiguous: an AMF3 object starts with the AMF0 strict array marker.
+        assert_eq!(
+            peek(include_bytes!("testdata/amf3-dynamic-object.bin")),
+            (Some(0x0A), Some(Version::Amf0))
+        );
+    }
+
+    #[test]
+    fn converts_pairs_from_and_to_tuples() {
+        let pair = Pair::from(("foo", 1));
+        assert_eq!(
+            pair,
+            Pair {
+                key: "foo",
+                value: 1
+            }
+        );
+        assert_eq!(pair.clone().into_tuple(), ("foo", 1));
+        assert_eq!(<(&str, i32)>::from(pair), ("foo", 1));
+
+        let entries: Vec<Pair<String, Amf0Value>> = vec![
+            ("a".to_string(), Amf0Value::Null),
+            ("b".to_string(), Amf0Value::Number(2.0)),
+        ]
+        .into_iter()
+        .map(Pair::from)
+        .collect();
+        assert_eq!(
+            entries,
+            [
+                Pair {
+                    key: "a".to_string(),
+                    value: Amf0Value::Null
+                },
+                Pair {
+                    key: "b".to_string(),
+                    value: Amf0Value::Number(2.0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_huge_lengths_without_allocating() {
+        decode_truncated!("fuzz-amf0-huge-long-string.bin", Version::Amf0);
+        decode_truncated!("fuzz-amf0-huge-xml-document.bin", Version::Amf0);
+        decode_truncated!("fuzz-amf3-huge-string.bin", Version::Amf3);
+        decode_truncated!("fuzz-amf3-huge-byte-array.bin", Version::Amf3);
+        decode_truncated!("fuzz-amf3-huge-trait.bin", Version::Amf3);
+    }
+
+    #[test]
+    fn rejects_too_deeply_nested_values() {
+        fn assert_depth_limit_exceeded(input: &[u8], version: Version) {
+            let e = Value::read_from(&mut &input[..], version).unwrap_err();
+            assert!(
+                matches!(e, DecodeError::DepthLimitExceeded { .. }),
+                "{:?}",
+                e
+            );
+            assert_eq!(ValueRef::read_from_slice(input, version).unwrap_err(), e);
+        }
+
+        // Strict arrays which contain one strict array each.
+        let amf0 = [0x0A, 0, 0, 0, 1].repeat(100_000);
+        assert_depth_limit_exceeded(&amf0, Version::Amf0);
+
+        // Dense arrays which contain one dense array each.
+        let amf3 = [0x09, 0x03, 0x01].repeat(100_000);
+        assert_depth_limit_exceeded(&amf3, Version::Amf3);
+
+        // The nesting level is carried over to AVM+ values.
+        let mut mixed = [0x0A, 0, 0, 0, 1].repeat(200);
+        mixed.push(0x11);
+        mixed.extend_from_slice(&[0x09, 0x03, 0x01].repeat(100));
+        assert_depth_limit_exceeded(&mixed, Version::Amf0);
+
+        // Values within the limit are decoded.
+        let mut nested = Amf3Value::Null;
+        for _ in 0..200 {
+            nested = Amf3Value::Array {
+                assoc_entries: vec![],
+                dense_entries: vec![nested],
+            };
+        }
+        let mut buf = Vec::new();
+        nested.write_to(&mut buf).unwrap();
+        assert_eq!(Amf3Value::read_from(&mut &buf[..]).unwrap(), nested);
+    }
+
+    #[test]
+    fn dumps_encoded_bytes_as_hex() {
+        let dump = Value::from(Amf0Value::Number(3.5))
+            .to_hex_dump(Version::Amf0)
+            .unwrap();
+        assert_eq!(
+            dump,
+            "00000000  00 40 0c 00 00 00 00 00  00                       |.@.......|\n"
+        );
+
+        let dump = Value::from(Amf3Value::String("0123456789abcdefXYZ".to_string()))
+            .to_hex_dump(Version::Amf3)
+            .unwrap();
+        assert_eq!(
+            dump,
+            concat!(
+                "00000000  06 27 30 31 32 33 34 35  36 37 38 39 61 62 63 64  |.'0123456789abcd|\n",
+                "00000010  65 66 58 59 5a                                    |efXYZ|\n",
+            )
+        );
+
+        assert!(Value::from(Amf0Value::Null)
+            .to_hex_dump(Version::Amf3)
+            .is_err());
+    }
+
+    #[test]
+    fn approx_size_bounds_encoded_size() {
+        macro_rules! assert_within_bounds {
+            ($file:expr, $version:expr) => {{
+                let input = include_bytes!(concat!("testdata/", $file));
+                let value = Value::read_from(&mut &input[..], $version).unwrap();
+                let size = value.write_to_counting(io::sink()).unwrap();
+                let approx = value.approx_size();
+                assert!(size <= approx && approx <= size * 16, "{}", $file);
+            }};
+        }
+        assert_within_bounds!("amf0-number.bin", Version::Amf0);
+        assert_within_bounds!("amf0-long-string.bin", Version::Amf0);
+        assert_within_bounds!("amf0-ref-test.bin", Version::Amf0);
+        assert_within_bounds!("amf0-typed-object.bin", Version::Amf0);
+        assert_within_bounds!("amf0-ecma-ordinal-array.bin", Version::Amf0);
+        assert_within_bounds!("amf0-avmplus-object.bin", Version::Amf0);
+        assert_within_bounds!("amf3-dictionary.bin", Version::Amf3);
+        assert_within_bounds!("amf3-typed-object.bin", Version::Amf3);
+        assert_within_bounds!("amf3-vector-object.bin", Version::Amf3);
+        assert_within_bounds!("amf3-vector-double.bin", Version::Amf3);
+        assert_within_bounds!("amf3-byte-array.bin", Version::Amf3);
+        assert_within_bounds!("amf3-mixed-array.bin", Version::Amf3);
+    }
+
+    #[test]
+    fn compares_with_inner_values() {
+        let amf0 = Amf0Value::Number(1.0);
+        let amf3 = Amf3Value::Integer(1);
+        assert_eq!(Value::Amf0(amf0.clone()), amf0);
+        assert_eq!(amf0, Value::Amf0(amf0.clone()));
+        assert_eq!(Value::Amf3(amf3.clone()), amf3);
+        assert_eq!(amf3, Value::Amf3(amf3.clone()));
+
+        assert_ne!(Value::Amf0(amf0.clone()), Amf0Value::Number(2.0));
+        assert_ne!(Value::Amf3(amf3.clone()), amf0);
+        assert_ne!(Value::Amf0(amf0), amf3.clone());
+        assert_ne!(Value::Amf0(Amf0Value::AvmPlus(amf3.clone())), amf3);
+    }
+
+    #[test]
+    fn defaults_to_null() {
+        assert_eq!(Value::default(), Value::Amf0(Amf0Value::Null));
+        assert_eq!(Amf0Value::default(), Amf0Value::Null);
+        assert_eq!(Amf3Value::default(), Amf3Value::Null);
+
+        let mut entry = crate::Pair {
+            key: "foo".to_string(),
+            value: Amf3Value::Integer(1),
+        };
+        assert_eq!(std::mem::take(&mut entry.value), Amf3Value::Integer(1));
+        assert_eq!(entry.value, Amf3Value::Null);
+    }
+
+    #[test]
+    fn unwraps_inner_values() {
+        let amf0 = Amf0Value::Number(1.0);
+        let amf3 = Amf3Value::Integer(1);
+
+        let value = Value::from(amf0.clone());
+        assert_eq!(value.version(), Version::Amf0);
+        assert_eq!(value.clone().into_amf0(), Some(amf0));
+        assert_eq!(value.into_amf3(), None);
+
+        let value = Value::from(amf3.clone());
+        assert_eq!(value.version(), Version::Amf3);
+        assert_eq!(value.clone().into_amf0(), None);
+        assert_eq!(value.into_amf3(), Some(amf3.clone()));
+
+        let avmplus = Value::from(Amf0Value::AvmPlus(amf3));
+        assert_eq!(avmplus.version(), Version::Amf0);
+        assert_eq!(avmplus.into_amf3(), None);
+    }
+
+    #[test]
+    fn reads_with_warnings() {
+        let mut input = include_bytes!("testdata/amf0-ecma-array-mismatched-count.bin").to_vec();
+        let (value, warnings) = Value::read_from_with_warnings(&input[..], Version::Amf0).unwrap();
+        assert_eq!(value, Value::read_from(&input[..], Version::Amf0).unwrap());
+        assert_eq!(
+            warnings,
+            [Warning::EcmaArrayCountMismatch {
+                declared: 5,
+                actual: 1,
+                offset: 1
+            }]
+        );
+
+        let len = input.len();
+        input.extend_from_slice(&[0; 300]);
+        let (_, warnings) = Value::read_from_with_warnings(&input[..], Version::Amf0).unwrap();
+        assert_eq!(
+            warnings[1],
+            Warning::TrailingBytes {
+                len: 300,
+                offset: len
+            }
+        );
+
+        let input = [0x04, 0x01];
+        let (_, warnings) = Value::read_from_with_warnings(&input[..], Version::Amf3).unwrap();
+        assert_eq!(warnings, []);
+    }
+
+    #[test]
+    fn transcodes_between_formats() {
+        use crate::amf0;
+
+        let object = amf0::object(
+            [
+                ("name", amf0::string("foo")),
+                ("size", amf0::number(1.5)),
+                (
+                    "tags",
+                    amf0::array(vec![amf0::string("a"), Amf0Value::Null]),
+                ),
+            ]
+            .into_iter(),
+        );
+        let mut input = Vec::new();
+        object.write_to(&mut input).unwrap();
+
+        let mut amf3_buf = Vec::new();
+        Value::transcode(&input[..], Version::Amf0, Version::Amf3, &mut amf3_buf).unwrap();
+        let decoded = Value::read_from(&amf3_buf[..], Version::Amf3).unwrap();
+        assert_eq!(decoded, object.to_amf3().unwrap());
+        assert_eq!(decoded.type_name(), "Object");
+
+        let mut amf0_buf = Vec::new();
+        Value::transcode(&amf3_buf[..], Version::Amf3, Version::Amf0, &mut amf0_buf).unwrap();
+        assert_eq!(amf0_buf, input);
+
+        // The decoder rejects the marker by default, so this is only reachable with the options.
+        assert_eq!(
+            Amf0Value::Unsupported { marker: 0x0D }.to_amf3(),
+            Err(DecodeError::UnrepresentableValue {
+                type_name: "Unsupported",
+                version: Version::Amf3
+            })
+        );
+    }
+}
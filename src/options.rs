@@ -0,0 +1,226 @@
+//! Encoding and decoding options.
+
+/// Options which control the behavior of the encoders.
+///
+/// `EncodeOptions::default()` produces the same output as `write_to`.
+///
+/// # Examples
+/// ```
+/// use amf::amf3::Value;
+/// use amf::options::EncodeOptions;
+///
+/// let mut options = EncodeOptions::default();
+/// options.use_string_references = true;
+///
+/// let value = Value::Array {
+///     assoc_entries: vec![],
+///     dense_entries: vec![Value::String("foo".to_owned()), Value::String("foo".to_owned())],
+/// };
+/// let mut plain = Vec::new();
+/// value.write_to(&mut plain).unwrap();
+/// let mut referenced = Vec::new();
+/// value.write_to_with_options(&mut referenced, &options).unwrap();
+///
+/// assert!(referenced.len() < plain.len());
+/// assert_eq!(Value::read_from(&mut &referenced[..]).unwrap(), value);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EncodeOptions {
+    /// If `true`, an AMF3 string which has already been encoded is written as a reference
+    /// to the string reference table.
+    ///
+    /// This applies to string values, object keys and class names (but not to XML values).
+    pub use_string_references: bool,
+
+    /// If `true`, a complex value (e.g., an object or an array) which is equal to an already encoded one
+    /// is written as a reference to the object reference table.
+    ///
+    /// Values are compared by `cmp_canonical`, because values have no identity in this crate.
+    /// In AMF0, only the first 65536 complex values can be referenced.
+    pub use_object_references: bool,
+
+    /// If `true`, the trait of an AMF3 object which has the same class name, dynamic-ness and
+    /// sealed member names as an already encoded object is written as a reference
+    /// to the trait reference table.
+    pub use_trait_references: bool,
+
+    /// If `true`, an AMF0 string is always written with the `LONG_STRING` marker,
+    /// even if it is shorter than 65536 bytes.
+    ///
+    /// This applies to string values only (object keys and class names are not affected).
+    pub force_long_strings: bool,
+
+    /// If `Some`, the time zone field of every AMF0 date is written as the given offset
+    /// instead of the `time_zone` of the value.
+    ///
+    /// The field is informational (the specification says it should be zero),
+    /// but some peers expect a specific offset.
+    /// By default, the `time_zone` of each value is written as is.
+    pub amf0_date_time_zone: Option<i16>,
+
+    /// If `true`, the top-level `Encoder` converts a value of the other format instead of rejecting it.
+    ///
+    /// An AMF3 value is written to an AMF0 encoder wrapped in an `AVMPLUS_OBJECT` marker,
+    /// and an AMF0 `AvmPlus` value is written to an AMF3 encoder as its AMF3 value
+    /// (as `Value::write_as` does).
+    /// Other AMF0 values are always rejected by an AMF3 encoder.
+    pub wrap_avmplus: bool,
+
+    /// If `true`, the `declared_count` of an AMF0 ECMA array is written as its count
+    /// instead of the number of its entries.
+    ///
+    /// Together with `DecodeOptions::preserve_ecma_array_counts`,
+    /// this makes an ECMA array with an incorrect count round-trip byte for byte.
+    pub preserve_ecma_array_counts: bool,
+}
+
+/// Options which control the behavior of the decoders.
+///
+/// `DecodeOptions::default()` behaves the same as `read_from`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// If `true`, the AMF3 reference tables of an AMF0 decoder persist across `AVMPLUS_OBJECT` markers
+    /// (until `clear_reference_table` is called).
+    ///
+    /// By default, each AMF3 value wrapped in an `AVMPLUS_OBJECT` marker is decoded with empty tables.
+    /// Some producers expect the tables to be shared by all the AMF3 values in one message body.
+    pub share_avmplus_reference_tables: bool,
+
+    /// If `true`, an AMF0 `RECORDSET` marker is decoded as a marker followed by an anonymous object body,
+    /// and returned as a typed `Object` whose class name is `"RecordSet"`.
+    ///
+    /// The AMF0 specification reserves the marker without defining its body,
+    /// so this is a best-effort guess based on Flash Remoting recordsets
+    /// (an object which has a cursor, the column names and the rows).
+    /// By default, `DecodeError::Unsupported` is returned for the marker.
+    pub decode_recordsets: bool,
+
+    /// If `true`, the AMF0 `MOVIECLIP`, `UNSUPPORTED` and `RECORDSET` markers are decoded
+    /// as `amf0::Value::Unsupported` placeholders instead of returning `DecodeError::Unsupported`.
+    ///
+    /// Those markers are assumed to have no body (as the `UNSUPPORTED` marker),
+    /// so the decoding continues just after the marker.
+    /// Unknown markers (and the AMF3 ones) have no self-delimiting form,
+    /// so `DecodeError::Unknown` is still returned for them.
+    /// If `decode_recordsets` is also enabled, it takes precedence for `RECORDSET`.
+    pub tolerate_unsupported: bool,
+
+    /// The maximum number of the entries of an object, if any.
+    ///
+    /// This limits the entries which are terminated by an end marker instead of being counted up front
+    /// (i.e., the members of AMF0 objects and ECMA arrays, and the dynamic members and
+    /// the associative part of AMF3 objects and arrays).
+    /// `DecodeError::CollectionTooLarge` is returned if the limit is exceeded.
+    pub max_object_entries: Option<usize>,
+
+    /// If `true`, the size in the header of an inline AMF3 date is ignored.
+    ///
+    /// The specification requires the size to be zero (i.e., the header is `0x01`),
+    /// and by default `DecodeError::InvalidDateHeader` is returned for other sizes.
+    /// The borrowing decoders (`ValueRef`) always check the header.
+    pub lenient_amf3_dates: bool,
+
+    /// If `true`, `DecodeError::NonFiniteNumber` is returned for a `NaN` or infinite number
+    /// (an AMF0 `Number`, an AMF3 `Double` or an element of an AMF3 `DoubleVector`).
+    ///
+    /// Such numbers are valid in AMF, but cannot be represented in JSON, for example.
+    /// Dates are always checked (see `DecodeError::InvalidDate`).
+    /// The borrowing decoders (`ValueRef`) do not check numbers.
+    pub reject_non_finite_numbers: bool,
+
+    /// If `true`, the decoders collect non-fatal anomalies as `Warning`s
+    /// (see `amf0::Decoder::take_warnings` and `Value::read_from_with_warnings`).
+    ///
+    /// Checking duplicate keys takes time proportional to the square of the number of the members,
+    /// so this is disabled by default.
+    pub collect_warnings: bool,
+
+    /// If `true`, `DecodeError::DuplicateKey` is returned for an object
+    /// which has more than one member with the same key.
+    ///
+    /// This applies to the members of AMF0 objects and ECMA arrays,
+    /// and to the dynamic members (including those which collide with the sealed members)
+    /// and the associative part of AMF3 objects and arrays.
+    /// By default, all the members are kept in the entries
+    /// (and reported as `Warning::DuplicateKey` if `collect_warnings` is enabled).
+    /// As with `collect_warnings`, the check takes quadratic time in the number of the members.
+    pub reject_duplicate_keys: bool,
+
+    /// The maximum total number of bytes which a decoder may allocate for one value, if any.
+    ///
+    /// Unlike the per-collection limits, this bounds the memory used by a message which consists of
+    /// many moderately sized parts.
+    /// The decoders charge the bytes of the strings and the byte arrays (including those copied
+    /// from the reference tables), the elements of the vectors, and
+    /// `size_of` an entry for each entry of the collections before allocating them,
+    /// and a value copied from the object reference table is charged by its `approx_size`.
+    /// The total is reset at the start of each `decode` call.
+    /// `DecodeError::AllocationBudgetExceeded` is returned if the limit is exceeded.
+    /// The borrowing decoders (`ValueRef`) do not check the total.
+    /// `Value::read_from_async_with_options` also limits the number of the buffered input bytes by this.
+    pub max_total_bytes: Option<usize>,
+
+    /// If `true`, the count of an AMF0 ECMA array is kept in `amf0::Value::EcmaArray::declared_count`
+    /// if it differs from the number of the decoded entries.
+    ///
+    /// By default, the count is ignored (as most decoders do), so equal arrays decode to equal values.
+    /// A mismatch is reported as `Warning::EcmaArrayCountMismatch` regardless of this option.
+    /// The borrowing decoders (`ValueRef`) always ignore the count.
+    pub preserve_ecma_array_counts: bool,
+
+    /// If `true`, a decoder keeps the keys, the class names and the short string values
+    /// which it has decoded, and copies a repeated one from them.
+    ///
+    /// A repeated string is looked up by its raw bytes, so it is not validated as UTF-8 again
+    /// (the kept strings persist across `decode` calls, and are shared with the AMF3 values in `AvmPlus`).
+    /// Note that the fields of the values are `String`s, so each decoded string still has its own allocation:
+    /// this does not reduce the memory held by the decoded values.
+    /// The lookup costs more than validating a short ASCII string,
+    /// so this pays off only for long or non-ASCII strings which are repeated many times
+    /// (see the `decode_amf0_repeated_*` benches).
+    /// Only the first 1024 distinct strings of up to 256 bytes are kept,
+    /// which bounds the memory of a decoder fed with untrusted input.
+    /// The borrowing decoders (`ValueRef`) do not allocate the strings in the first place.
+    pub intern_strings: bool,
+}
+impl DecodeOptions {
+    /// Returns the limits in these options.
+    pub fn limits(&self) -> DecodeLimits {
+        DecodeLimits {
+            max_object_entries: self.max_object_entries,
+            max_total_bytes: self.max_total_bytes,
+        }
+    }
+
+    /// Replaces the limits in these options with `limits`.
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        self.max_object_entries = limits.max_object_entries;
+        self.max_total_bytes = limits.max_total_bytes;
+    }
+}
+
+/// The limits of the decoders which guard against untrusted input.
+///
+/// These are a subset of `DecodeOptions`, which can be changed on an existing decoder
+/// (e.g., by `amf0::Decoder::set_limits`).
+/// `DecodeLimits::default()` has no limits.
+///
+/// # Examples
+/// ```
+/// use amf::options::DecodeLimits;
+///
+/// let mut limits = DecodeLimits::default();
+/// limits.max_object_entries = Some(64);
+/// limits.max_total_bytes = Some(64 * 1024);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DecodeLimits {
+    /// See `DecodeOptions::max_object_entries`.
+    pub max_object_entries: Option<usize>,
+
+    /// See `DecodeOptions::max_total_bytes`.
+    pub max_total_bytes: Option<usize>,
+}
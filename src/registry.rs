@@ -0,0 +1,302 @@
+//! A registry which maps the class names of typed objects to Rust types.
+//!
+//! # Examples
+//! ```
+//! use amf::amf0::{Decoder, Encoder, Value};
+//! use amf::registry::ClassRegistry;
+//! use amf::Pair;
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct User {
+//!     name: String,
+//! }
+//!
+//! let mut registry = ClassRegistry::new();
+//! registry.register(
+//!     "com.example.User",
+//!     |user: &User| vec![Pair { key: "name".to_owned(), value: Value::String(user.name.clone()) }],
+//!     |entries: &[Pair<String, Value>]| {
+//!         let name = entries.iter().find(|p| p.key == "name")?.value.try_as_str()?;
+//!         Some(User { name: name.to_owned() })
+//!     },
+//! );
+//!
+//! let mut buf = Vec::new();
+//! let user = User { name: "foo".to_owned() };
+//! Encoder::new(&mut buf).encode_with_registry(&user, &registry).unwrap();
+//!
+//! let decoded = Decoder::new(&buf[..]).decode_with_registry::<User>(&registry).unwrap();
+//! assert_eq!(decoded, Ok(user));
+//! ```
+use crate::{amf0, amf3, Pair};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::fmt;
+
+/// A value type which has typed objects (i.e., `amf0::Value` and `amf3::Value`).
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait TypedObject: Sized + private::Sealed {
+    /// Makes a typed object which has the given class name and entries.
+    ///
+    /// An AMF3 object is made with all the entries as sealed members.
+    fn from_typed_object(class_name: String, entries: Vec<Pair<String, Self>>) -> Self;
+
+    /// Returns the class name and the entries if this is a typed object.
+    fn as_typed_object(&self) -> Option<(&str, &[Pair<String, Self>])>;
+}
+impl TypedObject for amf0::Value {
+    fn from_typed_object(class_name: String, entries: Vec<Pair<String, Self>>) -> Self {
+        amf0::Value::Object {
+            class_name: Some(class_name),
+            entries,
+        }
+    }
+    fn as_typed_object(&self) -> Option<(&str, &[Pair<String, Self>])> {
+        match *self {
+            amf0::Value::Object {
+                class_name: Some(ref class_name),
+                ref entries,
+            } => Some((class_name, entries)),
+            _ => None,
+        }
+    }
+}
+impl TypedObject for amf3::Value {
+    fn from_typed_object(class_name: String, entries: Vec<Pair<String, Self>>) -> Self {
+        amf3::Value::Object {
+            class_name: Some(class_name),
+            sealed_count: entries.len(),
+            entries,
+        }
+    }
+    fn as_typed_object(&self) -> Option<(&str, &[Pair<String, Self>])> {
+        match *self {
+            amf3::Value::Object {
+                class_name: Some(ref class_name),
+                ref entries,
+                ..
+            } => Some((class_name, entries)),
+            _ => None,
+        }
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for crate::amf0::Value {}
+    impl Sealed for crate::amf3::Value {}
+}
+
+type ToEntries<V> = Box<dyn Fn(&dyn Any) -> Vec<Pair<String, V>>>;
+type FromEntries<V> = Box<dyn Fn(&[Pair<String, V>]) -> Option<Box<dyn Any>>>;
+
+struct Class<V> {
+    name: String,
+    type_id: TypeId,
+    to_entries: ToEntries<V>,
+    from_entries: FromEntries<V>,
+}
+
+/// A registry which maps the class names of typed objects to Rust types.
+///
+/// `V` is the value type of the objects (`amf0::Value` or `amf3::Value`).
+/// The registry is not used by the decoders and the encoders unless it is explicitly passed
+/// (e.g., to `amf0::Decoder::decode_with_registry`).
+pub struct ClassRegistry<V> {
+    classes: Vec<Class<V>>,
+}
+impl<V> ClassRegistry<V>
+where
+    V: TypedObject + 'static,
+{
+    /// Makes a new empty registry.
+    pub fn new() -> Self {
+        ClassRegistry {
+            classes: Vec::new(),
+        }
+    }
+
+    /// Registers the Rust type `T` as the class `class_name`.
+    ///
+    /// `to_entries` makes the entries of a typed object from a `T`,
+    /// and `from_entries` makes a `T` from the entries (returning `None` if they are malformed).
+    ///
+    /// If `class_name` or `T` is already registered, the previous registration is replaced.
+    pub fn register<T, F, G>(
+        &mut self,
+        class_name: &str,
+        to_entries: F,
+        from_entries: G,
+    ) -> &mut Self
+    where
+        T: Any,
+        F: Fn(&T) -> Vec<Pair<String, V>> + 'static,
+        G: Fn(&[Pair<String, V>]) -> Option<T> + 'static,
+    {
+        let type_id = TypeId::of::<T>();
+        self.classes
+            .retain(|c| c.name != class_name && c.type_id != type_id);
+        self.classes.push(Class {
+            name: class_name.into(),
+            type_id,
+            to_entries: Box::new(move |x| to_entries(x.downcast_ref().expect("never fails"))),
+            from_entries: Box::new(move |entries| {
+                from_entries(entries).map(|x| Box::new(x) as Box<dyn Any>)
+            }),
+        });
+        self
+    }
+
+    /// Returns the class name of `T` if it is registered.
+    pub fn class_name_of<T: Any>(&self) -> Option<&str> {
+        self.class_of::<T>().map(|c| c.name.as_str())
+    }
+
+    /// Converts `x` into a typed object.
+    ///
+    /// Returns `None` if `T` is not registered.
+    pub fn to_value<T: Any>(&self, x: &T) -> Option<V> {
+        let class = self.class_of::<T>()?;
+        Some(V::from_typed_object(
+            class.name.clone(),
+            (class.to_entries)(x),
+        ))
+    }
+
+    /// Converts a typed object into a `T`.
+    ///
+    /// If `value` is not a typed object of the class registered for `T`
+    /// or `from_entries` fails, the value is returned unchanged.
+    pub fn from_value<T: Any>(&self, value: V) -> Result<T, V> {
+        let converted = self.class_of::<T>().and_then(|class| {
+            let (class_name, entries) = value.as_typed_object()?;
+            if class_name != class.name {
+                return None;
+            }
+            (class.from_entries)(entries)
+        });
+        match converted {
+            Some(x) => Ok(*x.downcast().expect("never fails")),
+            None => Err(value),
+        }
+    }
+
+    fn class_of<T: Any>(&self) -> Option<&Class<V>> {
+        let type_id = TypeId::of::<T>();
+        self.classes.iter().find(|c| c.type_id == type_id)
+    }
+}
+impl<V> Default for ClassRegistry<V>
+where
+    V: TypedObject + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<V> fmt::Debug for ClassRegistry<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.classes.iter().map(|c| &c.name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClassRegistry;
+    use crate::{amf0, amf3, Pair};
+
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    fn point_registry() -> ClassRegistry<amf3::Value> {
+        let mut registry = ClassRegistry::new();
+        registry.register(
+            "com.example.Point",
+            |p: &Point| {
+                vec![
+                    Pair {
+                        key: "x".to_string(),
+                        value: amf3::Value::Double(p.x),
+                    },
+                    Pair {
+                        key: "y".to_string(),
+                        value: amf3::Value::Double(p.y),
+                    },
+                ]
+            },
+            |entries: &[Pair<String, amf3::Value>]| {
+                let get = |k| entries.iter().find(|p| p.key == k)?.value.try_as_f64();
+                Some(Point {
+                    x: get("x")?,
+                    y: get("y")?,
+                })
+            },
+        );
+        registry
+    }
+
+    #[test]
+    fn round_trips_registered_class() {
+        let registry = point_registry();
+        assert_eq!(registry.class_name_of::<Point>(), Some("com.example.Point"));
+
+        let point = Point { x: 1.0, y: -2.5 };
+        let mut buf = Vec::new();
+        amf3::Encoder::new(&mut buf)
+            .encode_with_registry(&point, &registry)
+            .unwrap();
+        let value = amf3::Value::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(
+            value,
+            amf3::Value::Object {
+                class_name: Some("com.example.Point".to_string()),
+                sealed_count: 2,
+                entries: vec![
+                    Pair {
+                        key: "x".to_string(),
+                        value: amf3::Value::Double(1.0),
+                    },
+                    Pair {
+                        key: "y".to_string(),
+                        value: amf3::Value::Double(-2.5),
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            amf3::Decoder::new(&buf[..]).decode_with_registry(&registry),
+            Ok(Ok(point))
+        );
+    }
+
+    #[test]
+    fn returns_unregistered_values_unchanged() {
+        let registry = point_registry();
+        let anonymous = amf3::Value::empty_object();
+        assert_eq!(
+            registry.from_value::<Point>(anonymous.clone()),
+            Err(anonymous)
+        );
+        let other = amf3::Value::Object {
+            class_name: Some("com.example.Other".to_string()),
+            sealed_count: 0,
+            entries: Vec::new(),
+        };
+        assert_eq!(registry.from_value::<Point>(other.clone()), Err(other));
+        assert_eq!(registry.to_value(&0u8), None);
+
+        let registry = ClassRegistry::<amf0::Value>::new();
+        assert_eq!(
+            amf0::Encoder::new(Vec::new()).encode_with_registry(&0u8, &registry),
+            Err(crate::error::EncodeError::UnregisteredType { type_name: "u8" })
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::io;
+use crate::{DecodeResult, Value, Version};
+use alloc::rc::Rc;
+use core::ops::Deref;
+
+/// A reference-counted `Value` which can be cloned cheaply.
+///
+/// Cloning a `Value` copies the whole tree (the decoders already copy referenced values
+/// into independent subtrees, so a `Value` never shares storage with another one).
+/// Cloning a `SharedValue` only increments a reference count,
+/// and the clones share the same (immutable) tree.
+/// Use `into_value` to get an independent `Value` back.
+///
+/// # Examples
+/// ```
+/// use amf::{Amf0Value, SharedValue, Value};
+///
+/// let shared = SharedValue::new(Value::from(Amf0Value::String("foo".to_string())));
+/// let cloned = shared.clone();
+/// assert!(SharedValue::ptr_eq(&shared, &cloned));
+/// assert_eq!(cloned.try_as_str(), Some("foo"));
+/// ```
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct SharedValue(Rc<Value>);
+impl SharedValue {
+    /// Makes a new instance which owns `value`.
+    pub fn new(value: Value) -> Self {
+        SharedValue(Rc::new(value))
+    }
+
+    /// Reads an AMF encoded value from `reader` (see `Value::read_from`).
+    pub fn read_from<R>(reader: R, version: Version) -> DecodeResult<Self>
+    where
+        R: io::Read,
+    {
+        Value::read_from(reader, version).map(Self::new)
+    }
+
+    /// Returns `true` if the two instances share the same tree.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Rc::ptr_eq(&this.0, &other.0)
+    }
+
+    /// Unwraps this instance, returning the value.
+    ///
+    /// If the tree is shared by other clones, it is deep-cloned.
+    pub fn into_value(self) -> Value {
+        Rc::try_unwrap(self.0).unwrap_or_else(|x| (*x).clone())
+    }
+}
+impl Deref for SharedValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.0
+    }
+}
+impl AsRef<Value> for SharedValue {
+    fn as_ref(&self) -> &Value {
+        &self.0
+    }
+}
+impl From<Value> for SharedValue {
+    fn from(f: Value) -> Self {
+        Self::new(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedValue;
+    use crate::{amf0, Value};
+
+    fn string_ptr(value: &Value) -> *const u8 {
+        match value {
+            Value::Amf0(amf0::Value::Array { entries }) => match entries[0] {
+                amf0::Value::String(ref s) => s.as_ptr(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn shares_storage_between_clones() {
+        let value = Value::Amf0(amf0::array(vec![amf0::string("foo")]));
+
+        let cloned = value.clone();
+        assert_eq!(cloned, value);
+        assert_ne!(string_ptr(&cloned), string_ptr(&value));
+
+        let shared = SharedValue::new(value.clone());
+        let shared_clone = shared.clone();
+        assert!(SharedValue::ptr_eq(&shared, &shared_clone));
+        assert_eq!(string_ptr(&shared), string_ptr(&shared_clone));
+
+        let independent = SharedValue::new(value.clone());
+        assert_eq!(independent, shared);
+        assert!(!SharedValue::ptr_eq(&independent, &shared));
+
+        let ptr = string_ptr(&shared);
+        drop(shared_clone);
+        let unwrapped = shared.into_value();
+        assert_eq!(unwrapped, value);
+        assert_eq!(string_ptr(&unwrapped), ptr);
+    }
+}
@@ -0,0 +1,108 @@
+//! Helpers for the tests of the crates which build on this crate.
+//!
+//! This module is available if the `testing` feature is enabled.
+use crate::{Value, Version};
+use alloc::vec::Vec;
+
+/// Encodes `value` in the format of `version`, decodes it and asserts that the result is equal to `value`.
+///
+/// The values are compared by `Value::cmp_canonical`, so a `NaN` number equals itself
+/// (and `0.0` differs from `-0.0`).
+///
+/// # Panics
+/// Panics if `value` is not a value of `version`, if the encoding or the decoding fails,
+/// or if the decoded value differs from `value`.
+/// The message includes a hex dump of the encoded bytes (see `Value::to_hex_dump`).
+///
+/// # Examples
+/// ```
+/// use amf::testing::assert_roundtrip;
+/// use amf::{Amf0Value, Value, Version};
+///
+/// assert_roundtrip(&Value::from(Amf0Value::String("foo".to_owned())), Version::Amf0);
+/// ```
+#[track_caller]
+pub fn assert_roundtrip(value: &Value, version: Version) {
+    assert_eq!(
+        value.version(),
+        version,
+        "the value is not a value of {:?}: {:?}",
+        version,
+        value
+    );
+    let mut buf = Vec::new();
+    if let Err(e) = value.write_to(&mut buf) {
+        panic!("failed to encode {:?}: {}", value, e);
+    }
+    let dump = crate::hex_dump(&buf);
+    match Value::read_from(&buf[..], version) {
+        Ok(decoded) => assert!(
+            decoded.cmp_canonical(value).is_eq(),
+            "the decoded value differs\n  expected: {:?}\n    actual: {:?}\nencoded bytes:\n{}",
+            value,
+            decoded,
+            dump
+        ),
+        Err(e) => panic!(
+            "failed to decode {:?}: {}\nencoded bytes:\n{}",
+            value, e, dump
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assert_roundtrip;
+    use crate::{Amf0Value, Amf3Value, Value, Version};
+
+    #[test]
+    fn round_trips_fixtures() {
+        let amf0: [&[u8]; 4] = [
+            include_bytes!("../testdata/amf0-number.bin"),
+            include_bytes!("../testdata/amf0-object.bin"),
+            include_bytes!("../testdata/amf0-typed-object.bin"),
+            include_bytes!("../testdata/amf0-avmplus-object.bin"),
+        ];
+        for input in amf0 {
+            assert_roundtrip(
+                &Value::read_from(input, Version::Amf0).unwrap(),
+                Version::Amf0,
+            );
+        }
+        let amf3: [&[u8]; 4] = [
+            include_bytes!("../testdata/amf3-dictionary.bin"),
+            include_bytes!("../testdata/amf3-associative-array.bin"),
+            include_bytes!("../testdata/amf3-vector-object.bin"),
+            include_bytes!("../testdata/amf3-byte-array.bin"),
+        ];
+        for input in amf3 {
+            assert_roundtrip(
+                &Value::read_from(input, Version::Amf3).unwrap(),
+                Version::Amf3,
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_nan() {
+        assert_roundtrip(&Value::from(Amf0Value::Number(f64::NAN)), Version::Amf0);
+        assert_roundtrip(&Value::from(Amf3Value::Double(f64::NAN)), Version::Amf3);
+    }
+
+    #[test]
+    #[should_panic(expected = "the decoded value differs")]
+    fn panics_on_mismatch() {
+        // The declared count is not re-emitted by default.
+        let value = Amf0Value::EcmaArray {
+            entries: Vec::new(),
+            declared_count: Some(3),
+        };
+        assert_roundtrip(&Value::from(value), Version::Amf0);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a value of Amf3")]
+    fn panics_on_version_mismatch() {
+        assert_roundtrip(&Value::from(Amf0Value::Null), Version::Amf3);
+    }
+}
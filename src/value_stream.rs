@@ -0,0 +1,141 @@
+use crate::io;
+use crate::{amf0, amf3, DecodeResult, Value, Version};
+
+/// An iterator which decodes back-to-back AMF values from a reader one at a time.
+///
+/// This is useful to process a recorded AMF log (e.g., a `BufReader<File>`) without reading it entirely.
+/// Each value is decoded with fresh reference tables (as `Value::read_from` does),
+/// and the iteration ends when the reader reaches its end just before a value.
+/// After an error is returned, the stream yields no more values.
+///
+/// # Examples
+/// ```
+/// use amf::{Amf0Value, Value, ValueStream, Version};
+///
+/// let values = [
+///     Value::from(Amf0Value::String("connect".to_string())),
+///     Value::from(Amf0Value::Number(1.0)),
+/// ];
+/// let mut buf = Vec::new();
+/// Value::write_all(&values, &mut buf, Version::Amf0).unwrap();
+///
+/// let decoded = ValueStream::new(&buf[..], Version::Amf0).collect::<Result<Vec<_>, _>>();
+/// assert_eq!(decoded.unwrap(), values);
+/// ```
+#[derive(Debug)]
+pub struct ValueStream<R> {
+    reader: R,
+    version: Version,
+    is_done: bool,
+}
+impl<R> ValueStream<R> {
+    /// Makes a new instance which decodes the values of `version` from `reader`.
+    pub fn new(reader: R, version: Version) -> Self {
+        ValueStream {
+            reader,
+            version,
+            is_done: false,
+        }
+    }
+
+    /// Unwraps this `ValueStream`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns the format version of the values.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+}
+impl<R> Iterator for ValueStream<R>
+where
+    R: io::Read,
+{
+    type Item = DecodeResult<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+        let mut marker = [0];
+        match self.reader.read_exact(&mut marker) {
+            Ok(()) => {}
+            Err(e) => {
+                self.is_done = true;
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    return None;
+                }
+                return Some(Err(e.into()));
+            }
+        }
+        let reader = PeekedReader {
+            peeked: Some(marker[0]),
+            inner: &mut self.reader,
+        };
+        let result = match self.version {
+            Version::Amf0 => amf0::Decoder::new(reader).decode().map(Value::Amf0),
+            Version::Amf3 => amf3::Decoder::new(reader).decode().map(Value::Amf3),
+        };
+        if result.is_err() {
+            self.is_done = true;
+        }
+        Some(result)
+    }
+}
+
+/// A reader which yields a byte which has already been read from `inner` before `inner`'s bytes.
+struct PeekedReader<R> {
+    peeked: Option<u8>,
+    inner: R,
+}
+impl<R: io::Read> io::Read for PeekedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match (self.peeked, buf.first_mut()) {
+            (Some(b), Some(first)) => {
+                *first = b;
+                self.peeked = None;
+                Ok(1)
+            }
+            _ => self.inner.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueStream;
+    use crate::{Value, Version};
+
+    #[test]
+    fn streams_concatenated_values() {
+        let files: [&[u8]; 3] = [
+            include_bytes!("testdata/amf0-number.bin"),
+            include_bytes!("testdata/amf0-ref-test.bin"),
+            include_bytes!("testdata/amf0-ref-test.bin"),
+        ];
+        let expected = files
+            .iter()
+            .map(|f| Value::read_from(&mut &f[..], Version::Amf0).unwrap())
+            .collect::<Vec<_>>();
+
+        let input = files.concat();
+        let stream = ValueStream::new(&input[..], Version::Amf0);
+        let decoded = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(decoded, expected);
+
+        assert_eq!(ValueStream::new(&[][..], Version::Amf3).count(), 0);
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let input = [0x04, 0x01, 0x06];
+        let mut stream = ValueStream::new(&input[..], Version::Amf3);
+        assert_eq!(
+            stream.next().unwrap(),
+            Ok(Value::Amf3(crate::Amf3Value::Integer(1)))
+        );
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+}